@@ -0,0 +1,386 @@
+use flavor::{Flavor, Lock};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use {SliceBox, SlicePool, ThreadSafe};
+
+/// The latest buffer of a swap chain which has not published any.
+const UNPUBLISHED: usize = usize::MAX;
+
+/// The increment of a double buffer's state for each guard, above the bit
+/// of the front buffer's index.
+const GUARD: usize = 2;
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Allocates a double buffer of two allocations with `len` elements.
+  ///
+  /// Returns `None` if the pool has no memory for both buffers.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let pool = SlicePool::new(vec![0; 8]);
+  /// let buffer = pool.alloc_double_buffer(4).unwrap();
+  /// buffer.back().copy_from_slice(&[1, 2, 3, 4]);
+  /// assert!(buffer.swap());
+  /// assert_eq!(*buffer.front(), [1, 2, 3, 4]);
+  /// ```
+  pub fn alloc_double_buffer(&self, len: usize) -> Option<DoubleBuffer<T, F>> {
+    let front = self.alloc(len)?;
+    let back = self.alloc(len)?;
+    Some(DoubleBuffer {
+      buffers: [Lock::new(front), Lock::new(back)],
+      state: AtomicUsize::new(0),
+    })
+  }
+}
+
+/// Two equally sized allocations, of which one is presented while the other
+/// is prepared.
+///
+/// The front buffer is read by consumers, while the back buffer is written
+/// by a producer, and `swap` exchanges them once the back buffer is ready.
+/// Swapping only flips an index, so it never copies or waits. Each buffer
+/// is locked while it is accessed, so a consumer still reading the previous
+/// front buffer delays the producer, rather than observing it being written.
+///
+/// Swapping fails while any guard of either buffer is held, so the front
+/// and back buffers never change under a thread holding one of them, and a
+/// thread may hold a guard of each.
+pub struct DoubleBuffer<T: 'static, F: Flavor = ThreadSafe> {
+  buffers: [F::Lock<SliceBox<T, F>>; 2],
+  /// The index of the front buffer, and the number of guards held.
+  state: AtomicUsize,
+}
+
+impl<T: 'static, F: Flavor> DoubleBuffer<T, F> {
+  /// Returns the number of elements of each buffer.
+  pub fn len(&self) -> usize {
+    self.front().len()
+  }
+
+  /// Returns whether the buffers are empty.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Locks the front buffer, which is presented to consumers.
+  pub fn front(&self) -> BufferGuard<'_, T, F> {
+    self.lock(0)
+  }
+
+  /// Locks the back buffer, which is prepared by the producer.
+  pub fn back(&self) -> BufferGuard<'_, T, F> {
+    self.lock(1)
+  }
+
+  /// Exchanges the front and back buffers.
+  ///
+  /// Returns `false` without swapping if any guard of the buffers is held,
+  /// in which case the producer may retry once they are released.
+  pub fn swap(&self) -> bool {
+    let state = self.state.load(Ordering::Relaxed);
+    state < GUARD
+      && self
+        .state
+        .compare_exchange(state, state ^ 1, Ordering::AcqRel, Ordering::Relaxed)
+        .is_ok()
+  }
+
+  /// Locks the buffer at an index relative to the front buffer.
+  fn lock(&self, relative: usize) -> BufferGuard<'_, T, F> {
+    // The guard is counted before the index is read, so it cannot change
+    let state = self.state.fetch_add(GUARD, Ordering::Acquire);
+    let held = Held(&self.state);
+    BufferGuard {
+      data: self.buffers[(state & 1) ^ relative].lock(),
+      _held: Some(held),
+    }
+  }
+
+  /// Returns the front and back buffers.
+  pub fn into_inner(self) -> (SliceBox<T, F>, SliceBox<T, F>) {
+    let [first, second] = self.buffers;
+    let (first, second) = (first.into_inner(), second.into_inner());
+    match self.state.into_inner() & 1 {
+      0 => (first, second),
+      _ => (second, first),
+    }
+  }
+}
+
+impl<T: fmt::Debug + 'static, F: Flavor> fmt::Debug for DoubleBuffer<T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("DoubleBuffer")
+      .field("front", &(self.state.load(Ordering::Relaxed) & 1))
+      .finish()
+  }
+}
+
+/// A ring of equally sized allocations, passing data from a producer to a
+/// consumer.
+///
+/// The producer acquires any buffer which is neither in use, nor the latest
+/// published one, fills it, and publishes it. The consumer always reads the
+/// latest published buffer, skipping any it did not keep up with. With
+/// three or more buffers, the producer never has to wait for the consumer.
+/// The buffers are recycled in place, so no memory is allocated once the
+/// chain has been constructed.
+///
+/// ```
+/// use slice_pool::sync::{SlicePool, SwapChain};
+///
+/// let pool = SlicePool::new(vec![0; 12]);
+/// let chain = SwapChain::new(&pool, 4, 3).unwrap();
+/// assert!(chain.latest().is_none());
+///
+/// let mut buffer = chain.acquire().unwrap();
+/// buffer.copy_from_slice(&[1, 2, 3, 4]);
+/// buffer.publish();
+/// assert_eq!(*chain.latest().unwrap(), [1, 2, 3, 4]);
+/// ```
+pub struct SwapChain<T: 'static, F: Flavor = ThreadSafe> {
+  buffers: Vec<F::Lock<SliceBox<T, F>>>,
+  latest: AtomicUsize,
+}
+
+impl<T: 'static, F: Flavor> SwapChain<T, F> {
+  /// Allocates `count` buffers of `len` elements from a pool.
+  ///
+  /// Returns `None` if the pool has no memory for every buffer.
+  ///
+  /// # Panics
+  ///
+  /// Panics if there are fewer than two buffers.
+  pub fn new(pool: &SlicePool<T, F>, len: usize, count: usize) -> Option<Self> {
+    assert!(count >= 2, "swap chains require two buffers");
+    let buffers = (0..count)
+      .map(|_| pool.alloc(len).map(Lock::new))
+      .collect::<Option<Vec<_>>>()?;
+
+    Some(SwapChain {
+      buffers,
+      latest: AtomicUsize::new(UNPUBLISHED),
+    })
+  }
+
+  /// Returns the number of elements of each buffer.
+  pub fn len(&self) -> usize {
+    self.buffers[0].lock().len()
+  }
+
+  /// Returns whether the buffers are empty.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Returns the number of buffers.
+  pub fn count(&self) -> usize {
+    self.buffers.len()
+  }
+
+  /// Acquires a buffer for the producer to fill.
+  ///
+  /// The buffer keeps its previous contents. Returns `None` if every buffer
+  /// other than the latest published one is in use.
+  pub fn acquire(&self) -> Option<PendingBuffer<'_, T, F>> {
+    let latest = self.latest.load(Ordering::Acquire);
+    self
+      .buffers
+      .iter()
+      .enumerate()
+      .filter(|&(index, _)| index != latest)
+      .find_map(|(index, buffer)| {
+        Some(PendingBuffer {
+          chain: self,
+          index,
+          data: BufferGuard {
+            data: buffer.try_lock()?,
+            _held: None,
+          },
+        })
+      })
+  }
+
+  /// Locks the latest published buffer for the consumer to read, or returns
+  /// `None` if none has been published.
+  pub fn latest(&self) -> Option<BufferGuard<'_, T, F>> {
+    let latest = self.latest.load(Ordering::Acquire);
+    self.buffers.get(latest).map(|buffer| BufferGuard {
+      data: buffer.lock(),
+      _held: None,
+    })
+  }
+
+  /// Returns the buffers.
+  pub fn into_inner(self) -> Vec<SliceBox<T, F>> {
+    self.buffers.into_iter().map(Lock::into_inner).collect()
+  }
+}
+
+impl<T: 'static, F: Flavor> fmt::Debug for SwapChain<T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let latest = self.latest.load(Ordering::Relaxed);
+    f.debug_struct("SwapChain")
+      .field("count", &self.buffers.len())
+      .field(
+        "latest",
+        &Some(latest).filter(|&latest| latest != UNPUBLISHED),
+      )
+      .finish()
+  }
+}
+
+/// A buffer of a `SwapChain` acquired by the producer.
+///
+/// Dropping the buffer without publishing it discards its contents.
+pub struct PendingBuffer<'a, T: 'static, F: Flavor> {
+  chain: &'a SwapChain<T, F>,
+  index: usize,
+  data: BufferGuard<'a, T, F>,
+}
+
+impl<'a, T: 'static, F: Flavor> PendingBuffer<'a, T, F> {
+  /// Publishes the buffer as the latest one, for the consumer to read.
+  pub fn publish(self) {
+    self.chain.latest.store(self.index, Ordering::Release);
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> Deref for PendingBuffer<'a, T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    &self.data
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> DerefMut for PendingBuffer<'a, T, F> {
+  fn deref_mut(&mut self) -> &mut [T] {
+    &mut self.data
+  }
+}
+
+impl<'a, T: fmt::Debug + 'static, F: Flavor> fmt::Debug for PendingBuffer<'a, T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+/// A locked buffer of a `DoubleBuffer` or a `SwapChain`.
+pub struct BufferGuard<'a, T: 'static, F: Flavor> {
+  data: <F::Lock<SliceBox<T, F>> as Lock<SliceBox<T, F>>>::Guard<'a>,
+  /// The count of guards of a double buffer, released after the lock.
+  _held: Option<Held<'a>>,
+}
+
+impl<'a, T: 'static, F: Flavor> Deref for BufferGuard<'a, T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    &self.data
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> DerefMut for BufferGuard<'a, T, F> {
+  fn deref_mut(&mut self) -> &mut [T] {
+    &mut self.data
+  }
+}
+
+/// A guard counted in the state of a double buffer, until dropped.
+struct Held<'a>(&'a AtomicUsize);
+
+impl<'a> Drop for Held<'a> {
+  fn drop(&mut self) {
+    self.0.fetch_sub(GUARD, Ordering::Release);
+  }
+}
+
+impl<'a, T: fmt::Debug + 'static, F: Flavor> fmt::Debug for BufferGuard<'a, T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Arc;
+  use std::thread;
+
+  #[test]
+  fn double_buffer_swap() {
+    let pool = SlicePool::<_, ThreadSafe>::new(vec![0; 6]);
+    assert!(pool.alloc_double_buffer(4).is_none());
+    assert_eq!(pool.stats().used, 0);
+
+    let buffer = Arc::new(pool.alloc_double_buffer(3).unwrap());
+    let producer = {
+      let buffer = buffer.clone();
+      thread::spawn(move || {
+        for value in 1..=100 {
+          buffer
+            .back()
+            .iter_mut()
+            .for_each(|element| *element = value);
+          while !buffer.swap() {
+            thread::yield_now();
+          }
+        }
+      })
+    };
+
+    // Consumers never observe a partially written buffer
+    for _ in 0..100 {
+      let front = buffer.front();
+      assert!(front.iter().all(|&element| element == front[0]));
+    }
+    producer.join().unwrap();
+
+    // Swapping fails while a guard is held, so both can be held at once
+    let front = buffer.front();
+    let back = buffer.back();
+    assert!(!buffer.swap());
+    assert_ne!(front.as_ptr(), back.as_ptr());
+    drop((front, back));
+
+    let buffer = Arc::try_unwrap(buffer).unwrap();
+    assert_eq!(buffer.len(), 3);
+    let (front, back) = buffer.into_inner();
+    assert_eq!((&*front, &*back), (&[100; 3][..], &[99; 3][..]));
+  }
+
+  #[test]
+  fn swap_chain_recycle() {
+    let pool = SlicePool::<_, ThreadSafe>::new(vec![0; 6]);
+    assert!(SwapChain::new(&pool, 2, 4).is_none());
+    let chain = SwapChain::new(&pool, 2, 3).unwrap();
+    assert_eq!((chain.count(), pool.stats().used), (3, 6));
+
+    // The producer skips the buffers in use, and the latest published one
+    let reading = {
+      let mut buffer = chain.acquire().unwrap();
+      buffer.copy_from_slice(&[1, 1]);
+      buffer.publish();
+      chain.latest().unwrap()
+    };
+    let mut pending = chain.acquire().unwrap();
+    pending.copy_from_slice(&[2, 2]);
+    let mut discarded = chain.acquire().unwrap();
+    discarded.copy_from_slice(&[3, 3]);
+    assert!(chain.acquire().is_none());
+    assert_eq!(*reading, [1, 1]);
+
+    drop((reading, discarded));
+    pending.publish();
+    assert_eq!(*chain.latest().unwrap(), [2, 2]);
+    assert_eq!(*chain.acquire().unwrap(), [1, 1]);
+
+    let buffers = chain.into_inner();
+    assert_eq!(
+      buffers.iter().map(|data| data[0]).collect::<Vec<_>>(),
+      [1, 2, 3]
+    );
+  }
+}
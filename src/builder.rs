@@ -0,0 +1,109 @@
+use flavor::{Erase, Flavor};
+use std::fmt;
+use {SlicePool, Strategy, ThreadSafe};
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Returns a builder of a pool from a sliceable object.
+  ///
+  /// ```
+  /// use slice_pool::{sync::SlicePool, Strategy};
+  ///
+  /// let pool = SlicePool::builder(vec![0; 64])
+  ///   .strategy(Strategy::FirstFit)
+  ///   .granularity(8)
+  ///   .max_allocations(2)
+  ///   .build();
+  /// let _data = (pool.alloc(3).unwrap(), pool.alloc(3).unwrap());
+  /// assert!(pool.alloc(3).is_none());
+  /// assert_eq!(pool.used_len(), 16);
+  /// ```
+  pub fn builder<S: Erase<F, F::Slice<T>>>(slice: S) -> SlicePoolBuilder<T, F> {
+    SlicePoolBuilder {
+      pool: SlicePool::new(slice),
+    }
+  }
+}
+
+/// A builder of a `SlicePool`, configuring it before any allocation.
+///
+/// Every setting defaults to the one of a pool constructed with `new`.
+pub struct SlicePoolBuilder<T: 'static, F: Flavor = ThreadSafe> {
+  pool: SlicePool<T, F>,
+}
+
+impl<T: 'static, F: Flavor> SlicePoolBuilder<T, F> {
+  /// Sets the strategy used for placing allocations.
+  pub fn strategy(self, strategy: Strategy) -> Self {
+    self.pool.set_strategy(strategy);
+    self
+  }
+
+  /// Places allocations using free lists segregated by size, with each
+  /// bound being the largest size of a class.
+  pub fn size_classes(mut self, bounds: &[usize]) -> Self {
+    self.pool = self.pool.with_size_classes(bounds);
+    self
+  }
+
+  /// Rounds the sizes of allocations up to a multiple of `granularity`
+  /// elements.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `granularity` is zero.
+  pub fn granularity(mut self, granularity: usize) -> Self {
+    self.pool.set_granularity(granularity);
+    self
+  }
+
+  /// Limits the number of live allocations.
+  pub fn max_allocations(mut self, max: usize) -> Self {
+    self.pool.set_max_allocations(Some(max));
+    self
+  }
+
+  /// Keeps the low `bits` address bits of every allocation zero.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `bits` is not less than the number of bits in a pointer.
+  pub fn tag_bits(mut self, bits: u32) -> Self {
+    self.pool.set_tag_bits(bits);
+    self
+  }
+
+  /// Reserves the first `len` elements of the slice as a header.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the reserved regions exceed the size of the slice.
+  pub fn header(mut self, len: usize) -> Self {
+    self.pool = self.pool.with_header(len);
+    self
+  }
+
+  /// Reserves `len` elements for allocations of at most `threshold`
+  /// elements, following the header.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the reserved regions exceed the size of the slice.
+  pub fn small_region(mut self, len: usize, threshold: usize) -> Self {
+    self.pool = self.pool.with_small_region(len, threshold);
+    self
+  }
+
+  /// Constructs the pool.
+  pub fn build(self) -> SlicePool<T, F> {
+    self.pool
+  }
+}
+
+impl<T: 'static, F: Flavor> fmt::Debug for SlicePoolBuilder<T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("SlicePoolBuilder")
+      .field("len", &self.pool.len())
+      .field("strategy", &self.pool.strategy())
+      .finish()
+  }
+}
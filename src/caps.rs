@@ -0,0 +1,49 @@
+use std::ops::Range;
+
+/// The caps on the elements occupied by allocations of certain sizes.
+///
+/// Each cap covers a range of allocation sizes, and limits the number of
+/// elements which the live allocations within the range may occupy. Sizes
+/// are classified as they are when checked, so an allocation which has
+/// been split or grown counts towards the range of its current size.
+#[derive(Default)]
+pub struct SizeCaps(Vec<SizeCap>);
+
+/// A cap on the elements occupied by allocations within a size range.
+struct SizeCap {
+  sizes: Range<usize>,
+  limit: usize,
+}
+
+impl SizeCaps {
+  /// Sets the cap of a size range, replacing any previous cap of the same
+  /// range, or removes it.
+  pub fn set(&mut self, sizes: Range<usize>, limit: Option<usize>) {
+    self.0.retain(|cap| cap.sizes != sizes);
+    if let Some(limit) = limit {
+      self.0.push(SizeCap { sizes, limit });
+    }
+  }
+
+  /// Returns the limit of the first cap an allocation would exceed, given
+  /// the sizes of the live allocations.
+  ///
+  /// The live allocations are only enumerated if a cap covers the size.
+  pub fn exceeded<A, I>(&self, size: usize, allocations: A) -> Option<usize>
+  where
+    A: Fn() -> I,
+    I: Iterator<Item = usize>,
+  {
+    self
+      .0
+      .iter()
+      .filter(|cap| cap.sizes.contains(&size))
+      .find(|cap| {
+        let used = allocations()
+          .filter(|size| cap.sizes.contains(size))
+          .sum::<usize>();
+        used + size > cap.limit
+      })
+      .map(|cap| cap.limit)
+  }
+}
@@ -0,0 +1,1446 @@
+use caps::SizeCaps;
+use classes::{ClassStats, Classes};
+use counters::StatsRecorder;
+use deferred::{Deferred, Release};
+use flavor::{Flavor, Lock};
+use integrity::Integrity;
+#[cfg(feature = "lifetime-histogram")]
+use lifetimes::Lifetimes;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "paranoid")]
+use shadow::Shadow;
+use small::SmallRegion;
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "paranoid")]
+use std::thread;
+#[cfg(feature = "lifetime-histogram")]
+use std::time::Duration;
+use store::ChunkStore;
+use strategy::{self, Placer};
+use {
+  thread_tag, AllocPolicy, ChainError, Chunk, ChunkToken, Order, PoolError, PoolSnapshot,
+  PoolStats, RegionStats, Strategy,
+};
+
+/// The chunks of a slice and their bookkeeping.
+struct Chain {
+  chunks: ChunkStore,
+  placer: Placer,
+  recent: Option<usize>,
+  used: usize,
+  live: usize,
+  peak: PeakUsage,
+  generation: u64,
+  fast_path_hits: usize,
+  splits: u64,
+  coalesces: u64,
+  header: usize,
+  small: Option<SmallRegion>,
+  affinity: Option<AffinityStats>,
+  integrity: Option<Integrity>,
+  classes: Classes,
+  caps: SizeCaps,
+  /// The alignment of allocations placed by a policy, in elements.
+  align: usize,
+  /// The chunks described to a policy, reused across allocations.
+  infos: Vec<ChunkInfo>,
+  /// The reference allocator cross-checking the chunks.
+  #[cfg(feature = "paranoid")]
+  shadow: Shadow,
+}
+
+/// A locked chain, publishing its usage to the recorder once unlocked.
+struct ChainGuard<'a, F: Flavor> {
+  chain: <F::Lock<Chain> as Lock<Chain>>::Guard<'a>,
+  recorder: &'a StatsRecorder,
+}
+
+impl<'a, F: Flavor> Deref for ChainGuard<'a, F> {
+  type Target = Chain;
+
+  fn deref(&self) -> &Chain {
+    &self.chain
+  }
+}
+
+impl<'a, F: Flavor> DerefMut for ChainGuard<'a, F> {
+  fn deref_mut(&mut self) -> &mut Chain {
+    &mut self.chain
+  }
+}
+
+impl<'a, F: Flavor> Drop for ChainGuard<'a, F> {
+  fn drop(&mut self) {
+    self.recorder.set_used(self.chain.used);
+
+    // Panicking again would abort, so a failed operation is not verified
+    #[cfg(feature = "paranoid")]
+    if !thread::panicking() {
+      let chunks = ChunkChain::<F>::chunks_of(&self.chain);
+      self.chain.shadow.verify(&chunks);
+    }
+  }
+}
+
+/// Statistics of allocations preferring memory freed by the same thread.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct AffinityStats {
+  /// Allocations served from a chunk last freed by the allocating thread.
+  pub hits: usize,
+  /// Allocations which fell back to any other chunk.
+  pub misses: usize,
+}
+
+impl AffinityStats {
+  /// Returns the fraction of allocations which were hits.
+  pub fn hit_rate(&self) -> f64 {
+    match self.hits + self.misses {
+      0 => 0.0,
+      total => self.hits as f64 / total as f64,
+    }
+  }
+}
+
+/// Statistics of the fragmentation of the chunk chain of a pool.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FragmentationStats {
+  /// The number of free chunks.
+  pub free_chunks: usize,
+  /// The number of free elements.
+  pub free_len: usize,
+  /// The number of elements in the largest free chunk.
+  pub largest_free_chunk: usize,
+  /// The number of free chunks by size, where the chunks counted at index
+  /// `i` have between `2^i` and `2^(i + 1) - 1` elements.
+  pub distribution: Vec<usize>,
+}
+
+impl FragmentationStats {
+  /// Returns the fraction of free elements outside of the largest free
+  /// chunk.
+  ///
+  /// This is zero if the free elements are contiguous, and approaches one
+  /// as they are split into many small chunks.
+  pub fn ratio(&self) -> f64 {
+    match self.free_len {
+      0 => 0.0,
+      len => 1.0 - self.largest_free_chunk as f64 / len as f64,
+    }
+  }
+}
+
+/// The highest usage of a pool since it was constructed.
+///
+/// Empty allocations occupy no memory, so they are not counted.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PeakUsage {
+  /// The largest number of elements allocated at once.
+  pub used: usize,
+  /// The largest number of allocations alive at once.
+  pub live: usize,
+}
+
+/// The state of a chunk of a pool.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ChunkState {
+  /// The chunk is available for allocations.
+  Free,
+  /// The chunk is allocated.
+  Allocated,
+  /// The chunk is reserved, e.g. as the header of the pool.
+  Reserved,
+}
+
+/// A description of a chunk of a pool.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ChunkInfo {
+  /// The element offset of the chunk within the pool.
+  pub offset: usize,
+  /// The number of elements in the chunk.
+  pub len: usize,
+  /// The state of the chunk.
+  pub state: ChunkState,
+}
+
+impl ChunkInfo {
+  /// Returns whether the chunk is free.
+  pub fn is_free(&self) -> bool {
+    self.state == ChunkState::Free
+  }
+}
+
+/// A chunk chain, synchronized according to its flavor.
+pub struct ChunkChain<F: Flavor> {
+  /// The unique identifier of the pool using the chain.
+  id: u64,
+  state: F::Lock<Chain>,
+  /// The releases deferred while the chain was locked.
+  deferred: Deferred,
+  /// The usage of the chain, readable without locking it.
+  recorder: StatsRecorder,
+  /// The lifetimes of released allocations.
+  #[cfg(feature = "lifetime-histogram")]
+  lifetimes: Lifetimes,
+  /// The policy replacing the strategy, if any.
+  policy: F::Lock<Option<Box<F::Policy>>>,
+}
+
+impl<F: Flavor> ChunkChain<F> {
+  /// Locks the chain, publishing its usage once it is unlocked.
+  fn lock(&self) -> ChainGuard<'_, F> {
+    ChainGuard {
+      chain: self.state.lock(),
+      recorder: &self.recorder,
+    }
+  }
+
+  fn try_lock(&self) -> Option<ChainGuard<'_, F>> {
+    self.state.try_lock().map(|chain| ChainGuard {
+      chain,
+      recorder: &self.recorder,
+    })
+  }
+
+  pub fn new(size: usize) -> Self {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+    let placer = Placer::new(Strategy::default());
+    let mut chunks = ChunkStore::new(Some(Chunk::new(size)));
+    chunks.set_index(placer.index());
+
+    ChunkChain {
+      id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+      state: Lock::new(Chain {
+        chunks,
+        placer,
+        recent: None,
+        used: 0,
+        live: 0,
+        peak: PeakUsage::default(),
+        generation: 0,
+        fast_path_hits: 0,
+        splits: 0,
+        coalesces: 0,
+        header: 0,
+        small: None,
+        affinity: None,
+        integrity: None,
+        classes: Classes::default(),
+        caps: SizeCaps::default(),
+        align: 1,
+        infos: Vec::new(),
+        #[cfg(feature = "paranoid")]
+        shadow: Shadow::new(size, 0),
+      }),
+      deferred: Deferred::new(),
+      recorder: StatsRecorder::default(),
+      #[cfg(feature = "lifetime-histogram")]
+      lifetimes: Lifetimes::default(),
+      policy: Lock::new(None),
+    }
+  }
+
+  pub fn id(&self) -> u64 {
+    self.id
+  }
+
+  /// Converts the chain into another flavor, given a conversion of its
+  /// policy.
+  pub fn into_flavor<G: Flavor>(
+    self,
+    policy: fn(Box<F::Policy>) -> Box<G::Policy>,
+  ) -> ChunkChain<G> {
+    ChunkChain {
+      id: self.id,
+      state: Lock::new(self.state.into_inner()),
+      deferred: self.deferred,
+      recorder: self.recorder,
+      #[cfg(feature = "lifetime-histogram")]
+      lifetimes: self.lifetimes,
+      policy: Lock::new(self.policy.into_inner().map(policy)),
+    }
+  }
+
+  pub fn allocate(&self, size: usize) -> Option<Chunk> {
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+    self.drain(chain);
+
+    // Zero-sized allocations occupy no chunk, so they never fail
+    if size == 0 {
+      return Some(Chunk {
+        free: false,
+        ..Chunk::new(0)
+      });
+    }
+
+    if Self::exceeded_cap(chain, size).is_some() {
+      return None;
+    }
+
+    // Serve small allocations from the bitmap region, if there is one
+    let generation = chain.generation + 1;
+    let small = chain.small.as_mut();
+    if let Some(chunk) = small.and_then(|small| small.allocate(size, generation)) {
+      chain.generation = generation;
+      Self::charge(chain, size, 1);
+      #[cfg(feature = "paranoid")]
+      chain.shadow.mark(chunk.offset, size, ChunkState::Allocated);
+      Self::validate(chain, &chunk);
+      return Some(chunk);
+    }
+
+    if let Some(policy) = self.policy.lock().as_mut() {
+      let offset = Self::choose(chain, &mut **policy, size)?;
+      if chain
+        .chunks
+        .get(offset)
+        .is_some_and(|chunk| chunk.size > size)
+      {
+        chain.splits += 1;
+      }
+      let offset = Self::split(&mut chain.chunks, offset, size);
+      return Some(Self::claim(chain, offset, size));
+    }
+
+    // Reuse the most recently released chunk if it is an exact fit
+    let offset = match Self::recent_offset(chain, size) {
+      Some(offset) => {
+        chain.fast_path_hits += 1;
+        offset
+      }
+      None => {
+        let offset = match chain.affinity.as_mut() {
+          Some(stats) => {
+            // Prefer memory which is likely to be in this thread's cache
+            let tag = thread_tag();
+            let chunks = chain.chunks.iter_from(chain.placer.scan_start());
+            let (offset, hit) = chain
+              .placer
+              .find_preferring(chunks, size, |chunk| chunk.affinity == tag)?;
+            if hit {
+              stats.hits += 1;
+            } else {
+              stats.misses += 1;
+            }
+            offset
+          }
+          // Bump placement only ever uses the chunk at the end of the chain
+          None if chain.placer.active() == Strategy::Bump && !chain.placer.is_shuffled() => {
+            let chunk = chain.chunks.last();
+            chunk
+              .filter(|chunk| chunk.free && chunk.size >= size)?
+              .offset
+          }
+          None if chain.chunks.is_indexed() && !chain.placer.is_shuffled() => {
+            chain.chunks.find_free(size)?
+          }
+          None => {
+            let chunks = chain.chunks.iter_from(chain.placer.scan_start());
+            chain.placer.find(chunks, size)?
+          }
+        };
+        if chain
+          .chunks
+          .get(offset)
+          .is_some_and(|chunk| chunk.size > size)
+        {
+          chain.splits += 1;
+        }
+        if chain.placer.carve_tail() {
+          Self::split_tail(&mut chain.chunks, offset, size)
+        } else {
+          Self::split(&mut chain.chunks, offset, size)
+        }
+      }
+    };
+
+    Some(Self::claim(chain, offset, size))
+  }
+
+  /// Allocates a chunk as close as possible to an offset.
+  pub fn allocate_near(&self, hint: usize, size: usize) -> Option<Chunk> {
+    if size == 0 {
+      return self.allocate(0);
+    }
+
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+    self.drain(chain);
+    if Self::exceeded_cap(chain, size).is_some() {
+      return None;
+    }
+
+    let (offset, at) = strategy::nearest(chain.chunks.iter(), size, hint)?;
+    if chain
+      .chunks
+      .get(offset)
+      .is_some_and(|chunk| chunk.size > size)
+    {
+      chain.splits += 1;
+    }
+    Self::carve(&mut chain.chunks, offset, at, size);
+    Some(Self::claim(chain, at, size))
+  }
+
+  pub fn allocate_adjacent(&self, token: ChunkToken, size: usize, order: Order) -> Option<Chunk> {
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+    self.drain(chain);
+    if Self::exceeded_cap(chain, size).is_some() {
+      return None;
+    }
+    let chunks = &mut chain.chunks;
+
+    let chunk = Self::token_chunk(chunks, token)?;
+    let free = Self::free_adjacent(chunks, chunk.offset, order).filter(|_| size > 0)?;
+    if free.size < size {
+      return None;
+    }
+    if free.size > size {
+      chain.splits += 1;
+    }
+
+    let offset = match order {
+      Order::Preceding if free.size == size => free.offset,
+      Order::Preceding => {
+        // Carve the allocation from the end of the preceding chunk
+        let offset = chunks.update(free.offset, |free| {
+          free.size -= size;
+          free.offset + free.size
+        });
+        chunks.insert(Chunk::with_offset(size, offset));
+        offset
+      }
+      Order::Following => {
+        if free.size > size {
+          // Carve the allocation from the start of the following chunk
+          chunks.rekey(free.offset, |free| {
+            free.offset += size;
+            free.size -= size;
+          });
+          chunks.insert(Chunk::with_offset(size, free.offset));
+        }
+        free.offset
+      }
+    };
+
+    Some(Self::claim(chain, offset, size))
+  }
+
+  /// Releases an allocation, uncharging its class, and returns whether it
+  /// was released.
+  ///
+  /// If deferred releases are enabled and the chain is locked, the release
+  /// is queued for the next thread allocating from the chain instead, unless
+  /// the queue is full. The token is only checked once the queue is
+  /// drained, so this returns false.
+  pub fn release(&self, token: ChunkToken, class: Option<usize>) -> bool {
+    let mut chain = match self.try_lock() {
+      Some(chain) => chain,
+      None if self.deferred.is_enabled() && self.deferred.push(Release { token, class }) => {
+        return false;
+      }
+      None => self.lock(),
+    };
+
+    let chain = &mut *chain;
+    self.drain(chain);
+    Self::release_chunk(chain, token, class)
+  }
+
+  pub fn set_deferred(&self, enabled: bool) {
+    self.deferred.set_enabled(enabled);
+  }
+
+  /// Returns whether releases may currently be deferred.
+  pub fn is_deferred(&self) -> bool {
+    self.deferred.is_enabled()
+  }
+
+  pub fn recorder(&self) -> &StatsRecorder {
+    &self.recorder
+  }
+
+  #[cfg(feature = "lifetime-histogram")]
+  pub fn record_lifetime(&self, lifetime: Duration) {
+    self.lifetimes.record(lifetime);
+  }
+
+  /// Releases the allocations queued while the chain was locked.
+  fn drain(&self, chain: &mut Chain) {
+    for release in self.deferred.take() {
+      let released = Self::release_chunk(chain, release.token, release.class);
+      debug_assert!(
+        released || release.token.generation == 0,
+        "deferred release of an allocation unknown to the pool"
+      );
+    }
+  }
+
+  fn release_chunk(chain: &mut Chain, token: ChunkToken, class: Option<usize>) -> bool {
+    if let Some(small) = Self::small_region(chain, token) {
+      let size = small.release(token);
+      chain.used -= size.unwrap_or(0);
+      #[cfg(feature = "paranoid")]
+      chain
+        .shadow
+        .mark(token.offset, size.unwrap_or(0), ChunkState::Free);
+      Self::record(chain, token.offset, size.unwrap_or(0));
+      if let (Some(class), Some(size)) = (class, size) {
+        chain.classes.uncharge(class, size);
+      }
+      chain.live -= size.is_some() as usize;
+      return size.is_some();
+    }
+
+    let chunks = &mut chain.chunks;
+
+    let size = match Self::token_chunk(chunks, token) {
+      Some(chunk) => chunk.size,
+      None => return false,
+    };
+    let affinity = thread_tag();
+    chain.used -= size;
+    chain.live -= 1;
+    #[cfg(feature = "paranoid")]
+    chain.shadow.mark(token.offset, size, ChunkState::Free);
+    if let Some(integrity) = chain.integrity.as_mut() {
+      integrity.record(token.offset, size);
+    }
+    if let Some(class) = class {
+      chain.classes.uncharge(class, size);
+    }
+
+    let preceding = Self::free_adjacent(chunks, token.offset, Order::Preceding);
+    let following = Self::free_adjacent(chunks, token.offset, Order::Following);
+    if let Some(free) = preceding {
+      // Increase the preceding chunk's size, absorbing the following one
+      chain.coalesces += 1;
+      chunks.remove(token.offset);
+      let mut size = size;
+      if let Some(following) = following {
+        chain.coalesces += 1;
+        chunks.remove(following.offset);
+        size += following.size;
+      }
+      chunks.update(free.offset, |free| {
+        free.size += size;
+        free.affinity = affinity;
+      });
+    } else if let Some(free) = following {
+      // Increase the extent of the next chunk
+      chain.coalesces += 1;
+      chunks.remove(token.offset);
+      chunks.rekey(free.offset, |free| {
+        free.offset -= size;
+        free.size += size;
+        free.affinity = affinity;
+      });
+    } else {
+      // No free adjacent chunks, simply mark this one as free
+      chunks.update(token.offset, |chunk| {
+        chunk.free = true;
+        chunk.affinity = affinity;
+      });
+      chain.recent = Some(token.offset);
+    }
+    true
+  }
+
+  pub fn release_head(&self, token: ChunkToken, size: usize) {
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+
+    if let Some(small) = Self::small_region(chain, token) {
+      assert!(small.release_head(token, size), "releasing chunk head");
+      chain.used -= size;
+      #[cfg(feature = "paranoid")]
+      chain.shadow.mark(token.offset, size, ChunkState::Free);
+      Self::record(chain, token.offset, size);
+      return;
+    }
+
+    Self::record(chain, token.offset, size);
+
+    let chunks = &mut chain.chunks;
+
+    Self::token_chunk(chunks, token).expect("releasing chunk head");
+
+    // Shrink the allocated chunk from the front
+    chunks.rekey(token.offset, |chunk| {
+      chunk.offset += size;
+      chunk.size -= size;
+    });
+    chain.used -= size;
+    #[cfg(feature = "paranoid")]
+    chain.shadow.mark(token.offset, size, ChunkState::Free);
+
+    // The released head is followed by the rest of the allocation, so only
+    // the preceding chunk can be merged with it
+    if let Some(free) = Self::free_adjacent(chunks, token.offset + size, Order::Preceding) {
+      // Increase the preceding chunk's size
+      chain.coalesces += 1;
+      chunks.update(free.offset, |free| {
+        free.size += size;
+        free.affinity = thread_tag();
+      });
+    } else {
+      // Insert a new chunk representing the released memory
+      chunks.insert(Chunk {
+        affinity: thread_tag(),
+        ..Chunk::with_offset(size, token.offset)
+      });
+    }
+  }
+
+  pub fn release_tail(&self, token: ChunkToken, size: usize) {
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+
+    if let Some(small) = Self::small_region(chain, token) {
+      let offset = small
+        .release_tail(token, size)
+        .expect("releasing chunk tail");
+      chain.used -= size;
+      #[cfg(feature = "paranoid")]
+      chain.shadow.mark(offset, size, ChunkState::Free);
+      Self::record(chain, offset, size);
+      return;
+    }
+
+    let chunks = &mut chain.chunks;
+
+    Self::token_chunk(chunks, token).expect("releasing chunk tail");
+
+    // Shrink the allocated chunk from the back
+    let offset = chunks.update(token.offset, |chunk| {
+      chunk.size -= size;
+      chunk.offset + chunk.size
+    });
+    chain.used -= size;
+    #[cfg(feature = "paranoid")]
+    chain.shadow.mark(offset, size, ChunkState::Free);
+    if let Some(integrity) = chain.integrity.as_mut() {
+      integrity.record(offset, size);
+    }
+
+    // The released tail is preceded by the rest of the allocation, so only
+    // the following chunk can be merged with it
+    if let Some(free) = Self::free_adjacent(chunks, token.offset, Order::Following) {
+      // Increase the extent of the next chunk
+      chain.coalesces += 1;
+      chunks.rekey(free.offset, |free| {
+        free.offset -= size;
+        free.size += size;
+        free.affinity = thread_tag();
+      });
+    } else {
+      // Insert a new chunk representing the released memory
+      chunks.insert(Chunk {
+        affinity: thread_tag(),
+        ..Chunk::with_offset(size, offset)
+      });
+    }
+  }
+
+  /// Splits an allocation in two, returning the token of the second half.
+  pub fn split_allocation(&self, token: ChunkToken, mid: usize) -> ChunkToken {
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+    chain.generation += 1;
+    let generation = chain.generation;
+
+    if let Some(small) = Self::small_region(chain, token) {
+      assert!(small.split(token, mid, generation), "splitting chunk");
+    } else {
+      let chunks = &mut chain.chunks;
+      let chunk = Self::token_chunk(chunks, token).expect("splitting chunk");
+      chunks.update(token.offset, |chunk| chunk.size = mid);
+      chunks.insert(Chunk {
+        offset: token.offset + mid,
+        size: chunk.size - mid,
+        generation,
+        ..chunk
+      });
+    }
+    Self::charge(chain, 0, 1);
+
+    ChunkToken {
+      offset: token.offset + mid,
+      generation,
+    }
+  }
+
+  /// Extends an allocation into the free chunk following it, returning
+  /// whether there was enough free memory.
+  pub fn grow_allocation(
+    &self,
+    token: ChunkToken,
+    additional: usize,
+    class: Option<usize>,
+  ) -> bool {
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+    self.drain(chain);
+
+    let offset = if let Some(small) = Self::small_region(chain, token) {
+      match small.grow(token, additional) {
+        Some(offset) => offset,
+        None => return false,
+      }
+    } else {
+      let chunks = &mut chain.chunks;
+      let free = Self::token_chunk(chunks, token)
+        .and_then(|chunk| Self::free_adjacent(chunks, chunk.offset, Order::Following))
+        .filter(|free| free.size >= additional);
+      let free = match free {
+        Some(free) => free,
+        None => return false,
+      };
+
+      if free.size == additional {
+        chunks.remove(free.offset);
+      } else {
+        chunks.rekey(free.offset, |free| {
+          free.offset += additional;
+          free.size -= additional;
+        });
+      }
+      chunks.update(token.offset, |chunk| chunk.size += additional);
+      free.offset
+    };
+
+    Self::charge(chain, additional, 0);
+    #[cfg(feature = "paranoid")]
+    chain.shadow.mark(offset, additional, ChunkState::Allocated);
+    if let Some(class) = class {
+      chain.classes.charge(class, additional);
+    }
+    if let Some(integrity) = chain.integrity.as_mut() {
+      integrity.validate(offset, additional);
+    }
+    true
+  }
+
+  /// Fuses an allocation with the one immediately following it, returning
+  /// whether they were adjacent.
+  pub fn merge_allocations(&self, first: ChunkToken, second: ChunkToken) -> bool {
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+
+    let small = chain.small.as_mut();
+    let merged = if let Some(small) = small.filter(|small| small.contains(first.offset)) {
+      small.merge(first, second)
+    } else {
+      let chunks = &mut chain.chunks;
+      match (
+        Self::token_chunk(chunks, first),
+        Self::token_chunk(chunks, second),
+      ) {
+        (Some(head), Some(tail)) if head.offset + head.size == tail.offset => {
+          chunks.remove(tail.offset);
+          chunks.update(head.offset, |chunk| chunk.size += tail.size);
+          true
+        }
+        _ => false,
+      }
+    };
+
+    chain.live -= merged as usize;
+    merged
+  }
+
+  /// Returns the token of the allocation starting at an offset, if it has
+  /// a given size.
+  pub fn token_at(&self, offset: usize, size: usize) -> Option<ChunkToken> {
+    let chain = self.lock();
+    if let Some(small) = chain.small.as_ref().filter(|small| small.contains(offset)) {
+      return small.token_at(offset, size);
+    }
+
+    let chunk = chain.chunks.get(offset)?;
+    Some(chunk.token()).filter(|_| !chunk.free && chunk.size == size)
+  }
+
+  pub fn is_allocated(&self, token: ChunkToken, size: usize) -> bool {
+    let chain = self.lock();
+    if let Some(small) = chain
+      .small
+      .as_ref()
+      .filter(|small| small.contains(token.offset))
+    {
+      return small.is_allocated(token, size);
+    }
+    Self::token_chunk(&chain.chunks, token).is_some_and(|chunk| chunk.size == size)
+  }
+
+  /// Restores the chain to a single free chunk, if it has no live
+  /// allocations.
+  pub fn reset_empty(&self, size: usize) -> bool {
+    let mut chain = self.lock();
+    let chain = &mut *chain;
+    self.drain(chain);
+    if chain.live > 0 {
+      return false;
+    }
+
+    Self::reset(chain, size);
+    true
+  }
+
+  pub fn reserve_header(&self, size: usize, len: usize) {
+    let mut chain = self.lock();
+    assert_eq!(chain.used, 0, "reserving a region with live allocations");
+
+    // The small-object region follows the header
+    chain.header = len;
+    chain.small = chain
+      .small
+      .take()
+      .map(|small| SmallRegion::new(len, small.len(), small.threshold()));
+    Self::reset(&mut chain, size);
+  }
+
+  pub fn reserve_small(&self, size: usize, len: usize, threshold: usize) {
+    let mut chain = self.lock();
+    assert_eq!(chain.used, 0, "reserving a region with live allocations");
+
+    let start = chain.header;
+    chain.small = Some(SmallRegion::new(start, len, threshold));
+    Self::reset(&mut chain, size);
+  }
+
+  /// Appends free elements to the end of the chain, once its slice grew.
+  pub fn extend(&self, len: usize, additional: usize) {
+    let mut chain = self.lock();
+    #[cfg(feature = "paranoid")]
+    chain.shadow.extend(additional);
+    let chunks = &mut chain.chunks;
+
+    let last = chunks
+      .get(len)
+      .or_else(|| chunks.preceding(len))
+      .filter(|chunk| chunk.free && chunk.offset + chunk.size == len)
+      .map(|chunk| chunk.offset);
+    match last {
+      // Extend the free chunk at the end of the chain
+      Some(offset) => chunks.update(offset, |chunk| chunk.size += additional),
+      None => chunks.insert(Chunk::with_offset(additional, len)),
+    }
+  }
+
+  /// Returns the largest free range an allocation can currently use.
+  pub fn largest_free_block(&self) -> usize {
+    let chain = self.lock();
+    let small = chain.small.as_ref().map_or(0, |small| {
+      let free = small
+        .chunks()
+        .into_iter()
+        .filter(|chunk| chunk.state == ChunkState::Free);
+      free
+        .map(|chunk| chunk.len.min(small.threshold()))
+        .max()
+        .unwrap_or(0)
+    });
+
+    let free = chain.chunks.iter().filter(|chunk| chunk.free);
+    free.map(|chunk| chunk.size).max().unwrap_or(0).max(small)
+  }
+
+  /// Returns the largest allocation the chain could ever satisfy.
+  pub fn max_possible_alloc(&self, size: usize) -> usize {
+    let chain = self.lock();
+    let small = chain.small.as_ref();
+    let start = chain.header + small.map_or(0, SmallRegion::len);
+    let small = small.map_or(0, |small| small.len().min(small.threshold()));
+    (size - start).max(small)
+  }
+
+  /// Invokes a function with the offset and length of each free range of
+  /// the chain within a range, while the chain is locked.
+  pub fn with_free_ranges<G: FnMut(usize, usize)>(&self, range: Range<usize>, mut f: G) {
+    let chain = self.lock();
+    for chunk in chain.chunks.iter().filter(|chunk| chunk.free) {
+      let start = chunk.offset.max(range.start);
+      let end = (chunk.offset + chunk.size).min(range.end);
+      if start < end {
+        f(start, end - start);
+      }
+    }
+  }
+
+  pub fn chunks(&self) -> Vec<ChunkInfo> {
+    Self::chunks_of(&self.lock())
+  }
+
+  pub fn chunk_count(&self) -> usize {
+    Self::chunks_of(&self.lock()).len()
+  }
+
+  fn chunks_of(chain: &Chain) -> Vec<ChunkInfo> {
+    let header = Some(ChunkInfo {
+      offset: 0,
+      len: chain.header,
+      state: ChunkState::Reserved,
+    })
+    .filter(|header| header.len > 0);
+    let small = chain.small.as_ref().map(SmallRegion::chunks);
+    let chunks = chain.chunks.iter().map(Self::info);
+
+    header
+      .into_iter()
+      .chain(small.into_iter().flatten())
+      .chain(chunks)
+      .collect()
+  }
+
+  pub fn region_stats(&self) -> RegionStats {
+    let chain = self.lock();
+    let (small_allocations, small_used) = chain
+      .small
+      .as_ref()
+      .map_or((0, 0), |small| (small.allocations(), small.used()));
+
+    RegionStats {
+      small_allocations,
+      small_used,
+      chain_allocations: chain.chunks.iter().filter(|chunk| !chunk.free).count(),
+      chain_used: chain.used - small_used,
+    }
+  }
+
+  pub fn stats(&self, len: usize) -> PoolStats {
+    self.stats_of(&self.lock(), len)
+  }
+
+  fn stats_of(&self, chain: &Chain, len: usize) -> PoolStats {
+    let free = chain.chunks.iter().filter(|chunk| chunk.free);
+
+    PoolStats {
+      len,
+      used: chain.used,
+      free_chunks: free.clone().count(),
+      free_chunk_len: free.map(|chunk| chunk.size).sum(),
+      splits: chain.splits,
+      coalesces: chain.coalesces,
+      deferred: self.deferred.pending(),
+      #[cfg(feature = "lifetime-histogram")]
+      lifetimes: self.lifetimes.histogram(),
+    }
+  }
+
+  pub fn used(&self) -> usize {
+    self.lock().used
+  }
+
+  pub fn live(&self) -> usize {
+    self.lock().live
+  }
+
+  pub fn peak_usage(&self) -> PeakUsage {
+    self.lock().peak
+  }
+
+  /// Returns a snapshot of the chain's statistics, taken at once.
+  pub fn snapshot(&self, len: usize) -> PoolSnapshot {
+    let chain = self.lock();
+    PoolSnapshot {
+      stats: self.stats_of(&chain, len),
+      fragmentation: Self::fragmentation_of(&chain),
+      counters: self.recorder.counters(),
+      peak: chain.peak,
+      chunks: Self::chunks_of(&chain),
+    }
+  }
+
+  /// Verifies that the chunks are ordered, and cover `len` elements
+  /// without overlapping or leaving gaps.
+  pub fn check(&self, len: usize) -> Result<(), ChainError> {
+    // Both passes inspect the same state, so releases cannot interleave
+    let chain = self.lock();
+    let mut end = 0;
+    let mut previous = None;
+    for chunk in Self::chunks_of(&chain) {
+      if let Some(previous) = previous.filter(|&previous| chunk.offset < previous) {
+        return Err(ChainError::Unsorted {
+          offset: chunk.offset,
+          previous,
+        });
+      }
+      if chunk.offset < end {
+        return Err(ChainError::Overlapping {
+          offset: chunk.offset,
+          end,
+        });
+      }
+      if chunk.offset > end {
+        return Err(ChainError::Gap {
+          offset: end,
+          len: chunk.offset - end,
+        });
+      }
+      previous = Some(chunk.offset);
+      end += chunk.len;
+    }
+
+    if end != len {
+      return Err(ChainError::Length { covered: end, len });
+    }
+
+    // Releases coalesce with every free neighbor
+    let mut chunks = chain.chunks.iter().peekable();
+    while let Some(chunk) = chunks.next() {
+      if let Some(next) = chunks.peek().filter(|next| chunk.free && next.free) {
+        return Err(ChainError::Uncoalesced {
+          offset: next.offset,
+        });
+      }
+    }
+    Ok(())
+  }
+
+  pub fn set_policy(&self, policy: Option<Box<F::Policy>>) {
+    *self.policy.lock() = policy;
+  }
+
+  /// Sets the alignment, in elements, of allocations placed by a policy.
+  pub fn set_align(&self, align: usize) {
+    self.lock().align = align;
+  }
+
+  pub fn set_strategy(&self, strategy: Strategy) {
+    let mut chain = self.lock();
+    chain.placer.set_strategy(strategy);
+    let index = chain.placer.index();
+    chain.chunks.set_index(index);
+  }
+
+  pub fn set_size_classes(&self, bounds: Option<Vec<usize>>) {
+    let mut chain = self.lock();
+    chain.placer.set_size_classes(bounds);
+    let index = chain.placer.index();
+    chain.chunks.set_index(index);
+  }
+
+  pub fn set_shuffle(&self, seed: Option<u64>) {
+    self.lock().placer.set_shuffle(seed);
+  }
+
+  pub fn strategy(&self) -> Strategy {
+    self.lock().placer.strategy()
+  }
+
+  pub fn active_strategy(&self) -> Strategy {
+    self.lock().placer.active()
+  }
+
+  pub fn set_affinity(&self, enabled: bool) {
+    self.lock().affinity = Some(AffinityStats::default()).filter(|_| enabled);
+  }
+
+  pub fn fragmentation(&self) -> FragmentationStats {
+    Self::fragmentation_of(&self.lock())
+  }
+
+  fn fragmentation_of(chain: &Chain) -> FragmentationStats {
+    let mut stats = FragmentationStats::default();
+
+    for chunk in chain
+      .chunks
+      .iter()
+      .filter(|chunk| chunk.free && chunk.size > 0)
+    {
+      let bucket = chunk.size.ilog2() as usize;
+      if stats.distribution.len() <= bucket {
+        stats.distribution.resize(bucket + 1, 0);
+      }
+
+      stats.distribution[bucket] += 1;
+      stats.free_chunks += 1;
+      stats.free_len += chunk.size;
+      stats.largest_free_chunk = stats.largest_free_chunk.max(chunk.size);
+    }
+    stats
+  }
+
+  pub fn affinity_stats(&self) -> Option<AffinityStats> {
+    self.lock().affinity
+  }
+
+  pub fn fast_path_hits(&self) -> usize {
+    self.lock().fast_path_hits
+  }
+
+  pub fn set_class_weight(&self, class: &'static str, weight: usize) {
+    self.lock().classes.set_weight(class, weight);
+  }
+
+  pub fn set_admission_threshold(&self, threshold: Option<usize>) {
+    self.lock().classes.set_threshold(threshold);
+  }
+
+  pub fn admit(&self, class: &'static str, size: usize, len: usize) -> Option<usize> {
+    let mut chain = self.lock();
+    let used = chain.used;
+    chain.classes.admit(class, size, used, len)
+  }
+
+  pub fn set_size_cap(&self, sizes: Range<usize>, limit: Option<usize>) {
+    self.lock().caps.set(sizes, limit);
+  }
+
+  /// Returns the limit of the size cap an allocation would exceed, if any.
+  pub fn size_cap_exceeded(&self, size: usize) -> Option<usize> {
+    Self::exceeded_cap(&self.lock(), size)
+  }
+
+  pub fn uncharge(&self, class: usize, size: usize) {
+    self.lock().classes.uncharge(class, size);
+  }
+
+  /// Replaces the charge of an allocation to a class.
+  pub fn recharge(&self, class: usize, from: usize, to: usize) {
+    if from != to {
+      let classes = &mut self.lock().classes;
+      classes.uncharge(class, from);
+      classes.charge(class, to);
+    }
+  }
+
+  pub fn class_stats(&self) -> Vec<ClassStats> {
+    self.lock().classes.stats()
+  }
+
+  pub fn set_integrity(&self, integrity: Option<Integrity>) {
+    self.lock().integrity = integrity;
+  }
+
+  pub fn take_violations(&self) -> Vec<PoolError> {
+    let mut chain = self.lock();
+    chain
+      .integrity
+      .as_mut()
+      .map_or_else(Vec::new, Integrity::take_violations)
+  }
+
+  /// Splits a free chunk, returning the offset of the allocation.
+  fn split(chunks: &mut ChunkStore, offset: usize, size: usize) -> usize {
+    // Determine whether there is any memory surplus
+    let chunk = *chunks.get(offset).expect("free chunk");
+    let delta = chunk.size - size;
+    if delta == 0 {
+      return offset;
+    }
+
+    if let Some(free) = Self::free_adjacent(chunks, offset, Order::Preceding) {
+      // Increase the size of the preceding chunk
+      chunks.update(free.offset, |chunk| chunk.size += delta);
+
+      // Shift the offset of the allocated chunk
+      chunks.rekey(offset, |chunk| {
+        chunk.offset += delta;
+        chunk.size = size;
+      });
+      offset + delta
+    } else {
+      // Deduct the left over memory from the allocation
+      chunks.update(offset, |chunk| chunk.size = size);
+
+      if let Some(free) = Self::free_adjacent(chunks, offset, Order::Following) {
+        // Update the size and offset of the next chunk
+        chunks.rekey(free.offset, |free| {
+          free.offset -= delta;
+          free.size += delta;
+        });
+      } else {
+        // Insert a new chunk representing the surplus memory
+        chunks.insert(Chunk {
+          affinity: chunk.affinity,
+          ..Chunk::with_offset(delta, offset + size)
+        });
+      }
+      offset
+    }
+  }
+
+  /// Splits a free chunk from its end, returning the offset of the
+  /// allocation.
+  fn split_tail(chunks: &mut ChunkStore, offset: usize, size: usize) -> usize {
+    let chunk = *chunks.get(offset).expect("free chunk");
+    let delta = chunk.size - size;
+    if delta == 0 {
+      return offset;
+    }
+
+    // Move the allocation to the end of the chunk
+    chunks.rekey(offset, |chunk| {
+      chunk.offset += delta;
+      chunk.size = size;
+    });
+
+    if let Some(free) = Self::free_adjacent(chunks, offset + delta, Order::Preceding) {
+      // Increase the size of the preceding chunk
+      chunks.update(free.offset, |chunk| chunk.size += delta);
+    } else {
+      // Insert a new chunk representing the surplus memory
+      chunks.insert(Chunk {
+        size: delta,
+        ..chunk
+      });
+    }
+    offset + delta
+  }
+
+  /// Carves an allocation at an offset within a free chunk, leaving the
+  /// memory surrounding it free.
+  fn carve(chunks: &mut ChunkStore, offset: usize, at: usize, size: usize) {
+    let chunk = chunks.remove(offset).expect("free chunk");
+    let end = chunk.offset + chunk.size;
+
+    if at > chunk.offset {
+      chunks.insert(Chunk {
+        size: at - chunk.offset,
+        ..chunk
+      });
+    }
+    chunks.insert(Chunk {
+      offset: at,
+      size,
+      ..chunk
+    });
+    if at + size < end {
+      chunks.insert(Chunk {
+        offset: at + size,
+        size: end - at - size,
+        ..chunk
+      });
+    }
+  }
+
+  /// Adds allocated elements and allocations, updating the peak usage.
+  fn charge(chain: &mut Chain, size: usize, allocations: usize) {
+    chain.used += size;
+    chain.live += allocations;
+    chain.peak.used = chain.peak.used.max(chain.used);
+    chain.peak.live = chain.peak.live.max(chain.live);
+  }
+
+  /// Returns the limit of the size cap an allocation would exceed, if any.
+  fn exceeded_cap(chain: &Chain, size: usize) -> Option<usize> {
+    chain.caps.exceeded(size, || {
+      let small = chain.small.as_ref().map(SmallRegion::allocation_sizes);
+      chain
+        .chunks
+        .iter()
+        .filter(|chunk| !chunk.free)
+        .map(|chunk| chunk.size)
+        .chain(small.into_iter().flatten())
+    })
+  }
+
+  fn info(chunk: &Chunk) -> ChunkInfo {
+    ChunkInfo {
+      offset: chunk.offset,
+      len: chunk.size,
+      state: if chunk.free {
+        ChunkState::Free
+      } else {
+        ChunkState::Allocated
+      },
+    }
+  }
+
+  /// Lets a policy choose a free chunk, rejecting chunks which cannot hold
+  /// the allocation.
+  fn choose<P: AllocPolicy + ?Sized>(
+    chain: &mut Chain,
+    policy: &mut P,
+    size: usize,
+  ) -> Option<usize> {
+    chain.infos.clear();
+    chain.infos.extend(chain.chunks.iter().map(Self::info));
+    let offset = policy.choose(&chain.infos, size, chain.align)?;
+    chain
+      .chunks
+      .get(offset)
+      .filter(|chunk| chunk.free && chunk.size >= size)
+      .filter(|chunk| chunk.offset.is_multiple_of(chain.align))
+      .map(|chunk| chunk.offset)
+  }
+
+  fn claim(chain: &mut Chain, offset: usize, size: usize) -> Chunk {
+    // Stamp the allocation so stale tokens can be detected
+    chain.generation += 1;
+    Self::charge(chain, size, 1);
+    #[cfg(feature = "paranoid")]
+    chain.shadow.mark(offset, size, ChunkState::Allocated);
+    let generation = chain.generation;
+
+    let chunk = chain.chunks.update(offset, |chunk| {
+      chunk.generation = generation;
+      chunk.free = false;
+      *chunk
+    });
+    Self::validate(chain, &chunk);
+    chunk
+  }
+
+  /// Records the checksum of released memory, if integrity is validated.
+  fn record(chain: &mut Chain, offset: usize, size: usize) {
+    if let Some(integrity) = chain.integrity.as_mut() {
+      integrity.record(offset, size);
+    }
+  }
+
+  /// Validates the released memory reused by an allocation.
+  fn validate(chain: &mut Chain, chunk: &Chunk) {
+    if let Some(integrity) = chain.integrity.as_mut() {
+      integrity.validate(chunk.offset, chunk.size);
+    }
+  }
+
+  /// Makes the chain manage the memory following the reserved regions.
+  fn reset(chain: &mut Chain, size: usize) {
+    let start = chain.header + chain.small.as_ref().map_or(0, SmallRegion::len);
+    assert!(start <= size, "region exceeds the pool");
+
+    chain
+      .chunks
+      .reset(Some(Chunk::with_offset(size - start, start)).filter(|chunk| chunk.size > 0));
+    chain.recent = None;
+    #[cfg(feature = "paranoid")]
+    {
+      chain.shadow = Shadow::new(size, chain.header);
+    }
+  }
+
+  fn small_region(chain: &mut Chain, token: ChunkToken) -> Option<&mut SmallRegion> {
+    chain
+      .small
+      .as_mut()
+      .filter(|small| small.contains(token.offset))
+  }
+
+  fn token_chunk(chunks: &ChunkStore, token: ChunkToken) -> Option<Chunk> {
+    chunks
+      .get(token.offset)
+      .filter(|chunk| !chunk.free && chunk.generation == token.generation)
+      .cloned()
+  }
+
+  fn recent_offset(chain: &Chain, size: usize) -> Option<usize> {
+    if chain.placer.is_shuffled() || chain.placer.active() == Strategy::Bump {
+      return None;
+    }
+
+    let offset = chain.recent?;
+    let chunk = chain.chunks.get(offset)?;
+    let local = chain.affinity.is_none() || chunk.affinity == thread_tag();
+    Some(offset).filter(|_| chunk.free && chunk.size == size && local)
+  }
+
+  /// Returns the free chunk adjacent to the one starting at an offset.
+  fn free_adjacent(chunks: &ChunkStore, offset: usize, order: Order) -> Option<Chunk> {
+    let chunk = match order {
+      Order::Preceding => chunks.preceding(offset),
+      Order::Following => chunks.following(offset),
+    };
+    chunk.filter(|chunk| chunk.free).cloned()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use {Local, ThreadSafe};
+
+  #[test]
+  fn chain_stale_token() {
+    stale_token(ChunkChain::<ThreadSafe>::new(4));
+    stale_token(ChunkChain::<Local>::new(4));
+  }
+
+  #[test]
+  fn chain_queued_release() {
+    let chain = ChunkChain::<ThreadSafe>::new(4);
+    chain.set_deferred(true);
+    let token = chain.allocate(4).unwrap().token();
+
+    // The release is queued while the chain is locked
+    let guard = chain.state.lock();
+    assert!(!chain.release(token, None));
+    drop(guard);
+    assert_eq!((chain.used(), chain.stats(4).deferred), (4, 1));
+
+    // The next allocation releases it first
+    assert!(chain.allocate(4).is_some());
+    assert_eq!(chain.stats(4).deferred, 0);
+  }
+
+  #[test]
+  fn chain_elastic_store() {
+    let chain = ChunkChain::<Local>::new(1024);
+    let tokens = (0..600)
+      .map(|_| chain.allocate(1).unwrap().token())
+      .collect::<Vec<_>>();
+    assert!(chain.state.lock().chunks.is_tree());
+
+    // Releasing from the end merges every chunk into the trailing one
+    for &token in tokens.iter().rev() {
+      assert!(chain.release(token, None));
+    }
+    assert!(!chain.state.lock().chunks.is_tree());
+    assert!(chain.allocate(1024).is_some());
+  }
+
+  // The shadow allocator would reject the corrupted chunks first
+  #[test]
+  #[cfg(not(feature = "paranoid"))]
+  fn chain_check() {
+    let chain = ChunkChain::<Local>::new(8);
+    let token = chain.allocate(2).unwrap().token();
+    assert_eq!(chain.check(8), Ok(()));
+    assert_eq!(
+      chain.check(9),
+      Err(ChainError::Length { covered: 8, len: 9 })
+    );
+
+    chain
+      .state
+      .lock()
+      .chunks
+      .update(token.offset, |chunk| chunk.size = 3);
+    assert_eq!(
+      chain.check(8),
+      Err(ChainError::Overlapping { offset: 2, end: 3 })
+    );
+
+    chain
+      .state
+      .lock()
+      .chunks
+      .update(token.offset, |chunk| chunk.size = 1);
+    assert_eq!(chain.check(8), Err(ChainError::Gap { offset: 1, len: 1 }));
+  }
+
+  fn stale_token<F: Flavor>(chain: ChunkChain<F>) {
+    let token = chain.allocate(4).unwrap().token();
+    assert!(chain.release(token, None));
+    assert!(!chain.release(token, None));
+
+    // A new allocation at the same offset must not accept the old token
+    let fresh = chain.allocate(4).unwrap().token();
+    assert_eq!(fresh.offset, token.offset);
+    assert!(!chain.release(token, None));
+    assert!(chain.release(fresh, None));
+  }
+}
@@ -0,0 +1,125 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// The weighted allocation classes of a pool.
+///
+/// Classes are admitted first-come-first-served until the number of
+/// allocated elements exceeds the admission threshold. Beyond it, a class
+/// is only admitted while its usage stays within its share of the pool,
+/// proportional to its weight.
+#[derive(Default)]
+pub struct Classes {
+  threshold: Option<usize>,
+  list: Vec<Class>,
+}
+
+/// The state of an allocation class.
+struct Class {
+  name: &'static str,
+  weight: usize,
+  used: usize,
+  rejected: usize,
+}
+
+/// The usage of an allocation class.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ClassStats {
+  /// The name of the class.
+  pub class: &'static str,
+  /// The weight of the class.
+  pub weight: usize,
+  /// The number of elements allocated by the class.
+  pub used: usize,
+  /// The number of allocations rejected under memory pressure.
+  pub rejected: usize,
+}
+
+impl Classes {
+  /// Sets the weight of a class, registering it if needed.
+  pub fn set_weight(&mut self, name: &'static str, weight: usize) {
+    let index = self.index(name);
+    self.list[index].weight = weight;
+  }
+
+  /// Sets the usage above which classes are admitted by weight.
+  pub fn set_threshold(&mut self, threshold: Option<usize>) {
+    self.threshold = threshold;
+  }
+
+  /// Charges an allocation to a class if it is admitted, returning the
+  /// index of the class.
+  pub fn admit(
+    &mut self,
+    name: &'static str,
+    size: usize,
+    used: usize,
+    len: usize,
+  ) -> Option<usize> {
+    let index = self.index(name);
+    let total = self
+      .list
+      .iter()
+      .map(|class| class.weight as u128)
+      .sum::<u128>();
+    let pressure = self
+      .threshold
+      .is_some_and(|threshold| used + size > threshold);
+
+    let class = &mut self.list[index];
+    if pressure {
+      let share = match total {
+        0 => 0,
+        total => (len as u128 * class.weight as u128 / total) as usize,
+      };
+
+      if class.used + size > share {
+        class.rejected += 1;
+        return None;
+      }
+    }
+
+    class.used += size;
+    Some(index)
+  }
+
+  /// Charges an allocation to a class, regardless of admission.
+  pub fn charge(&mut self, index: usize, size: usize) {
+    self.list[index].used += size;
+  }
+
+  /// Removes a charge from a class.
+  pub fn uncharge(&mut self, index: usize, size: usize) {
+    self.list[index].used -= size;
+  }
+
+  /// Returns the usage of each class, in registration order.
+  pub fn stats(&self) -> Vec<ClassStats> {
+    self
+      .list
+      .iter()
+      .map(|class| ClassStats {
+        class: class.name,
+        weight: class.weight,
+        used: class.used,
+        rejected: class.rejected,
+      })
+      .collect()
+  }
+
+  fn index(&mut self, name: &'static str) -> usize {
+    match self.list.iter().position(|class| class.name == name) {
+      Some(index) => index,
+      None => {
+        // Unknown classes have the default weight
+        self.list.push(Class {
+          name,
+          weight: 1,
+          used: 0,
+          rejected: 0,
+        });
+        self.list.len() - 1
+      }
+    }
+  }
+}
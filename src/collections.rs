@@ -0,0 +1,294 @@
+use flavor::Flavor;
+use std::ops::{Deref, DerefMut};
+use std::{fmt, mem, str};
+use {AllocError, SliceBox, SlicePool, ThreadSafe};
+
+/// A growable vector stored in a pool.
+///
+/// The elements are stored in a single allocation, which grows in place
+/// when the memory following it is free, and otherwise moves to a larger
+/// allocation. Growth fails with an `AllocError` once the pool is
+/// exhausted, instead of aborting.
+///
+/// Since the memory of a pool always holds valid values, the spare
+/// capacity of the vector does too. Elements removed from the vector are
+/// reset to their default value.
+///
+/// ```
+/// use slice_pool::sync::{PoolVec, SlicePool};
+///
+/// let pool = SlicePool::new(vec![0; 16]);
+/// let mut values = PoolVec::new(&pool);
+/// values.push(1);
+/// values.push(2);
+/// assert_eq!(*values, [1, 2]);
+/// assert!(values.try_reserve(32).unwrap_err().is_too_large());
+/// ```
+pub struct PoolVec<'a, T: 'static, F: Flavor = ThreadSafe> {
+  pool: &'a SlicePool<T, F>,
+  data: Option<SliceBox<T, F>>,
+  len: usize,
+}
+
+impl<'a, T: 'static, F: Flavor> PoolVec<'a, T, F> {
+  /// Constructs a new, empty vector, without allocating.
+  pub fn new(pool: &'a SlicePool<T, F>) -> Self {
+    PoolVec {
+      pool,
+      data: None,
+      len: 0,
+    }
+  }
+
+  /// Constructs a new, empty vector with a capacity.
+  pub fn with_capacity(pool: &'a SlicePool<T, F>, capacity: usize) -> Result<Self, AllocError> {
+    let mut vec = Self::new(pool);
+    vec.try_reserve(capacity)?;
+    Ok(vec)
+  }
+
+  /// Returns the number of elements in the vector.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns whether the vector is empty.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Returns the number of elements the vector holds without growing.
+  pub fn capacity(&self) -> usize {
+    self.data.as_ref().map_or(0, |data| data.len())
+  }
+
+  /// Reserves capacity for at least `additional` more elements.
+  ///
+  /// The capacity is doubled if possible, to amortize growth.
+  pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+    let capacity = self.capacity();
+    let required = self.len.saturating_add(additional);
+    if required <= capacity {
+      return Ok(());
+    }
+
+    let amortized = required.max(capacity.saturating_mul(2));
+    if let Some(data) = self.data.as_mut() {
+      if data.grow(amortized - capacity) || data.grow(required - capacity) {
+        return Ok(());
+      }
+    }
+
+    let mut data = self
+      .pool
+      .try_alloc(amortized)
+      .or_else(|_| self.pool.try_alloc(required))?;
+    if let Some(mut previous) = self.data.take() {
+      // The spare values of the new allocation move into the previous one
+      data[..self.len].swap_with_slice(&mut previous[..self.len]);
+    }
+    self.data = Some(data);
+    Ok(())
+  }
+
+  /// Appends an element to the back of the vector.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the pool has no memory for the vector to grow.
+  pub fn push(&mut self, value: T) {
+    if let Err(error) = self.try_push(value) {
+      panic!("{}", error);
+    }
+  }
+
+  /// Appends an element to the back of the vector, if it can grow.
+  pub fn try_push(&mut self, value: T) -> Result<(), AllocError> {
+    self.try_reserve(1)?;
+    let data = self.data.as_mut().expect("reserved capacity");
+    data[self.len] = value;
+    self.len += 1;
+    Ok(())
+  }
+
+  /// Removes the last element of the vector and returns it.
+  pub fn pop(&mut self) -> Option<T>
+  where
+    T: Default,
+  {
+    self.len = self.len.checked_sub(1)?;
+    let data = self.data.as_mut().expect("allocated elements");
+    Some(mem::take(&mut data[self.len]))
+  }
+
+  /// Shortens the vector to `len` elements, resetting the rest.
+  pub fn truncate(&mut self, len: usize)
+  where
+    T: Default,
+  {
+    if len < self.len {
+      let data = self.data.as_mut().expect("allocated elements");
+      data[len..self.len].fill_with(T::default);
+      self.len = len;
+    }
+  }
+
+  /// Removes every element of the vector, keeping its capacity.
+  pub fn clear(&mut self)
+  where
+    T: Default,
+  {
+    self.truncate(0);
+  }
+}
+
+impl<'a, T: Clone + 'static, F: Flavor> PoolVec<'a, T, F> {
+  /// Clones and appends every element of a slice, if the vector can grow.
+  pub fn try_extend_from_slice(&mut self, values: &[T]) -> Result<(), AllocError> {
+    self.try_reserve(values.len())?;
+    let data = self.data.as_mut().expect("reserved capacity");
+    data[self.len..self.len + values.len()].clone_from_slice(values);
+    self.len += values.len();
+    Ok(())
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> Deref for PoolVec<'a, T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    self.data.as_ref().map_or(&[], |data| &data[..self.len])
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> DerefMut for PoolVec<'a, T, F> {
+  fn deref_mut(&mut self) -> &mut [T] {
+    match self.data.as_mut() {
+      Some(data) => &mut data[..self.len],
+      None => &mut [],
+    }
+  }
+}
+
+impl<'a, T: fmt::Debug + 'static, F: Flavor> fmt::Debug for PoolVec<'a, T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+/// A growable UTF-8 string stored in a byte pool.
+///
+/// ```
+/// use slice_pool::sync::{PoolString, SlicePool};
+///
+/// let pool = SlicePool::new(vec![0; 16]);
+/// let mut text = PoolString::new(&pool);
+/// text.push_str("pooled");
+/// text.push('!');
+/// assert_eq!(&*text, "pooled!");
+/// ```
+pub struct PoolString<'a, F: Flavor = ThreadSafe> {
+  bytes: PoolVec<'a, u8, F>,
+}
+
+impl<'a, F: Flavor> PoolString<'a, F> {
+  /// Constructs a new, empty string, without allocating.
+  pub fn new(pool: &'a SlicePool<u8, F>) -> Self {
+    PoolString {
+      bytes: PoolVec::new(pool),
+    }
+  }
+
+  /// Constructs a new, empty string with a capacity in bytes.
+  pub fn with_capacity(pool: &'a SlicePool<u8, F>, capacity: usize) -> Result<Self, AllocError> {
+    PoolVec::with_capacity(pool, capacity).map(|bytes| PoolString { bytes })
+  }
+
+  /// Returns the length of the string in bytes.
+  pub fn len(&self) -> usize {
+    self.bytes.len()
+  }
+
+  /// Returns whether the string is empty.
+  pub fn is_empty(&self) -> bool {
+    self.bytes.is_empty()
+  }
+
+  /// Returns the number of bytes the string holds without growing.
+  pub fn capacity(&self) -> usize {
+    self.bytes.capacity()
+  }
+
+  /// Returns the contents of the string.
+  pub fn as_str(&self) -> &str {
+    unsafe { str::from_utf8_unchecked(&self.bytes) }
+  }
+
+  /// Reserves capacity for at least `additional` more bytes.
+  pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+    self.bytes.try_reserve(additional)
+  }
+
+  /// Appends a string slice.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the pool has no memory for the string to grow.
+  pub fn push_str(&mut self, string: &str) {
+    if let Err(error) = self.try_push_str(string) {
+      panic!("{}", error);
+    }
+  }
+
+  /// Appends a string slice, if the string can grow.
+  pub fn try_push_str(&mut self, string: &str) -> Result<(), AllocError> {
+    self.bytes.try_extend_from_slice(string.as_bytes())
+  }
+
+  /// Appends a character.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the pool has no memory for the string to grow.
+  pub fn push(&mut self, character: char) {
+    self.push_str(character.encode_utf8(&mut [0; 4]));
+  }
+
+  /// Removes the last character of the string and returns it.
+  pub fn pop(&mut self) -> Option<char> {
+    let character = self.as_str().chars().next_back()?;
+    self.bytes.truncate(self.len() - character.len_utf8());
+    Some(character)
+  }
+
+  /// Removes every character of the string, keeping its capacity.
+  pub fn clear(&mut self) {
+    self.bytes.clear();
+  }
+}
+
+impl<'a, F: Flavor> Deref for PoolString<'a, F> {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl<'a, F: Flavor> fmt::Write for PoolString<'a, F> {
+  fn write_str(&mut self, string: &str) -> fmt::Result {
+    self.try_push_str(string).map_err(|_| fmt::Error)
+  }
+}
+
+impl<'a, F: Flavor> fmt::Debug for PoolString<'a, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fmt::Debug::fmt(self.as_str(), f)
+  }
+}
+
+impl<'a, F: Flavor> fmt::Display for PoolString<'a, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fmt::Display::fmt(self.as_str(), f)
+  }
+}
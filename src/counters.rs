@@ -0,0 +1,78 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// The usage of a pool, recorded without locking.
+///
+/// The pool updates the recorder with relaxed atomics, so samplers can
+/// read it periodically without contending with allocations for the lock
+/// of the pool. Values are loaded individually, so a sample taken while
+/// the pool is in use may mix values from before and after an operation.
+#[derive(Debug, Default)]
+pub struct StatsRecorder {
+  used: AtomicUsize,
+  allocations: AtomicU64,
+  releases: AtomicU64,
+  failures: AtomicU64,
+}
+
+impl StatsRecorder {
+  /// Returns the number of allocated elements.
+  pub fn used(&self) -> usize {
+    self.used.load(Ordering::Relaxed)
+  }
+
+  /// Returns the number of allocations made and released.
+  pub fn counters(&self) -> PoolCounters {
+    PoolCounters {
+      allocations: self.allocations.load(Ordering::Relaxed),
+      releases: self.releases.load(Ordering::Relaxed),
+      failures: self.failures.load(Ordering::Relaxed),
+    }
+  }
+
+  /// Publishes the number of allocated elements.
+  pub(crate) fn set_used(&self, used: usize) {
+    self.used.store(used, Ordering::Relaxed);
+  }
+
+  /// Records an allocation attempt, and whether it succeeded.
+  pub(crate) fn allocation(&self, succeeded: bool) {
+    let counter = if succeeded {
+      &self.allocations
+    } else {
+      &self.failures
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Records a released allocation.
+  pub(crate) fn release(&self) {
+    self.releases.fetch_add(1, Ordering::Relaxed);
+  }
+}
+
+/// The number of allocations made and released by a pool.
+///
+/// Splitting an allocation counts as an additional allocation, and merging
+/// two allocations as a release. Leaked allocations are never released.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PoolCounters {
+  /// The number of successful allocations.
+  pub allocations: u64,
+  /// The number of released allocations.
+  pub releases: u64,
+  /// The number of allocation attempts which failed.
+  pub failures: u64,
+}
+
+impl PoolCounters {
+  /// Returns the number of allocations which have not been released.
+  ///
+  /// The counters are loaded individually, so a snapshot taken while other
+  /// threads are releasing allocations may be slightly off.
+  pub fn live(&self) -> u64 {
+    self.allocations.saturating_sub(self.releases)
+  }
+}
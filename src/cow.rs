@@ -0,0 +1,278 @@
+use flavor::Flavor;
+use std::fmt;
+use std::ops::Deref;
+use {AllocError, SliceBox, SlicePool, ThreadSafe};
+
+/// Elements which are either borrowed, on the heap, or in a pool.
+///
+/// This eases adopting a pool in code which passes around boxed slices or
+/// vectors, since each of them converts into this type, and it derefs to a
+/// slice regardless of where the elements are stored.
+///
+/// ```
+/// use slice_pool::sync::{BorrowedOrPooled, SlicePool};
+///
+/// fn checksum(data: BorrowedOrPooled<u8>) -> u32 {
+///   data.iter().map(|&byte| u32::from(byte)).sum()
+/// }
+///
+/// let pool = SlicePool::new(vec![1u8; 8]);
+/// assert_eq!(checksum(pool.alloc(4).unwrap().into()), 4);
+/// assert_eq!(checksum(vec![1, 2].into()), 3);
+/// assert_eq!(checksum((&[3u8][..]).into()), 3);
+/// ```
+pub enum BorrowedOrPooled<'a, T: 'static, F: Flavor = ThreadSafe> {
+  /// Borrowed elements.
+  Borrowed(&'a [T]),
+  /// Elements in a boxed slice.
+  Boxed(Box<[T]>),
+  /// Elements in a vector.
+  Vec(Vec<T>),
+  /// Elements in an allocation of a pool.
+  Pooled(SliceBox<T, F>),
+}
+
+impl<'a, T: 'static, F: Flavor> BorrowedOrPooled<'a, T, F> {
+  /// Returns whether the elements are stored in a pool.
+  pub fn is_pooled(&self) -> bool {
+    matches!(self, BorrowedOrPooled::Pooled(_))
+  }
+
+  /// Mutably borrows the elements, copying them to the heap if they are
+  /// borrowed.
+  pub fn to_mut(&mut self) -> &mut [T]
+  where
+    T: Clone,
+  {
+    if let BorrowedOrPooled::Borrowed(data) = *self {
+      *self = BorrowedOrPooled::Boxed(Box::from(data));
+    }
+
+    match self {
+      BorrowedOrPooled::Borrowed(_) => unreachable!("borrowed elements"),
+      BorrowedOrPooled::Boxed(data) => data,
+      BorrowedOrPooled::Vec(data) => data,
+      BorrowedOrPooled::Pooled(data) => data,
+    }
+  }
+
+  /// Returns the elements as a boxed slice, copying them unless they are
+  /// already on the heap.
+  pub fn into_boxed_slice(self) -> Box<[T]>
+  where
+    T: Clone,
+  {
+    match self {
+      BorrowedOrPooled::Borrowed(data) => Box::from(data),
+      BorrowedOrPooled::Boxed(data) => data,
+      BorrowedOrPooled::Vec(data) => data.into_boxed_slice(),
+      BorrowedOrPooled::Pooled(data) => data.into(),
+    }
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> Deref for BorrowedOrPooled<'a, T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    match self {
+      BorrowedOrPooled::Borrowed(data) => data,
+      BorrowedOrPooled::Boxed(data) => data,
+      BorrowedOrPooled::Vec(data) => data,
+      BorrowedOrPooled::Pooled(data) => data,
+    }
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> From<&'a [T]> for BorrowedOrPooled<'a, T, F> {
+  fn from(data: &'a [T]) -> Self {
+    BorrowedOrPooled::Borrowed(data)
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> From<Box<[T]>> for BorrowedOrPooled<'a, T, F> {
+  fn from(data: Box<[T]>) -> Self {
+    BorrowedOrPooled::Boxed(data)
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> From<Vec<T>> for BorrowedOrPooled<'a, T, F> {
+  fn from(data: Vec<T>) -> Self {
+    BorrowedOrPooled::Vec(data)
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> From<SliceBox<T, F>> for BorrowedOrPooled<'a, T, F> {
+  fn from(data: SliceBox<T, F>) -> Self {
+    BorrowedOrPooled::Pooled(data)
+  }
+}
+
+impl<'a, T: fmt::Debug + 'static, F: Flavor> fmt::Debug for BorrowedOrPooled<'a, T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+/// A copy-on-write handle to an allocation in a `SlicePool`.
+///
+/// Handles are cheap to clone and share the allocation read-only. Once a
+/// handle is mutated while shared, it allocates a private copy from the
+/// pool, leaving the other handles untouched. This suits templates which
+/// are mostly read, and only occasionally customized.
+///
+/// ```
+/// use slice_pool::sync::{CowSliceBox, SlicePool};
+///
+/// let pool = SlicePool::new(vec![0; 8]);
+/// let mut template = pool.alloc(2).unwrap();
+/// template.copy_from_slice(&[1, 2]);
+///
+/// let template = CowSliceBox::new(&pool, template);
+/// let mut copy = template.clone();
+/// copy.to_mut()[0] = 10;
+/// assert_eq!((&*template, &*copy), (&[1, 2][..], &[10, 2][..]));
+/// ```
+pub struct CowSliceBox<'a, T: Clone + 'static, F: Flavor = ThreadSafe> {
+  pool: &'a SlicePool<T, F>,
+  data: F::Ptr<SliceBox<T, F>>,
+}
+
+impl<'a, T: Clone + 'static, F: Flavor> CowSliceBox<'a, T, F> {
+  /// Constructs a new handle from an allocation of a pool.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the allocation belongs to another pool.
+  pub fn new(pool: &'a SlicePool<T, F>, data: SliceBox<T, F>) -> Self {
+    assert_eq!(data.pool_id(), pool.id(), "allocation of another pool");
+    CowSliceBox {
+      pool,
+      data: F::share(data),
+    }
+  }
+
+  /// Returns whether two handles share the same allocation.
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    F::ptr_eq(&this.data, &other.data)
+  }
+
+  /// Mutably borrows the elements, copying them if they are shared.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the pool has no memory for the copy.
+  pub fn to_mut(&mut self) -> &mut [T] {
+    match self.try_to_mut() {
+      Ok(data) => data,
+      Err(error) => panic!("{}", error),
+    }
+  }
+
+  /// Mutably borrows the elements, copying them if they are shared and the
+  /// pool has memory for the copy.
+  pub fn try_to_mut(&mut self) -> Result<&mut [T], AllocError> {
+    if F::get_mut(&mut self.data).is_none() {
+      let mut data = self.pool.try_alloc(self.data.len())?;
+      data.clone_from_slice(&self.data);
+      self.data = F::share(data);
+    }
+    Ok(F::get_mut(&mut self.data).expect("unique allocation"))
+  }
+
+  /// Returns the allocation, copying it if it is shared.
+  pub fn into_owned(self) -> Result<SliceBox<T, F>, AllocError> {
+    match F::try_unwrap(self.data) {
+      Ok(data) => Ok(data),
+      Err(shared) => {
+        let mut data = self.pool.try_alloc(shared.len())?;
+        data.clone_from_slice(&shared);
+        Ok(data)
+      }
+    }
+  }
+}
+
+impl<'a, T: Clone + 'static, F: Flavor> Clone for CowSliceBox<'a, T, F> {
+  fn clone(&self) -> Self {
+    CowSliceBox {
+      pool: self.pool,
+      data: self.data.clone(),
+    }
+  }
+}
+
+impl<'a, T: Clone + 'static, F: Flavor> Deref for CowSliceBox<'a, T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    &self.data
+  }
+}
+
+impl<'a, T: Clone + fmt::Debug + 'static, F: Flavor> fmt::Debug for CowSliceBox<'a, T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::mem;
+  use unsync;
+
+  #[test]
+  fn cow_copies_on_write() {
+    let pool = ::SlicePool::<_, ThreadSafe>::new(vec![10, 20, 30, 40, 50]);
+    let template = CowSliceBox::new(&pool, pool.alloc(2).unwrap());
+    let mut copy = template.clone();
+    assert!(CowSliceBox::ptr_eq(&template, &copy));
+
+    copy.to_mut()[0] = 15;
+    assert!(!CowSliceBox::ptr_eq(&template, &copy));
+    assert_eq!((&*template, &*copy), (&[10, 20][..], &[15, 20][..]));
+    assert_eq!(pool.stats().used, 4);
+
+    // A unique handle is mutated in place
+    copy.to_mut()[1] = 25;
+    assert_eq!((&*copy, pool.stats().used), (&[15, 25][..], 4));
+
+    // Copies fail once the pool is exhausted
+    let mut other = template.clone();
+    assert!(other.try_to_mut().unwrap_err().is_exhausted());
+    mem::drop(other);
+    assert_eq!(*template.into_owned().unwrap(), [10, 20]);
+  }
+
+  #[test]
+  fn borrowed_or_pooled() {
+    let pool = ::SlicePool::<_, ThreadSafe>::new(vec![10, 20, 30, 40]);
+    let data: Vec<i32> = pool.alloc(3).unwrap().into();
+    assert_eq!((data, pool.stats().used), (vec![10, 20, 30], 0));
+
+    let mut data = BorrowedOrPooled::from(pool.alloc(2).unwrap());
+    assert!(data.is_pooled());
+    data.to_mut()[0] = 15;
+    assert_eq!(*data.into_boxed_slice(), [15, 20]);
+    assert_eq!(pool.stats().used, 0);
+
+    let values = [1, 2];
+    let mut data = BorrowedOrPooled::<_, ThreadSafe>::from(&values[..]);
+    data.to_mut()[1] = 3;
+    assert_eq!((&*data, values), (&[1, 3][..], [1, 2]));
+    assert!(!data.is_pooled());
+  }
+
+  #[test]
+  fn cow_copies_on_write_local() {
+    let pool = unsync::SlicePool::new(vec![10, 20, 30, 40]);
+    let template = CowSliceBox::new(&pool, pool.alloc(2).unwrap());
+    let mut copy = template.clone();
+
+    copy.to_mut()[0] = 15;
+    assert_eq!((&*template, &*copy), (&[10, 20][..], &[15, 20][..]));
+    assert!(copy.clone().try_to_mut().is_err());
+    assert_eq!(*copy.into_owned().unwrap(), [15, 20]);
+  }
+}
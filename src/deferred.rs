@@ -0,0 +1,216 @@
+//! A lock-free queue of deferred releases.
+//!
+//! The queue is verified with `loom`, by running the tests with
+//! `RUSTFLAGS="--cfg loom" cargo test --release deferred`.
+
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+use std::cell::UnsafeCell;
+use std::ptr;
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+use ChunkToken;
+
+/// The number of releases which can be queued at once.
+#[cfg(not(loom))]
+const CAPACITY: usize = 64;
+
+/// The number of releases which can be queued at once, kept small so the
+/// model remains tractable.
+#[cfg(loom)]
+const CAPACITY: usize = 2;
+
+/// The states of a slot in the queue.
+const FREE: u8 = 0;
+const CLAIMED: u8 = 1;
+const QUEUED: u8 = 2;
+
+/// A release of an allocation which could not lock its chain.
+#[derive(Debug, Copy, Clone)]
+pub struct Release {
+  pub token: ChunkToken,
+  pub class: Option<usize>,
+}
+
+/// A bounded queue of releases, pushed by any thread without blocking or
+/// allocating.
+///
+/// The slots are allocated once releases are first deferred. A push claims
+/// a free slot and marks it queued once it is written, and a take claims
+/// the queued slots and frees them once they are read, so every slot is
+/// only ever accessed by the thread which claimed it.
+pub struct Deferred {
+  enabled: AtomicBool,
+  /// The number of slots claimed by pushes, which have not been taken.
+  pending: AtomicUsize,
+  slots: AtomicPtr<[Slot; CAPACITY]>,
+}
+
+struct Slot {
+  state: AtomicU8,
+  release: UnsafeCell<Option<Release>>,
+}
+
+impl Deferred {
+  pub fn new() -> Self {
+    Deferred {
+      enabled: AtomicBool::new(false),
+      pending: AtomicUsize::new(0),
+      slots: AtomicPtr::new(ptr::null_mut()),
+    }
+  }
+
+  /// Returns whether releases should be deferred when a chain is locked.
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Ordering::Relaxed)
+  }
+
+  pub fn set_enabled(&self, enabled: bool) {
+    if enabled && self.slots.load(Ordering::Acquire).is_null() {
+      let slots = Box::into_raw(Box::new(std::array::from_fn(|_| Slot {
+        state: AtomicU8::new(FREE),
+        release: UnsafeCell::new(None),
+      })));
+      let installed =
+        self
+          .slots
+          .compare_exchange(ptr::null_mut(), slots, Ordering::AcqRel, Ordering::Acquire);
+      if installed.is_err() {
+        drop(unsafe { Box::from_raw(slots) });
+      }
+    }
+    self.enabled.store(enabled, Ordering::Relaxed);
+  }
+
+  /// Returns the number of queued releases.
+  pub fn pending(&self) -> usize {
+    self.pending.load(Ordering::Relaxed)
+  }
+
+  /// Pushes a release onto the queue, returning whether there was room.
+  pub fn push(&self, release: Release) -> bool {
+    let slots = match unsafe { self.slots.load(Ordering::Acquire).as_ref() } {
+      Some(slots) => slots,
+      None => return false,
+    };
+
+    // Counting the claim first guarantees a free slot
+    if self.pending.fetch_add(1, Ordering::Relaxed) >= CAPACITY {
+      self.pending.fetch_sub(1, Ordering::Relaxed);
+      return false;
+    }
+
+    loop {
+      for slot in slots {
+        if slot.claim(FREE) {
+          unsafe { *slot.release.get() = Some(release) };
+          slot.state.store(QUEUED, Ordering::Release);
+          return true;
+        }
+      }
+    }
+  }
+
+  /// Takes every queued release.
+  pub fn take(&self) -> Vec<Release> {
+    // Avoid scanning the slots if there is nothing to take
+    let slots = match unsafe { self.slots.load(Ordering::Acquire).as_ref() } {
+      Some(slots) if self.pending.load(Ordering::Acquire) > 0 => slots,
+      _ => return Vec::new(),
+    };
+
+    let mut releases = Vec::new();
+    for slot in slots.iter().filter(|slot| slot.claim(QUEUED)) {
+      releases.extend(unsafe { (*slot.release.get()).take() });
+      slot.state.store(FREE, Ordering::Release);
+      self.pending.fetch_sub(1, Ordering::Release);
+    }
+    releases
+  }
+}
+
+impl Slot {
+  /// Claims the slot if it is in a state, returning whether it was.
+  fn claim(&self, state: u8) -> bool {
+    self
+      .state
+      .compare_exchange(state, CLAIMED, Ordering::Acquire, Ordering::Relaxed)
+      .is_ok()
+  }
+}
+
+// The releases are only accessed by the thread which claimed their slot
+unsafe impl Sync for Slot {}
+
+impl Drop for Deferred {
+  fn drop(&mut self) {
+    let slots = self.slots.load(Ordering::Acquire);
+    if !slots.is_null() {
+      drop(unsafe { Box::from_raw(slots) });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[cfg(loom)]
+  use loom::sync::Arc;
+  #[cfg(loom)]
+  use loom::thread;
+
+  fn release(offset: usize) -> Release {
+    Release {
+      token: ChunkToken {
+        offset,
+        generation: 1,
+      },
+      class: None,
+    }
+  }
+
+  #[test]
+  #[cfg(not(loom))]
+  fn deferred_bounded() {
+    let queue = Deferred::new();
+    assert!(!queue.push(release(0)));
+
+    // The queue holds a bounded number of releases, until taken
+    queue.set_enabled(true);
+    assert!((0..CAPACITY).all(|offset| queue.push(release(offset))));
+    assert!(!queue.push(release(CAPACITY)));
+    assert_eq!(queue.pending(), CAPACITY);
+    assert_eq!(queue.take().len(), CAPACITY);
+    assert_eq!(queue.pending(), 0);
+    assert!(queue.push(release(0)));
+  }
+
+  #[test]
+  #[cfg(loom)]
+  fn deferred_concurrent() {
+    loom::model(|| {
+      let queue = Arc::new(Deferred::new());
+      queue.set_enabled(true);
+      let threads = (0..2)
+        .map(|offset| {
+          let queue = queue.clone();
+          thread::spawn(move || assert!(queue.push(release(offset))))
+        })
+        .collect::<Vec<_>>();
+
+      // Releases are taken while they are being pushed
+      let mut taken = queue.take();
+      for thread in threads {
+        thread.join().unwrap();
+      }
+      taken.extend(queue.take());
+
+      let mut offsets = taken
+        .iter()
+        .map(|release| release.token.offset)
+        .collect::<Vec<_>>();
+      offsets.sort();
+      assert_eq!(offsets, [0, 1]);
+    });
+  }
+}
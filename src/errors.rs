@@ -0,0 +1,409 @@
+//! The errors of this crate.
+//!
+//! Errors are non-exhaustive, so new failure modes can be introduced
+//! without breaking downstream code. Rather than matching on variants,
+//! code can inspect the `kind` of an error, or use helpers such as
+//! `is_exhausted` and `is_fragmented`.
+
+use std::{error, fmt, io};
+
+/// An error returned when an allocation cannot be made.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AllocError {
+  /// There are fewer free elements than requested.
+  Exhausted {
+    /// The number of requested elements.
+    size: usize,
+    /// The number of free elements.
+    available: usize,
+  },
+  /// There are enough free elements, but not contiguously.
+  Fragmented {
+    /// The number of requested elements.
+    size: usize,
+    /// The number of free elements.
+    available: usize,
+  },
+  /// The allocation would exceed the cap on the elements occupied by
+  /// allocations of its size.
+  Capped {
+    /// The number of requested elements.
+    size: usize,
+    /// The number of elements allocations of the size may occupy.
+    limit: usize,
+  },
+  /// The pool could never satisfy the request, even if it were empty.
+  TooLarge {
+    /// The number of requested elements.
+    size: usize,
+    /// The largest possible allocation.
+    max: usize,
+  },
+  /// The pool has as many live allocations as it allows.
+  Limited {
+    /// The largest number of live allocations.
+    max: usize,
+  },
+}
+
+impl AllocError {
+  /// Returns the kind of the error.
+  pub fn kind(&self) -> ErrorCode {
+    match self {
+      AllocError::Exhausted { .. } => ErrorCode::Exhausted,
+      AllocError::Fragmented { .. } => ErrorCode::Fragmented,
+      AllocError::Capped { .. } => ErrorCode::Capped,
+      AllocError::TooLarge { .. } => ErrorCode::TooLarge,
+      AllocError::Limited { .. } => ErrorCode::Limited,
+    }
+  }
+
+  /// Returns whether the pool has fewer free elements than requested.
+  pub fn is_exhausted(&self) -> bool {
+    self.kind().is_exhausted()
+  }
+
+  /// Returns whether the free elements of the pool are too fragmented.
+  pub fn is_fragmented(&self) -> bool {
+    self.kind().is_fragmented()
+  }
+
+  /// Returns whether the request exceeds what the pool could ever satisfy.
+  ///
+  /// Unlike the other errors, retrying the allocation once memory has been
+  /// released is futile.
+  pub fn is_too_large(&self) -> bool {
+    self.kind().is_too_large()
+  }
+}
+
+impl fmt::Display for AllocError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AllocError::Exhausted { size, available } => write!(
+        f,
+        "cannot allocate {} elements, only {} are free",
+        size, available
+      ),
+      AllocError::Fragmented { size, available } => write!(
+        f,
+        "cannot allocate {} contiguous elements of {} free",
+        size, available
+      ),
+      AllocError::Capped { size, limit } => write!(
+        f,
+        "cannot allocate {} elements, exceeding a size cap of {}",
+        size, limit
+      ),
+      AllocError::TooLarge { size, max } => write!(
+        f,
+        "cannot allocate {} elements, at most {} ever fit",
+        size, max
+      ),
+      AllocError::Limited { max } => write!(
+        f,
+        "cannot allocate, at most {} allocations may be live",
+        max
+      ),
+    }
+  }
+}
+
+impl error::Error for AllocError {}
+
+/// An error detected by a pool, reported through its error hook.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PoolError {
+  /// Released memory was modified before it was allocated again.
+  Corrupted {
+    /// The element offset of the released range.
+    offset: usize,
+    /// The number of elements in the released range.
+    len: usize,
+  },
+}
+
+impl PoolError {
+  /// Returns the kind of the error.
+  pub fn kind(&self) -> ErrorCode {
+    match self {
+      PoolError::Corrupted { .. } => ErrorCode::Corrupted,
+    }
+  }
+}
+
+impl fmt::Display for PoolError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      PoolError::Corrupted { offset, len } => write!(
+        f,
+        "released memory at offset {} ({} elements) was modified",
+        offset, len
+      ),
+    }
+  }
+}
+
+impl error::Error for PoolError {}
+
+/// An inconsistency in the chunks of a pool, detected by `validate`.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChainError {
+  /// A chunk starts before the chunk preceding it.
+  Unsorted {
+    /// The element offset of the chunk.
+    offset: usize,
+    /// The element offset of the preceding chunk.
+    previous: usize,
+  },
+  /// A chunk starts before the end of the chunk preceding it.
+  Overlapping {
+    /// The element offset of the chunk.
+    offset: usize,
+    /// The end of the preceding chunk.
+    end: usize,
+  },
+  /// A range of elements is not covered by any chunk.
+  Gap {
+    /// The element offset of the range.
+    offset: usize,
+    /// The number of elements in the range.
+    len: usize,
+  },
+  /// The chunks cover more or fewer elements than the pool has.
+  Length {
+    /// The number of elements covered by the chunks.
+    covered: usize,
+    /// The number of elements in the pool.
+    len: usize,
+  },
+  /// A free chunk follows another free chunk, instead of being coalesced
+  /// with it.
+  Uncoalesced {
+    /// The element offset of the chunk.
+    offset: usize,
+  },
+}
+
+impl ChainError {
+  /// Returns the kind of the error.
+  pub fn kind(&self) -> ErrorCode {
+    ErrorCode::Inconsistent
+  }
+}
+
+impl fmt::Display for ChainError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ChainError::Unsorted { offset, previous } => write!(
+        f,
+        "chunk at offset {} follows a chunk at offset {}",
+        offset, previous
+      ),
+      ChainError::Overlapping { offset, end } => write!(
+        f,
+        "chunk at offset {} overlaps a chunk ending at {}",
+        offset, end
+      ),
+      ChainError::Gap { offset, len } => write!(
+        f,
+        "{} elements at offset {} are not covered by any chunk",
+        len, offset
+      ),
+      ChainError::Length { covered, len } => write!(
+        f,
+        "chunks cover {} elements of a pool with {}",
+        covered, len
+      ),
+      ChainError::Uncoalesced { offset } => write!(
+        f,
+        "free chunk at offset {} follows another free chunk",
+        offset
+      ),
+    }
+  }
+}
+
+impl error::Error for ChainError {}
+
+/// An error returned when an allocation is already borrowed incompatibly.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BorrowError;
+
+impl BorrowError {
+  /// Returns the kind of the error.
+  pub fn kind(&self) -> ErrorCode {
+    ErrorCode::Borrowed
+  }
+}
+
+impl fmt::Display for BorrowError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "allocation is already borrowed")
+  }
+}
+
+impl error::Error for BorrowError {}
+
+/// An error returned when the lengths of two slices differ.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LengthError {
+  /// The length of the allocation.
+  pub expected: usize,
+  /// The length of the other slice.
+  pub actual: usize,
+}
+
+impl LengthError {
+  /// Returns the kind of the error.
+  pub fn kind(&self) -> ErrorCode {
+    ErrorCode::Mismatched
+  }
+}
+
+impl fmt::Display for LengthError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "slice of {} elements does not match allocation of {}",
+      self.actual, self.expected
+    )
+  }
+}
+
+impl error::Error for LengthError {}
+
+/// A compact code for the errors of this crate, which is also their kind.
+///
+/// This carries no data, so it can be stored or passed across FFI and
+/// constrained targets without allocating. Every error converts into its
+/// code, and codes convert into an `io::Error`.
+///
+/// ```
+/// use slice_pool::{BorrowError, ErrorCode};
+///
+/// let code = ErrorCode::from(BorrowError);
+/// assert_eq!(code as u8, 1);
+/// assert_eq!(ErrorCode::from_u8(1), Some(code));
+/// ```
+#[non_exhaustive]
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+  /// An allocation is already borrowed incompatibly.
+  Borrowed = 1,
+  /// Released memory was modified before it was allocated again.
+  Corrupted = 2,
+  /// A pool has fewer free elements than requested.
+  Exhausted = 3,
+  /// The free elements of a pool are too fragmented.
+  Fragmented = 4,
+  /// A request exceeds what a pool could ever satisfy.
+  TooLarge = 5,
+  /// The chunks of a pool are inconsistent.
+  Inconsistent = 6,
+  /// An allocation would exceed the cap on allocations of its size.
+  Capped = 7,
+  /// The lengths of two slices differ.
+  Mismatched = 8,
+  /// A pool has as many live allocations as it allows.
+  Limited = 9,
+}
+
+impl ErrorCode {
+  /// Returns the code with a numeric value, if there is one.
+  pub fn from_u8(value: u8) -> Option<Self> {
+    match value {
+      1 => Some(ErrorCode::Borrowed),
+      2 => Some(ErrorCode::Corrupted),
+      3 => Some(ErrorCode::Exhausted),
+      4 => Some(ErrorCode::Fragmented),
+      5 => Some(ErrorCode::TooLarge),
+      6 => Some(ErrorCode::Inconsistent),
+      7 => Some(ErrorCode::Capped),
+      8 => Some(ErrorCode::Mismatched),
+      9 => Some(ErrorCode::Limited),
+      _ => None,
+    }
+  }
+
+  /// Returns whether a pool has fewer free elements than requested.
+  pub fn is_exhausted(&self) -> bool {
+    *self == ErrorCode::Exhausted
+  }
+
+  /// Returns whether the free elements of a pool are too fragmented.
+  pub fn is_fragmented(&self) -> bool {
+    *self == ErrorCode::Fragmented
+  }
+
+  /// Returns whether a request exceeds what a pool could ever satisfy.
+  pub fn is_too_large(&self) -> bool {
+    *self == ErrorCode::TooLarge
+  }
+}
+
+impl fmt::Display for ErrorCode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ErrorCode::Borrowed => write!(f, "allocation is already borrowed"),
+      ErrorCode::Corrupted => write!(f, "released memory was modified"),
+      ErrorCode::Exhausted => write!(f, "pool is exhausted"),
+      ErrorCode::Fragmented => write!(f, "pool is fragmented"),
+      ErrorCode::TooLarge => write!(f, "request exceeds pool capacity"),
+      ErrorCode::Inconsistent => write!(f, "pool chunks are inconsistent"),
+      ErrorCode::Capped => write!(f, "request exceeds a size cap"),
+      ErrorCode::Mismatched => write!(f, "slice lengths differ"),
+      ErrorCode::Limited => write!(f, "pool allows no more allocations"),
+    }
+  }
+}
+
+impl error::Error for ErrorCode {}
+
+impl From<AllocError> for ErrorCode {
+  fn from(error: AllocError) -> Self {
+    error.kind()
+  }
+}
+
+impl From<ChainError> for ErrorCode {
+  fn from(error: ChainError) -> Self {
+    error.kind()
+  }
+}
+
+impl From<BorrowError> for ErrorCode {
+  fn from(error: BorrowError) -> Self {
+    error.kind()
+  }
+}
+
+impl From<LengthError> for ErrorCode {
+  fn from(error: LengthError) -> Self {
+    error.kind()
+  }
+}
+
+impl From<PoolError> for ErrorCode {
+  fn from(error: PoolError) -> Self {
+    error.kind()
+  }
+}
+
+impl From<ErrorCode> for io::Error {
+  fn from(code: ErrorCode) -> Self {
+    let kind = match code {
+      ErrorCode::Borrowed => io::ErrorKind::WouldBlock,
+      ErrorCode::Corrupted | ErrorCode::Inconsistent => io::ErrorKind::InvalidData,
+      ErrorCode::Exhausted | ErrorCode::Fragmented | ErrorCode::Capped | ErrorCode::Limited => {
+        io::ErrorKind::OutOfMemory
+      }
+      ErrorCode::TooLarge | ErrorCode::Mismatched => io::ErrorKind::InvalidInput,
+    };
+    io::Error::new(kind, code)
+  }
+}
@@ -0,0 +1,144 @@
+use std::cell::{RefCell, RefMut};
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, Mutex, MutexGuard, PoisonError};
+use {AllocPolicy, PoolError};
+
+/// The synchronization of a pool and its allocations.
+///
+/// Pools share a single implementation, parameterized by a flavor. The
+/// thread-safe flavor, `ThreadSafe`, uses `Arc` and `Mutex` and is the
+/// default. The `Local` flavor uses `Rc` and `RefCell`, for pools which
+/// never leave a thread. The trait is sealed, so it cannot be implemented
+/// outside of this crate.
+pub trait Flavor: Sealed + Sized + 'static {
+  /// A reference counted pointer.
+  #[doc(hidden)]
+  type Ptr<U: ?Sized + 'static>: Clone + Deref<Target = U> + From<Box<U>>;
+
+  /// A cell providing exclusive access to its value.
+  #[doc(hidden)]
+  type Lock<U: 'static>: Lock<U>;
+
+  /// The type-erased slice of a pool.
+  #[doc(hidden)]
+  type Slice<T: 'static>: ?Sized + AsRef<[T]> + AsMut<[T]> + 'static;
+
+  /// The type-erased eviction callback of a soft limit.
+  #[doc(hidden)]
+  type Evict: ?Sized + FnMut(usize) -> bool + 'static;
+
+  /// The type-erased release callback of an allocation owner.
+  #[doc(hidden)]
+  type Release: ?Sized + FnMut() + 'static;
+
+  /// The type-erased error hook of a pool.
+  #[doc(hidden)]
+  type ErrorHook: ?Sized + FnMut(PoolError) + 'static;
+
+  /// The type-erased allocation policy of a pool.
+  #[doc(hidden)]
+  type Policy: ?Sized + AllocPolicy + 'static;
+
+  #[doc(hidden)]
+  fn share<U: 'static>(value: U) -> Self::Ptr<U>;
+
+  #[doc(hidden)]
+  fn ptr_eq<U: ?Sized + 'static>(this: &Self::Ptr<U>, other: &Self::Ptr<U>) -> bool;
+
+  #[doc(hidden)]
+  fn try_unwrap<U: 'static>(ptr: Self::Ptr<U>) -> Result<U, Self::Ptr<U>>;
+
+  #[doc(hidden)]
+  fn get_mut<U: ?Sized + 'static>(ptr: &mut Self::Ptr<U>) -> Option<&mut U>;
+}
+
+/// Prevents implementations of `Flavor` outside of this crate.
+pub trait Sealed {}
+
+/// A cell providing exclusive access to its value.
+pub trait Lock<U> {
+  type Guard<'a>: DerefMut<Target = U>
+  where
+    Self: 'a;
+
+  fn new(value: U) -> Self;
+
+  /// Acquires exclusive access, panicking if it is poisoned or taken.
+  fn lock(&self) -> Self::Guard<'_>;
+
+  /// Acquires exclusive access, unless it is already taken.
+  fn try_lock(&self) -> Option<Self::Guard<'_>>;
+
+  /// Consumes the cell, returning its value.
+  fn into_inner(self) -> U;
+}
+
+impl<U> Lock<U> for Mutex<U> {
+  type Guard<'a>
+    = MutexGuard<'a, U>
+  where
+    U: 'a;
+
+  fn new(value: U) -> Self {
+    Mutex::new(value)
+  }
+
+  fn lock(&self) -> Self::Guard<'_> {
+    unpoison(Mutex::lock(self))
+  }
+
+  fn try_lock(&self) -> Option<Self::Guard<'_>> {
+    Mutex::try_lock(self).ok()
+  }
+
+  fn into_inner(self) -> U {
+    unpoison(Mutex::into_inner(self))
+  }
+}
+
+/// Returns the value of a mutex, recovering it from poisoning with the
+/// `recover-poison` feature, or panicking otherwise.
+///
+/// Mutexes are only poisoned by callbacks, or code holding a buffer,
+/// panicking while they are locked, which never leaves the state guarded by
+/// them inconsistent. The feature only concerns poisoning; other misuse,
+/// such as invalid arguments or locking a `Local` pool reentrantly, still
+/// panics.
+fn unpoison<G>(result: LockResult<G>) -> G {
+  if cfg!(feature = "recover-poison") {
+    result.unwrap_or_else(PoisonError::into_inner)
+  } else {
+    result.expect("poisoned lock")
+  }
+}
+
+impl<U> Lock<U> for RefCell<U> {
+  type Guard<'a>
+    = RefMut<'a, U>
+  where
+    U: 'a;
+
+  fn new(value: U) -> Self {
+    RefCell::new(value)
+  }
+
+  fn lock(&self) -> Self::Guard<'_> {
+    self.borrow_mut()
+  }
+
+  fn try_lock(&self) -> Option<Self::Guard<'_>> {
+    self.try_borrow_mut().ok()
+  }
+
+  fn into_inner(self) -> U {
+    RefCell::into_inner(self)
+  }
+}
+
+/// Conversion of a value into its boxed, type-erased form for a flavor.
+///
+/// This is what restricts a thread-safe pool to `Send` slices and
+/// callbacks, while a local pool accepts any.
+pub trait Erase<F: Flavor, U: ?Sized> {
+  fn erase(self) -> Box<U>;
+}
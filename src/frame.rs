@@ -0,0 +1,114 @@
+use flavor::Flavor;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use {SliceBox, SlicePool, ThreadSafe};
+
+impl<F: Flavor> SlicePool<u8, F> {
+  /// Allocates a frame with a payload of `payload_len` bytes.
+  ///
+  /// The frame is prefixed by its payload length, as a big-endian `u32`.
+  /// Returns `None` if the pool has no memory for the frame, or if the
+  /// length does not fit in the prefix.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let pool = SlicePool::new(vec![0u8; 64]);
+  /// let mut frame = pool.alloc_frame(3).unwrap();
+  /// frame.copy_from_slice(b"abc");
+  /// assert_eq!(frame.as_wire(), b"\0\0\0\x03abc");
+  /// ```
+  pub fn alloc_frame(&self, payload_len: usize) -> Option<FrameBox<F>> {
+    let prefix = u32::try_from(payload_len).ok()?.to_be_bytes();
+    let mut data = self.alloc(payload_len.checked_add(FrameBox::<F>::HEADER_LEN)?)?;
+    data[..FrameBox::<F>::HEADER_LEN].copy_from_slice(&prefix);
+    Some(FrameBox { data })
+  }
+}
+
+/// A length-prefixed frame allocated from a byte `SlicePool`.
+///
+/// The frame dereferences to its payload, while `as_wire` includes the
+/// length prefix, ready to be written to a stream.
+pub struct FrameBox<F: Flavor = ThreadSafe> {
+  data: SliceBox<u8, F>,
+}
+
+impl<F: Flavor> FrameBox<F> {
+  /// The number of bytes of the length prefix.
+  pub const HEADER_LEN: usize = 4;
+
+  /// Returns the number of bytes of the payload.
+  pub fn payload_len(&self) -> usize {
+    self.data.len() - Self::HEADER_LEN
+  }
+
+  /// Returns the frame, including its length prefix.
+  pub fn as_wire(&self) -> &[u8] {
+    &self.data
+  }
+
+  /// Shortens the payload, releasing the excess bytes to the pool and
+  /// updating the length prefix.
+  ///
+  /// Has no effect if the payload is not longer than `len`.
+  pub fn truncate(&mut self, len: usize) {
+    let excess = self.payload_len().saturating_sub(len);
+    if excess > 0 {
+      self.data.release_tail(excess);
+      self.data[..Self::HEADER_LEN].copy_from_slice(&(len as u32).to_be_bytes());
+    }
+  }
+
+  /// Returns the allocation of the frame, including its length prefix.
+  pub fn into_inner(self) -> SliceBox<u8, F> {
+    self.data
+  }
+}
+
+impl<F: Flavor> Deref for FrameBox<F> {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    &self.data[Self::HEADER_LEN..]
+  }
+}
+
+impl<F: Flavor> DerefMut for FrameBox<F> {
+  fn deref_mut(&mut self) -> &mut [u8] {
+    &mut self.data[Self::HEADER_LEN..]
+  }
+}
+
+impl<F: Flavor> fmt::Debug for FrameBox<F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pool_alloc_frame() {
+    let pool = SlicePool::<u8, ThreadSafe>::new(vec![0u8; 16]);
+    let mut frame = pool.alloc_frame(6).unwrap();
+    frame.copy_from_slice(b"abcdef");
+    assert_eq!((frame.payload_len(), pool.stats().used), (6, 10));
+    assert!(pool.alloc_frame(3).is_none());
+
+    frame.truncate(2);
+    assert_eq!(
+      (&*frame, frame.as_wire()),
+      (&b"ab"[..], &b"\0\0\0\x02ab"[..])
+    );
+    assert_eq!(pool.stats().used, 6);
+
+    let empty = pool.alloc_frame(0).unwrap();
+    assert_eq!(empty.as_wire(), [0; 4]);
+    assert!(pool.alloc_frame(usize::MAX).is_none());
+    assert_eq!(frame.into_inner().len(), 6);
+  }
+}
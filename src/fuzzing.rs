@@ -0,0 +1,122 @@
+//! Operation sequences for fuzzing pool configurations.
+//!
+//! Operations implement `Arbitrary`, so a `cargo fuzz` target only needs to
+//! configure a pool and run the operations generated for it:
+//!
+//! ```ignore
+//! fuzz_target!(|ops: Vec<Op>| {
+//!   let pool = SlicePool::new(vec![0u8; 256]).with_small_region(32, 4);
+//!   run_ops(&pool, &ops);
+//! });
+//! ```
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use {Flavor, SliceBox, SlicePool};
+
+/// An operation on a pool.
+///
+/// Indices refer to the live allocations made by previous operations, and
+/// wrap around their count, so any generated index is meaningful.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op {
+  /// Allocates a slice.
+  Alloc { size: usize },
+  /// Releases an allocation.
+  Free { index: usize },
+  /// Replaces an allocation with one of another size, allocating the new
+  /// one before releasing the old one.
+  Realloc { index: usize, size: usize },
+  /// Splits an allocation in two, at an index wrapping around its length.
+  Split { index: usize, mid: usize },
+}
+
+impl<'a> Arbitrary<'a> for Op {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    // Sizes are kept small, so allocations are likely to succeed
+    let size = |u: &mut Unstructured<'a>| u.arbitrary::<u8>().map(usize::from);
+    Ok(match u.int_in_range(0..=3)? {
+      0 => Op::Alloc { size: size(u)? },
+      1 => Op::Free {
+        index: u.arbitrary()?,
+      },
+      2 => Op::Realloc {
+        index: u.arbitrary()?,
+        size: size(u)?,
+      },
+      _ => Op::Split {
+        index: u.arbitrary()?,
+        mid: u.arbitrary()?,
+      },
+    })
+  }
+}
+
+/// Runs a sequence of operations on a pool.
+///
+/// Operations which cannot be performed (e.g. failed allocations) are
+/// skipped. Every allocation is released once the operations are done.
+///
+/// # Panics
+///
+/// Panics if the pool loses track of its allocated elements, or if its
+/// chunks do not cover it.
+pub fn run_ops<T: 'static, F: Flavor>(pool: &SlicePool<T, F>, ops: &[Op]) {
+  let mut live: Vec<SliceBox<T, F>> = Vec::new();
+
+  for &op in ops {
+    match op {
+      Op::Alloc { size } => live.extend(pool.alloc(size)),
+      Op::Free { index } if !live.is_empty() => {
+        live.swap_remove(index % live.len());
+      }
+      Op::Realloc { index, size } if !live.is_empty() => {
+        if let Some(data) = pool.alloc(size) {
+          let index = index % live.len();
+          live[index] = data;
+        }
+      }
+      Op::Split { index, mid } if !live.is_empty() => {
+        let index = index % live.len();
+        let len = live[index].len();
+        if len > 1 {
+          let (head, tail) = live.swap_remove(index).split_at(1 + mid % (len - 1));
+          live.push(head);
+          live.push(tail);
+        }
+      }
+      _ => (),
+    }
+
+    let used = live.iter().map(|data| data.len()).sum::<usize>();
+    assert_eq!(pool.stats().used, used, "allocated elements diverged");
+
+    let end = pool.chunks().fold(0, |offset, chunk| {
+      assert_eq!(chunk.offset, offset, "chunks do not cover the pool");
+      offset + chunk.len
+    });
+    assert_eq!(end, pool.len(), "chunks do not cover the pool");
+  }
+
+  live.clear();
+  assert_eq!(pool.stats().used, 0, "allocated elements leaked");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ThreadSafe;
+
+  #[test]
+  fn fuzzing_run_ops() {
+    let bytes = (0..1024u32)
+      .map(|index| (index.wrapping_mul(2_654_435_761) >> 13) as u8)
+      .collect::<Vec<_>>();
+    let mut u = Unstructured::new(&bytes);
+    let ops = (0..256)
+      .map(|_| Op::arbitrary(&mut u).unwrap())
+      .collect::<Vec<_>>();
+
+    let pool = SlicePool::<_, ThreadSafe>::new(vec![0u8; 512]).with_small_region(32, 4);
+    run_ops(&pool, &ops);
+  }
+}
@@ -0,0 +1,40 @@
+//! Instruction cache maintenance for code written into pooled memory.
+
+/// Makes code written to `[ptr, ptr + len)` visible to instruction fetch.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn flush(_ptr: *const u8, _len: usize) {
+  // The instruction cache is coherent with data writes on x86, only the
+  // compiler must be kept from reordering the writes past this point.
+  std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+/// Makes code written to `[ptr, ptr + len)` visible to instruction fetch.
+#[cfg(target_arch = "aarch64")]
+pub fn flush(ptr: *const u8, len: usize) {
+  use std::arch::asm;
+
+  let start = ptr as usize;
+  let end = start + len;
+
+  // The cache line sizes are encoded as log2 of the number of words
+  let ctr: u64;
+  unsafe { asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack)) };
+  let dline = 4usize << ((ctr >> 16) & 0xF);
+  let iline = 4usize << (ctr & 0xF);
+
+  // Clean the data cache to the point of unification
+  let mut addr = start & !(dline - 1);
+  while addr < end {
+    unsafe { asm!("dc cvau, {}", in(reg) addr, options(nostack)) };
+    addr += dline;
+  }
+  unsafe { asm!("dsb ish", options(nostack)) };
+
+  // Invalidate the stale instructions
+  let mut addr = start & !(iline - 1);
+  while addr < end {
+    unsafe { asm!("ic ivau, {}", in(reg) addr, options(nostack)) };
+    addr += iline;
+  }
+  unsafe { asm!("dsb ish", "isb", options(nostack)) };
+}
@@ -0,0 +1,210 @@
+use std::collections::{BTreeSet, HashMap};
+use tlsf::TlsfIndex;
+
+/// An index of the free chunks of a store, by size.
+pub enum FreeIndex {
+  Tlsf(TlsfIndex),
+  Segregated(SegregatedIndex),
+  Sizes(SizeIndex),
+}
+
+impl FreeIndex {
+  /// Adds a free chunk.
+  pub fn insert(&mut self, offset: usize, size: usize) {
+    match self {
+      FreeIndex::Tlsf(index) => index.insert(offset, size),
+      FreeIndex::Segregated(index) => index.insert(offset, size),
+      FreeIndex::Sizes(index) => index.insert(offset, size),
+    }
+  }
+
+  /// Removes a free chunk, with the size it was indexed by.
+  pub fn remove(&mut self, offset: usize, size: usize) {
+    match self {
+      FreeIndex::Tlsf(index) => index.remove(offset),
+      FreeIndex::Segregated(index) => index.remove(offset),
+      FreeIndex::Sizes(index) => index.remove(offset, size),
+    }
+  }
+
+  /// Returns the offset of a free chunk of at least `size` elements.
+  pub fn find(&self, size: usize) -> Option<usize> {
+    match self {
+      FreeIndex::Tlsf(index) => index.find(size),
+      FreeIndex::Segregated(index) => index.find(size),
+      FreeIndex::Sizes(index) => index.find(size),
+    }
+  }
+
+  /// Removes every chunk.
+  pub fn clear(&mut self) {
+    match self {
+      FreeIndex::Tlsf(index) => *index = TlsfIndex::new(),
+      FreeIndex::Segregated(index) => index.lists = FreeLists::new(index.bounds.len() + 1),
+      FreeIndex::Sizes(index) => index.0.clear(),
+    }
+  }
+}
+
+/// Lists of free chunks, supporting constant-time removal by offset.
+pub struct FreeLists {
+  /// The offsets and sizes of the free chunks, in each list.
+  chunks: Vec<Vec<(usize, usize)>>,
+  /// The list and position of each free chunk, by offset.
+  positions: HashMap<usize, (usize, usize)>,
+}
+
+impl FreeLists {
+  pub fn new(count: usize) -> Self {
+    FreeLists {
+      chunks: vec![Vec::new(); count],
+      positions: HashMap::new(),
+    }
+  }
+
+  /// Returns the chunks of a list.
+  pub fn get(&self, list: usize) -> &[(usize, usize)] {
+    &self.chunks[list]
+  }
+
+  /// Adds a free chunk to a list.
+  pub fn insert(&mut self, list: usize, offset: usize, size: usize) {
+    let previous = self
+      .positions
+      .insert(offset, (list, self.chunks[list].len()));
+    assert!(previous.is_none(), "indexing a chunk twice");
+    self.chunks[list].push((offset, size));
+  }
+
+  /// Removes a free chunk, returning its list and whether it is now empty.
+  pub fn remove(&mut self, offset: usize) -> (usize, bool) {
+    let (list, position) = self
+      .positions
+      .remove(&offset)
+      .expect("removing an unindexed chunk");
+
+    let chunks = &mut self.chunks[list];
+    chunks.swap_remove(position);
+    if let Some(&(moved, _)) = chunks.get(position) {
+      self.positions.insert(moved, (list, position));
+    }
+    (list, chunks.is_empty())
+  }
+}
+
+/// Free lists segregated by configurable size classes.
+///
+/// Each class holds the chunks larger than the bound of the previous class,
+/// up to and including its own bound, and a final class holds the chunks
+/// exceeding every bound. Any chunk of a class above the class of a size
+/// fits it, so only the class of the size itself needs to be scanned.
+pub struct SegregatedIndex {
+  bounds: Vec<usize>,
+  lists: FreeLists,
+}
+
+impl SegregatedIndex {
+  /// Constructs an index with classes up to each bound, in any order.
+  pub fn new(bounds: &[usize]) -> Self {
+    let mut bounds = bounds.to_vec();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    SegregatedIndex {
+      lists: FreeLists::new(bounds.len() + 1),
+      bounds,
+    }
+  }
+
+  pub fn insert(&mut self, offset: usize, size: usize) {
+    let class = self.class(size);
+    self.lists.insert(class, offset, size);
+  }
+
+  pub fn remove(&mut self, offset: usize) {
+    self.lists.remove(offset);
+  }
+
+  /// Returns the offset of a free chunk of at least `size` elements,
+  /// preferring the smallest class.
+  pub fn find(&self, size: usize) -> Option<usize> {
+    let class = self.class(size);
+    let fit = self
+      .lists
+      .get(class)
+      .iter()
+      .find(|&&(_, chunk)| chunk >= size);
+
+    fit
+      .or_else(|| (class + 1..=self.bounds.len()).find_map(|list| self.lists.get(list).last()))
+      .map(|&(offset, _)| offset)
+  }
+
+  fn class(&self, size: usize) -> usize {
+    self.bounds.partition_point(|&bound| bound < size)
+  }
+}
+
+/// The free chunks ordered by size, and then by offset.
+///
+/// The smallest chunk which fits a size is found in logarithmic time, and
+/// among chunks of equal size, the one with the lowest offset, so this
+/// places allocations exactly like a linear best-fit search.
+#[derive(Default)]
+pub struct SizeIndex(BTreeSet<(usize, usize)>);
+
+impl SizeIndex {
+  pub fn insert(&mut self, offset: usize, size: usize) {
+    assert!(self.0.insert((size, offset)), "indexing a chunk twice");
+  }
+
+  pub fn remove(&mut self, offset: usize, size: usize) {
+    assert!(
+      self.0.remove(&(size, offset)),
+      "removing an unindexed chunk"
+    );
+  }
+
+  /// Returns the offset of the smallest free chunk of at least `size`
+  /// elements.
+  pub fn find(&self, size: usize) -> Option<usize> {
+    self.0.range((size, 0)..).next().map(|&(_, offset)| offset)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn segregated_find() {
+    let mut index = SegregatedIndex::new(&[16, 4, 1, 4]);
+    assert_eq!(index.bounds, [1, 4, 16]);
+    index.insert(0, 3);
+    index.insert(10, 40);
+    index.insert(100, 16);
+    assert_eq!(index.find(1), Some(0));
+    assert_eq!(index.find(4), Some(100));
+    assert_eq!(index.find(17), Some(10));
+    assert_eq!(index.find(41), None);
+
+    index.remove(100);
+    assert_eq!(index.find(4), Some(10));
+    index.remove(10);
+    assert_eq!((index.find(3), index.find(4)), (Some(0), None));
+  }
+
+  #[test]
+  fn sizes_find() {
+    let mut index = SizeIndex::default();
+    index.insert(20, 5);
+    index.insert(0, 8);
+    index.insert(10, 5);
+    assert_eq!(index.find(4), Some(10));
+    assert_eq!(index.find(6), Some(0));
+    assert_eq!(index.find(9), None);
+
+    index.remove(10, 5);
+    assert_eq!(index.find(5), Some(20));
+  }
+}
@@ -0,0 +1,142 @@
+use flavor::Flavor;
+use std::fmt;
+use {SliceBox, SlicePool};
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Allocates a new slice from the pool and initializes it in place.
+  ///
+  /// The closure writes each element once through a `SliceInit`, so large
+  /// buffers can be filled without first being copied into a `SliceBox`.
+  /// The memory of a pool always holds initialized values of `T`, so the
+  /// elements are never exposed as `MaybeUninit`; instead, every write
+  /// drops the value previously stored in the pool. If any element is left
+  /// unwritten, and would keep its stale value, this panics once the
+  /// closure returns. If the closure unwinds, the allocation is released
+  /// with the elements written so far.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let pool = SlicePool::new(vec![0; 6]);
+  /// let data = pool
+  ///   .alloc_with(4, |init| init.fill_with(|index| index * 10))
+  ///   .unwrap();
+  /// assert_eq!(*data, [0, 10, 20, 30]);
+  /// ```
+  pub fn alloc_with<I>(&self, size: usize, f: I) -> Option<SliceBox<T, F>>
+  where
+    I: FnOnce(&mut SliceInit<T>),
+  {
+    let mut data = self.alloc(size)?;
+    let mut init = SliceInit::new(&mut data);
+    f(&mut init);
+    init.check();
+    Some(data)
+  }
+}
+
+/// The elements of an allocation being initialized by
+/// `SlicePool::alloc_with`.
+pub struct SliceInit<'a, T: 'a> {
+  data: &'a mut [T],
+  /// Which elements have been written.
+  written: Vec<bool>,
+}
+
+impl<'a, T> SliceInit<'a, T> {
+  fn new(data: &'a mut [T]) -> Self {
+    SliceInit {
+      written: vec![false; data.len()],
+      data,
+    }
+  }
+
+  /// Returns the number of elements.
+  pub fn len(&self) -> usize {
+    self.data.len()
+  }
+
+  /// Returns whether there are no elements.
+  pub fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  /// Writes an element, returning a reference to it.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of bounds.
+  pub fn write(&mut self, index: usize, value: T) -> &mut T {
+    self.written[index] = true;
+    let element = &mut self.data[index];
+    *element = value;
+    element
+  }
+
+  /// Writes every element with the value returned for its index, in order.
+  pub fn fill_with<G: FnMut(usize) -> T>(&mut self, mut f: G) {
+    for index in 0..self.len() {
+      self.write(index, f(index));
+    }
+  }
+
+  /// Asserts that every element has been written.
+  fn check(&self) {
+    if let Some(index) = self.written.iter().position(|&written| !written) {
+      panic!("element {} not initialized", index);
+    }
+  }
+}
+
+impl<'a, T> fmt::Debug for SliceInit<'a, T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("SliceInit")
+      .field("len", &self.len())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::panic::{self, AssertUnwindSafe};
+  use std::rc::Rc;
+  use ThreadSafe;
+
+  #[test]
+  fn pool_alloc_with_drops() {
+    let value = Rc::new(());
+    let pool = SlicePool::<_, ::Local>::new(vec![value.clone(), value.clone(), value.clone()]);
+    assert_eq!(Rc::strong_count(&value), 4);
+
+    // Written elements drop the values they replace
+    let data = pool
+      .alloc_with(2, |init| {
+        init.write(1, Rc::new(()));
+        init.write(0, Rc::new(()));
+      })
+      .unwrap();
+    assert_eq!(Rc::strong_count(&value), 2);
+    drop(data);
+
+    // A panic releases the allocation with the elements written so far
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+      pool.alloc_with(3, |init| {
+        init.write(2, Rc::new(()));
+        panic!();
+      })
+    }));
+    assert!(result.is_err());
+    assert_eq!((Rc::strong_count(&value), pool.stats().used), (1, 0));
+  }
+
+  #[test]
+  #[should_panic(expected = "element 2 not initialized")]
+  fn pool_alloc_with_uninitialized() {
+    let pool = SlicePool::<_, ThreadSafe>::new(vec![0; 4]);
+    pool.alloc_with(3, |init| {
+      init.write(0, 1);
+      init.write(1, 2);
+    });
+  }
+}
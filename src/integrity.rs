@@ -0,0 +1,103 @@
+use std::{mem, slice};
+use {Plain, PoolError};
+
+/// Checksums of released memory, validated once it is reused.
+///
+/// A checksum is computed for every released range while the chain is
+/// locked. When any of a range is allocated again, the entire range is
+/// validated, so writes made through stale pointers are detected.
+pub struct Integrity {
+  base: usize,
+  checksum: fn(usize, usize, usize) -> u64,
+  idle: Vec<Idle>,
+  violations: Vec<PoolError>,
+}
+
+/// A released range of elements and the checksum of its contents.
+struct Idle {
+  offset: usize,
+  len: usize,
+  checksum: u64,
+}
+
+impl Integrity {
+  pub fn new<T: Plain>(base: *const T) -> Self {
+    Integrity {
+      base: base as usize,
+      checksum: checksum::<T>,
+      idle: Vec::new(),
+      violations: Vec::new(),
+    }
+  }
+
+  /// Records the checksum of a released range.
+  pub fn record(&mut self, offset: usize, len: usize) {
+    if len > 0 {
+      let checksum = (self.checksum)(self.base, offset, len);
+      self.idle.push(Idle {
+        offset,
+        len,
+        checksum,
+      });
+    }
+  }
+
+  /// Validates every released range overlapping an allocated one.
+  pub fn validate(&mut self, offset: usize, len: usize) {
+    let (base, checksum) = (self.base, self.checksum);
+    let violations = &mut self.violations;
+
+    self.idle.retain(|idle| {
+      let overlaps = idle.offset < offset + len && offset < idle.offset + idle.len;
+      if overlaps && checksum(base, idle.offset, idle.len) != idle.checksum {
+        violations.push(PoolError::Corrupted {
+          offset: idle.offset,
+          len: idle.len,
+        });
+      }
+      !overlaps
+    });
+  }
+
+  /// Returns the violations detected since the last call.
+  pub fn take_violations(&mut self) -> Vec<PoolError> {
+    mem::take(&mut self.violations)
+  }
+}
+
+/// Computes the FNV-1a hash of a range of plain elements.
+fn checksum<T: Plain>(base: usize, offset: usize, len: usize) -> u64 {
+  let bytes = unsafe {
+    let data = (base as *const T).add(offset);
+    slice::from_raw_parts(data as *const u8, len * mem::size_of::<T>())
+  };
+
+  bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+    (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn integrity_overlap() {
+    let mut data = [0u32; 8];
+    let base = data.as_mut_ptr();
+    let mut integrity = Integrity::new(base);
+    integrity.record(0, 4);
+    integrity.record(4, 4);
+
+    // Only ranges overlapping the allocation are validated
+    unsafe { *base.add(1) = 1 };
+    integrity.validate(5, 1);
+    assert!(integrity.take_violations().is_empty());
+    integrity.validate(2, 4);
+    assert_eq!(
+      integrity.take_violations(),
+      [PoolError::Corrupted { offset: 0, len: 4 }]
+    );
+    assert!(integrity.idle.is_empty());
+  }
+}
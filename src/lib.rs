@@ -21,8 +21,101 @@
 //! let mem2 = memory.alloc(5).unwrap();
 //! assert_eq!(*mem2, [30, 40, 50, 60, 70]);
 //! ```
+//!
+//! Pools share a single implementation, generic over a `Flavor` deciding
+//! how they are synchronized. The `sync` and `unsync` modules provide
+//! aliases for the thread-safe and the non thread-safe flavor.
+
+pub use buffering::{BufferGuard, DoubleBuffer, PendingBuffer, SwapChain};
+pub use builder::SlicePoolBuilder;
+pub use chain::{AffinityStats, ChunkInfo, ChunkState, FragmentationStats, PeakUsage};
+pub use classes::ClassStats;
+pub use collections::{PoolString, PoolVec};
+pub use counters::{PoolCounters, StatsRecorder};
+pub use cow::{BorrowedOrPooled, CowSliceBox};
+pub use errors::{AllocError, BorrowError, ChainError, ErrorCode, LengthError, PoolError};
+pub use flavor::Flavor;
+pub use frame::FrameBox;
+pub use init::SliceInit;
+#[cfg(feature = "lifetime-histogram")]
+pub use lifetimes::LifetimeHistogram;
+pub use open::OpenAlloc;
+pub use policy::AllocPolicy;
+pub use pool::{AllocEach, Chunks, PoolSnapshot, PoolStats, Releaser, SliceBox, SlicePool};
+pub use reader::PooledReader;
+pub use reserve::TailReservation;
+#[cfg(feature = "secure")]
+pub use secure::SecureSlice;
+pub use selfcheck::{SelfCheckIssue, SelfCheckReport};
+pub use shared::{SharedBox, SharedRef, SharedRefMut};
+pub use small::RegionStats;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+pub use storage::GrowableStorage;
+pub use strategy::Strategy;
+pub use sync::ThreadSafe;
+pub use typed::TypedSlice;
+pub use unsync::Local;
+
+#[cfg(feature = "fuzzing")]
+extern crate arbitrary;
+#[cfg(all(feature = "secure", unix))]
+extern crate libc;
+#[cfg(loom)]
+extern crate loom;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "stable-deref")]
+extern crate stable_deref_trait;
+#[cfg(feature = "profiling")]
+extern crate tracy_client_sys;
 
+#[macro_use]
+mod macros;
+
+mod buffering;
+mod builder;
+mod caps;
+mod chain;
+mod classes;
+mod collections;
+mod counters;
+mod cow;
+mod deferred;
+pub mod errors;
+mod flavor;
+mod frame;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+mod icache;
+mod index;
+mod init;
+mod integrity;
+#[cfg(feature = "lifetime-histogram")]
+mod lifetimes;
+mod open;
+mod policy;
+mod pool;
+#[cfg(feature = "profiling")]
+mod profiling;
+mod reader;
+mod reserve;
+#[cfg(feature = "secure")]
+mod secure;
+mod selfcheck;
+#[cfg(feature = "paranoid")]
+mod shadow;
+mod shared;
+mod small;
+mod storage;
+mod store;
+mod strategy;
 pub mod sync;
+mod tlsf;
+mod typed;
 pub mod unsync;
 
 /// A chunk of memory inside a slice.
@@ -31,6 +124,8 @@ struct Chunk {
   offset: usize,
   size: usize,
   free: bool,
+  generation: u64,
+  affinity: u64,
 }
 
 impl Chunk {
@@ -43,6 +138,235 @@ impl Chunk {
       size,
       offset,
       free: true,
+      generation: 0,
+      affinity: 0,
+    }
+  }
+
+  pub fn token(&self) -> ChunkToken {
+    ChunkToken {
+      offset: self.offset,
+      generation: self.generation,
+    }
+  }
+}
+
+/// Returns a non-zero tag identifying the current thread.
+fn thread_tag() -> u64 {
+  static NEXT: AtomicU64 = AtomicU64::new(1);
+  thread_local!(static TAG: u64 = NEXT.fetch_add(1, Ordering::Relaxed));
+  TAG.with(|tag| *tag)
+}
+
+/// The side of an allocation on which a neighboring chunk lies.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Order {
+  /// The chunk ends where the allocation starts.
+  Preceding,
+  /// The chunk starts where the allocation ends.
+  Following,
+}
+
+/// A reference to an allocated chunk, captured when it is allocated.
+///
+/// The generation distinguishes successive allocations at the same offset,
+/// so a stale token is rejected instead of releasing someone else's chunk.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct ChunkToken {
+  offset: usize,
+  generation: u64,
+}
+
+/// A descriptor of an allocation with a stable, C compatible layout.
+///
+/// This can be shared with C code or stored in shared-memory headers. The
+/// offset and length are measured in elements of the pool.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RawChunk {
+  /// The element offset of the allocation within the pool.
+  pub offset: usize,
+  /// The number of elements in the allocation.
+  pub len: usize,
+  /// The generation of the allocation, unique for each allocation.
+  pub generation: u64,
+}
+
+impl RawChunk {
+  fn new(token: ChunkToken, len: usize) -> Self {
+    RawChunk {
+      offset: token.offset,
+      len,
+      generation: token.generation,
     }
   }
+
+  fn token(&self) -> ChunkToken {
+    ChunkToken {
+      offset: self.offset,
+      generation: self.generation,
+    }
+  }
+}
+
+// Guard the layout shared with C against accidental changes
+const _: () = {
+  use std::mem::{align_of, size_of};
+  assert!(size_of::<RawChunk>() == 2 * size_of::<usize>() + size_of::<u64>());
+  assert!(align_of::<RawChunk>() == align_of::<u64>());
+};
+
+/// A position-independent reference to an element in a pool.
+///
+/// The pointer stores the element's offset relative to the start of the
+/// pool, so it remains meaningful when the backing memory is mapped at a
+/// different address (e.g. shared memory or a persisted pool). It is
+/// resolved through the allocation containing the element.
+#[repr(transparent)]
+pub struct PoolPtr<T> {
+  offset: usize,
+  _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> PoolPtr<T> {
+  /// Constructs a pointer from an element offset within a pool.
+  pub fn from_offset(offset: usize) -> Self {
+    PoolPtr {
+      offset,
+      _marker: PhantomData,
+    }
+  }
+
+  /// Returns the element offset within the pool.
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+}
+
+impl<T> Clone for PoolPtr<T> {
+  fn clone(&self) -> Self {
+    *self
+  }
 }
+
+impl<T> Copy for PoolPtr<T> {}
+
+impl<T> PartialEq for PoolPtr<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.offset == other.offset
+  }
+}
+
+impl<T> Eq for PoolPtr<T> {}
+
+impl<T> Hash for PoolPtr<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.offset.hash(state)
+  }
+}
+
+impl<T> fmt::Debug for PoolPtr<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "PoolPtr({})", self.offset)
+  }
+}
+
+/// A pointer to a pooled element with a tag packed into its low bits.
+///
+/// The pointer must be aligned to `2^BITS` bytes, which is guaranteed for
+/// allocations from a pool configured with `set_tag_bits`. This allows
+/// e.g. interpreters to store type tags alongside pointers to objects.
+#[repr(transparent)]
+pub struct TaggedPoolPtr<T, const BITS: u32> {
+  bits: usize,
+  _marker: PhantomData<*mut T>,
+}
+
+impl<T, const BITS: u32> TaggedPoolPtr<T, BITS> {
+  /// The mask of the bits storing the tag.
+  const MASK: usize = (1 << BITS) - 1;
+
+  /// Packs a tag into a pointer.
+  ///
+  /// Returns `None` if the pointer is not aligned or the tag does not fit.
+  pub fn new(ptr: *mut T, tag: usize) -> Option<Self> {
+    let address = ptr as usize;
+    if address & Self::MASK != 0 || tag & !Self::MASK != 0 {
+      return None;
+    }
+
+    Some(TaggedPoolPtr {
+      bits: address | tag,
+      _marker: PhantomData,
+    })
+  }
+
+  /// Returns the pointer without its tag.
+  pub fn ptr(&self) -> *mut T {
+    (self.bits & !Self::MASK) as *mut T
+  }
+
+  /// Returns the tag.
+  pub fn tag(&self) -> usize {
+    self.bits & Self::MASK
+  }
+
+  /// Returns the pointer with a different tag, if it fits.
+  pub fn with_tag(self, tag: usize) -> Option<Self> {
+    Self::new(self.ptr(), tag)
+  }
+}
+
+impl<T, const BITS: u32> Clone for TaggedPoolPtr<T, BITS> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<T, const BITS: u32> Copy for TaggedPoolPtr<T, BITS> {}
+
+impl<T, const BITS: u32> PartialEq for TaggedPoolPtr<T, BITS> {
+  fn eq(&self, other: &Self) -> bool {
+    self.bits == other.bits
+  }
+}
+
+impl<T, const BITS: u32> Eq for TaggedPoolPtr<T, BITS> {}
+
+impl<T, const BITS: u32> Hash for TaggedPoolPtr<T, BITS> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.bits.hash(state)
+  }
+}
+
+impl<T, const BITS: u32> fmt::Debug for TaggedPoolPtr<T, BITS> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "TaggedPoolPtr({:p}, {})", self.ptr(), self.tag())
+  }
+}
+
+/// Types which can be viewed as plain bytes.
+///
+/// # Safety
+///
+/// Implementors must have no padding and every bit pattern must be a valid
+/// value, so that their memory may be read and written as bytes.
+pub unsafe trait Plain: Copy + Send + Sync + 'static {}
+
+unsafe impl Plain for u8 {}
+unsafe impl Plain for u16 {}
+unsafe impl Plain for u32 {}
+unsafe impl Plain for u64 {}
+unsafe impl Plain for u128 {}
+unsafe impl Plain for usize {}
+unsafe impl Plain for i8 {}
+unsafe impl Plain for i16 {}
+unsafe impl Plain for i32 {}
+unsafe impl Plain for i64 {}
+unsafe impl Plain for i128 {}
+unsafe impl Plain for isize {}
+
+/// An identifier of an allocation owner registered with a pool.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct OwnerId(usize);
@@ -0,0 +1,124 @@
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use std::{array, fmt};
+
+/// The number of histogram buckets, one for each power of two nanoseconds.
+const BUCKETS: usize = 64;
+
+/// The lifetimes of released allocations, recorded without locking.
+pub struct Lifetimes {
+  buckets: [AtomicU64; BUCKETS],
+}
+
+impl Default for Lifetimes {
+  fn default() -> Self {
+    Lifetimes {
+      buckets: array::from_fn(|_| AtomicU64::new(0)),
+    }
+  }
+}
+
+impl Lifetimes {
+  /// Records the lifetime of a released allocation.
+  pub fn record(&self, lifetime: Duration) {
+    let nanos = lifetime.as_nanos().min(u64::MAX as u128) as u64;
+    let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+    self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Returns a snapshot of the recorded lifetimes.
+  pub fn histogram(&self) -> LifetimeHistogram {
+    LifetimeHistogram {
+      buckets: array::from_fn(|index| self.buckets[index].load(Ordering::Relaxed)),
+    }
+  }
+}
+
+/// A histogram of the time from allocation to release.
+///
+/// Lifetimes are grouped in buckets by powers of two nanoseconds, so
+/// percentiles are upper bounds within a factor of two. This helps choosing
+/// between pool sizes, or between ring and general allocation strategies.
+#[derive(Clone, PartialEq, Eq)]
+pub struct LifetimeHistogram {
+  buckets: [u64; BUCKETS],
+}
+
+impl LifetimeHistogram {
+  /// Returns the number of recorded lifetimes.
+  pub fn count(&self) -> u64 {
+    self.buckets.iter().sum()
+  }
+
+  /// Returns an upper bound of the lifetime below which the given
+  /// percentage of allocations were released.
+  ///
+  /// Returns `None` if no lifetimes have been recorded.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the percentile is not within `0.0..=100.0`.
+  pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+    assert!((0.0..=100.0).contains(&percentile), "invalid percentile");
+
+    let count = self.count();
+    let rank = ((count as f64 * percentile / 100.0).ceil() as u64).max(1);
+    let mut seen = 0;
+    self
+      .buckets
+      .iter()
+      .position(|&bucket| {
+        seen += bucket;
+        count > 0 && seen >= rank
+      })
+      .map(|bucket| {
+        let bits = (BUCKETS - bucket) as u32;
+        Duration::from_nanos(u64::MAX.checked_shr(bits).unwrap_or(0))
+      })
+  }
+}
+
+impl fmt::Debug for LifetimeHistogram {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("LifetimeHistogram")
+      .field("count", &self.count())
+      .field("p50", &self.percentile(50.0))
+      .field("p99", &self.percentile(99.0))
+      .finish()
+  }
+}
+
+/// Serializes the counts of each bucket, in nanoseconds below `2^i`.
+#[cfg(feature = "serde")]
+impl Serialize for LifetimeHistogram {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(self.buckets.iter())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn lifetime_percentiles() {
+    let lifetimes = Lifetimes::default();
+    assert_eq!(lifetimes.histogram().percentile(50.0), None);
+
+    for _ in 0..9 {
+      lifetimes.record(Duration::from_nanos(100));
+    }
+    lifetimes.record(Duration::from_millis(1));
+
+    // Percentiles are the upper bounds of their buckets
+    let histogram = lifetimes.histogram();
+    assert_eq!(histogram.count(), 10);
+    assert_eq!(histogram.percentile(90.0), Some(Duration::from_nanos(127)));
+    assert_eq!(
+      histogram.percentile(100.0),
+      Some(Duration::from_nanos(1_048_575))
+    );
+  }
+}
@@ -0,0 +1,71 @@
+/// Declares a static arena and a lazily initialized `sync::StaticPool`
+/// over it.
+///
+/// The arena is zero initialized, unless an initial element value is
+/// provided.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slice_pool;
+///
+/// static_pool!(BUFFERS: [u8; 4096]);
+/// static_pool!(pub FLOATS: [f32; 64] = 0.0);
+///
+/// fn main() {
+///   let buffer = BUFFERS.alloc(1024).unwrap();
+///   assert_eq!(buffer.len(), 1024);
+///   assert_eq!(FLOATS.len(), 64);
+/// }
+/// ```
+#[macro_export]
+macro_rules! static_pool {
+  ($(#[$attr:meta])* $vis:vis $name:ident: [$ty:ty; $len:expr]) => {
+    static_pool!($(#[$attr])* $vis $name: [$ty; $len] = 0);
+  };
+  ($(#[$attr:meta])* $vis:vis $name:ident: [$ty:ty; $len:expr] = $init:expr) => {
+    $(#[$attr])*
+    $vis static $name: $crate::sync::StaticPool<$ty> = $crate::sync::StaticPool::new({
+      fn arena() -> &'static mut [$ty] {
+        static mut ARENA: [$ty; $len] = [$init; $len];
+        // The pool invokes this function at most once, so the arena is
+        // never borrowed mutably more than once.
+        unsafe { &mut *::std::ptr::addr_of_mut!(ARENA) }
+      }
+      arena
+    });
+  };
+}
+
+/// Declares constants for a set of fixed partitions and verifies at compile
+/// time that they fit within an arena of a given length.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slice_pool;
+///
+/// pool_layout!(4096; HEADER = 64, PACKETS = 2048, SCRATCH = 1024);
+///
+/// fn main() {
+///   assert_eq!(HEADER + PACKETS + SCRATCH, 3136);
+/// }
+/// ```
+///
+/// A layout exceeding the arena fails to compile:
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate slice_pool;
+///
+/// pool_layout!(128; HEADER = 64, PACKETS = 65);
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! pool_layout {
+  ($len:expr; $($vis:vis $name:ident = $size:expr),+ $(,)*) => {
+    $($vis const $name: usize = $size;)+
+    const _: () = assert!(
+      0 $(+ $name)+ <= $len,
+      "pool layout exceeds the arena"
+    );
+  };
+}
@@ -0,0 +1,161 @@
+use flavor::Flavor;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use {SliceBox, SlicePool, ThreadSafe};
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Opens an allocation with room for `capacity` elements, which are
+  /// committed as they are appended.
+  ///
+  /// This suits data-dependent sizes, where allocating the worst case
+  /// would waste memory. Returns `None` if the pool has no memory for the
+  /// capacity.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let pool = SlicePool::new(vec![0; 16]);
+  /// let mut open = pool.open_alloc(8).unwrap();
+  /// open.extend_from_slice(&[1, 2, 3]);
+  /// open.push(4).unwrap();
+  ///
+  /// let data = open.commit();
+  /// assert_eq!((&*data, pool.stats().used), (&[1, 2, 3, 4][..], 4));
+  /// ```
+  pub fn open_alloc(&self, capacity: usize) -> Option<OpenAlloc<'_, T, F>> {
+    Some(OpenAlloc {
+      pool: self,
+      data: self.alloc(capacity)?,
+      len: 0,
+    })
+  }
+}
+
+/// An allocation of a pool which is appended to, until it is committed.
+///
+/// Committing the allocation returns the appended elements as a right-sized
+/// `SliceBox`, releasing the rest of its capacity. If it is dropped
+/// instead, the entire allocation is released. The capacity grows in place
+/// when the memory following it is free, but the allocation never moves.
+pub struct OpenAlloc<'a, T: 'static, F: Flavor = ThreadSafe> {
+  pool: &'a SlicePool<T, F>,
+  data: SliceBox<T, F>,
+  len: usize,
+}
+
+impl<'a, T: 'static, F: Flavor> OpenAlloc<'a, T, F> {
+  /// Returns the number of appended elements.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns whether no elements have been appended.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Returns the number of elements which can be appended without growing.
+  pub fn capacity(&self) -> usize {
+    self.data.len()
+  }
+
+  /// Appends an element, returning it if the allocation is full and cannot
+  /// grow in place.
+  pub fn push(&mut self, value: T) -> Result<(), T> {
+    if !self.reserve(1) {
+      return Err(value);
+    }
+
+    self.data[self.len] = value;
+    self.len += 1;
+    Ok(())
+  }
+
+  /// Commits the appended elements, releasing the remaining capacity.
+  pub fn commit(self) -> SliceBox<T, F> {
+    let mut data = self.data;
+    if self.len == 0 {
+      return self.pool.alloc(0).expect("empty allocation");
+    }
+
+    data.shrink(self.len);
+    data
+  }
+
+  /// Ensures there is capacity for `additional` more elements.
+  fn reserve(&mut self, additional: usize) -> bool {
+    let required = self.len.saturating_add(additional);
+    required <= self.capacity() || self.data.grow(required - self.capacity())
+  }
+}
+
+impl<'a, T: Clone + 'static, F: Flavor> OpenAlloc<'a, T, F> {
+  /// Clones and appends every element of a slice, returning whether there
+  /// was room for all of them.
+  ///
+  /// Nothing is appended if the allocation cannot hold the entire slice.
+  pub fn extend_from_slice(&mut self, values: &[T]) -> bool {
+    if !self.reserve(values.len()) {
+      return false;
+    }
+
+    self.data[self.len..self.len + values.len()].clone_from_slice(values);
+    self.len += values.len();
+    true
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> Deref for OpenAlloc<'a, T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    &self.data[..self.len]
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> DerefMut for OpenAlloc<'a, T, F> {
+  fn deref_mut(&mut self) -> &mut [T] {
+    &mut self.data[..self.len]
+  }
+}
+
+impl<'a, T: fmt::Debug + 'static, F: Flavor> fmt::Debug for OpenAlloc<'a, T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::mem;
+
+  #[test]
+  fn pool_open_alloc() {
+    let pool = SlicePool::<_, ThreadSafe>::new(vec![0; 8]);
+    let mut open = pool.open_alloc(2).unwrap();
+    assert!(open.extend_from_slice(&[1, 2]));
+
+    // The capacity grows in place, into the free memory following it
+    open.push(3).unwrap();
+    assert_eq!((&*open, open.capacity()), (&[1, 2, 3][..], 3));
+
+    let _next = pool.alloc(4).unwrap();
+    assert!(!open.extend_from_slice(&[4, 5]));
+    assert_eq!(open.len(), 3);
+    let data = open.commit();
+    assert_eq!((&*data, pool.stats().used), (&[1, 2, 3][..], 7));
+    mem::drop(data);
+
+    // Dropping an open allocation rolls it back
+    let mut open = pool.open_alloc(3).unwrap();
+    open.push(10).unwrap();
+    assert_eq!(open.push(20), Ok(()));
+    mem::drop(open);
+    assert_eq!(pool.stats().used, 4);
+
+    let open = pool.open_alloc(3).unwrap();
+    assert!(open.is_empty());
+    assert_eq!((open.commit().len(), pool.stats().used), (0, 4));
+  }
+}
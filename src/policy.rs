@@ -0,0 +1,43 @@
+use ChunkInfo;
+
+/// Custom placement of allocations.
+///
+/// A policy replaces the strategy of a pool, choosing which free chunk an
+/// allocation is carved from, e.g. to prefer low offsets, or a region of
+/// the slice which is local to a NUMA node.
+///
+/// ```
+/// use slice_pool::{sync::SlicePool, AllocPolicy, ChunkInfo};
+///
+/// /// Places allocations in the free chunk with the highest offset.
+/// struct Highest;
+///
+/// impl AllocPolicy for Highest {
+///   fn choose(&mut self, chunks: &[ChunkInfo], size: usize, _align: usize) -> Option<usize> {
+///     let mut free = chunks.iter().filter(|chunk| chunk.is_free() && chunk.len >= size);
+///     free.next_back().map(|chunk| chunk.offset)
+///   }
+/// }
+///
+/// let pool = SlicePool::new(vec![0; 8]);
+/// let first = pool.alloc(2).unwrap();
+/// let _second = pool.alloc(4).unwrap();
+/// drop(first);
+///
+/// pool.set_alloc_policy(Highest);
+/// assert_eq!(pool.alloc(1).unwrap().offset(), 6);
+/// ```
+pub trait AllocPolicy {
+  /// Chooses the free chunk an allocation of `size` elements is carved
+  /// from, returning its offset.
+  ///
+  /// The chunks of the chain are ordered by their offsets, excluding the
+  /// header and the small-object region. The offset of the chunk must be a
+  /// multiple of `align` elements, the period of addresses aligned for the
+  /// tag bits of the pool, which is one without tag bits. Returning `None`,
+  /// or a chunk which is allocated, too small or unaligned, fails the
+  /// allocation.
+  ///
+  /// The pool is locked while choosing, so the policy must not use it.
+  fn choose(&mut self, chunks: &[ChunkInfo], size: usize, align: usize) -> Option<usize>;
+}
@@ -0,0 +1,2117 @@
+use chain::ChunkChain;
+use classes::ClassStats;
+use flavor::{Erase, Flavor, Lock};
+use integrity::Integrity;
+#[cfg(feature = "profiling")]
+use profiling;
+#[cfg(feature = "secure")]
+use secure::{self, SecureSlice};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "stable-deref")]
+use stable_deref_trait::StableDeref;
+#[cfg(feature = "profiling")]
+use std::ffi::CStr;
+#[cfg(feature = "secure")]
+use std::io;
+use std::iter::FusedIterator;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::ops::{Bound, Deref, DerefMut, Range, RangeBounds};
+use std::pin::Pin;
+use std::sync::Arc;
+#[cfg(feature = "lifetime-histogram")]
+use std::time::Instant;
+use std::{fmt, mem, ptr, slice, vec};
+use storage::{self, Growable, GrowableStorage};
+use {sync, unsync};
+use {
+  AffinityStats, AllocError, AllocPolicy, ChainError, Chunk, ChunkInfo, ChunkState, ChunkToken,
+  FragmentationStats, LengthError, Local, Order, OwnerId, PeakUsage, Plain, PoolCounters,
+  PoolError, PoolPtr, RawChunk, RegionStats, StatsRecorder, Strategy, ThreadSafe,
+};
+
+/// An interface for allocating chunks in an owned slice.
+///
+/// The pool is thread-safe unless another flavor is used. The `sync` and
+/// `unsync` modules provide aliases for either flavor.
+pub struct SlicePool<T: 'static, F: Flavor = ThreadSafe> {
+  inner: F::Ptr<Inner<T, F>>,
+  scrub: Option<fn(&mut [T])>,
+  grow: Option<unsafe fn(*const (), usize) -> bool>,
+  tag_bits: u32,
+  granularity: usize,
+  max_allocations: Option<usize>,
+  header: usize,
+  checksums: bool,
+  soft_limit: F::Lock<Option<SoftLimit<F>>>,
+  owners: F::Lock<Owners<F>>,
+  error_hook: F::Lock<Option<Box<F::ErrorHook>>>,
+}
+
+/// The slice of a pool and its chunks, shared with its allocations.
+///
+/// Both are kept behind a single reference counted pointer, so allocating
+/// and releasing a slice only updates one reference count.
+struct Inner<T: 'static, F: Flavor> {
+  chain: ChunkChain<F>,
+  slice: Box<F::Slice<T>>,
+  /// The name of the pool in frame profilers.
+  #[cfg(feature = "profiling")]
+  name: &'static CStr,
+}
+
+impl<T: 'static, F: Flavor> Inner<T, F> {
+  /// Releases the chunk of an allocation.
+  fn release(&self, token: ChunkToken, class: Option<usize>) {
+    // The token must match a live chunk, to catch corrupted boxes (zero-sized
+    // allocations have no chunk, and are stamped with generation zero).
+    // Deferred releases are checked once they are applied instead.
+    let released = self.chain.release(token, class);
+    debug_assert!(
+      released || token.generation == 0 || self.chain.is_deferred(),
+      "releasing an allocation unknown to the pool"
+    );
+    self.chain.recorder().release();
+  }
+}
+
+/// The owners asked to release their allocations on request.
+struct Owners<F: Flavor> {
+  next: usize,
+  list: Vec<Owner<F>>,
+}
+
+/// A registered owner of allocations.
+struct Owner<F: Flavor> {
+  id: OwnerId,
+  tag: &'static str,
+  release: Box<F::Release>,
+}
+
+/// Statistics of a pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PoolStats {
+  /// The number of elements in the pool.
+  pub len: usize,
+  /// The number of allocated elements.
+  pub used: usize,
+  /// The number of free chunks in the chunk chain.
+  pub free_chunks: usize,
+  /// The number of free elements in the chunk chain.
+  pub free_chunk_len: usize,
+  /// The number of times an allocation split a free chunk.
+  pub splits: u64,
+  /// The number of released chunks merged with a free neighbor.
+  pub coalesces: u64,
+  /// The number of releases queued while the pool was locked, which the
+  /// next allocation applies.
+  pub deferred: usize,
+  /// The time from allocation to release of previous allocations.
+  #[cfg(feature = "lifetime-histogram")]
+  pub lifetimes: ::LifetimeHistogram,
+}
+
+impl PoolStats {
+  /// Returns the average number of elements in a free chunk.
+  ///
+  /// Comparing this to typical allocation sizes shows whether a workload
+  /// fragments the pool, and could benefit from coarser granularity.
+  pub fn average_free_chunk_len(&self) -> f64 {
+    match self.free_chunks {
+      0 => 0.0,
+      count => self.free_chunk_len as f64 / count as f64,
+    }
+  }
+}
+
+/// A snapshot of the state of a pool, e.g. for monitoring agents.
+///
+/// With the `serde` feature, the snapshot can be serialized, so it can be
+/// shipped as JSON or any other format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PoolSnapshot {
+  /// The usage of the pool.
+  pub stats: PoolStats,
+  /// The fragmentation of the pool's free memory.
+  pub fragmentation: FragmentationStats,
+  /// The number of allocations made and released.
+  pub counters: PoolCounters,
+  /// The highest usage of the pool.
+  pub peak: PeakUsage,
+  /// The chunks of the pool, ordered by their offsets.
+  pub chunks: Vec<ChunkInfo>,
+}
+
+/// A soft limit on the number of allocated elements.
+struct SoftLimit<F: Flavor> {
+  limit: usize,
+  evict: Box<F::Evict>,
+}
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Constructs a new owned slice pool from a sliceable object.
+  pub fn new<S: Erase<F, F::Slice<T>>>(slice: S) -> Self {
+    let slice = slice.erase();
+    let size = (*slice).as_ref().len();
+
+    SlicePool {
+      inner: F::share(Inner {
+        chain: ChunkChain::new(size),
+        slice,
+        #[cfg(feature = "profiling")]
+        name: profiling::DEFAULT_NAME,
+      }),
+      scrub: None,
+      grow: None,
+      tag_bits: 0,
+      granularity: 1,
+      max_allocations: None,
+      header: 0,
+      checksums: false,
+      soft_limit: Lock::new(None),
+      owners: Lock::new(Owners {
+        next: 0,
+        list: Vec::new(),
+      }),
+      error_hook: Lock::new(None),
+    }
+  }
+
+  /// Constructs a pool for sensitive data, such as key material.
+  ///
+  /// The memory of the slice is locked as a `SecureSlice`, and released
+  /// elements are zeroed, regardless of whether scrubbing is enabled.
+  #[cfg(feature = "secure")]
+  pub fn new_secure<S>(slice: S) -> io::Result<Self>
+  where
+    T: Plain,
+    S: AsRef<[T]> + AsMut<[T]>,
+    SecureSlice<S, T>: Erase<F, F::Slice<T>>,
+  {
+    let mut pool = SlicePool::new(SecureSlice::new(slice)?);
+    pool.scrub = Some(secure::zero::<T>);
+    Ok(pool)
+  }
+
+  /// Constructs a pool which can grow in place, using `try_grow`.
+  pub fn new_growable<S>(storage: S) -> Self
+  where
+    S: GrowableStorage<T>,
+    Growable<S>: Erase<F, F::Slice<T>>,
+  {
+    let mut pool = SlicePool::new(Growable::new(storage));
+    pool.grow = Some(storage::grow::<T, S>);
+    pool
+  }
+
+  /// Grows the pool in place by `additional` free elements.
+  ///
+  /// Returns whether the storage could expand without moving, which is
+  /// never the case for pools not constructed with `new_growable`. Live
+  /// allocations are unaffected, and the added elements follow the last
+  /// chunk of the pool.
+  pub fn try_grow(&mut self, additional: usize) -> bool {
+    let grow = match self.grow {
+      Some(grow) => grow,
+      None => return false,
+    };
+    if additional == 0 {
+      return true;
+    }
+
+    let (len, base) = (self.len(), self.as_ptr());
+    let storage = &*self.inner.slice as *const F::Slice<T> as *const ();
+    if !unsafe { grow(storage, additional) } {
+      return false;
+    }
+
+    assert!(
+      self.as_ptr() == base && self.len() == len + additional,
+      "storage did not grow in place"
+    );
+    self.inner.chain.extend(len, additional);
+    true
+  }
+
+  /// Allocates a new slice from the pool.
+  pub fn alloc(&self, size: usize) -> Option<SliceBox<T, F>> {
+    let data = if self.tag_bits > 0 && size > 0 {
+      self.alloc_aligned(size)
+    } else {
+      let chunk = self.allocate(size);
+      chunk.map(|chunk| self.slice_box(chunk).slice(..size))
+    };
+
+    self.report();
+    data
+  }
+
+  /// Allocates a scratch slice for the duration of a closure.
+  ///
+  /// The slice is released once the closure returns, or unwinds. Unlike
+  /// `alloc`, no `SliceBox` is constructed, so the reference counts of the
+  /// pool are left untouched. Returns `None` if the allocation fails.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let pool = SlicePool::new(vec![0u8; 16]);
+  /// let sum = pool.with_alloc(4, |scratch| {
+  ///   scratch.copy_from_slice(&[1, 2, 3, 4]);
+  ///   scratch.iter().sum::<u8>()
+  /// });
+  /// assert_eq!(sum, Some(10));
+  /// assert_eq!(pool.stats().used, 0);
+  /// ```
+  pub fn with_alloc<R, G: FnOnce(&mut [T]) -> R>(&self, size: usize, f: G) -> Option<R> {
+    if self.tag_bits > 0 && size > 0 {
+      return self.alloc(size).map(|mut data| f(&mut data));
+    }
+
+    let chunk = self.allocate(size);
+    self.report();
+    let chunk = chunk?;
+
+    // The chunk is exclusively owned until the scratch slice is dropped
+    let data = unsafe {
+      let base = (*self.inner.slice).as_ref().as_ptr().add(chunk.offset);
+      slice::from_raw_parts_mut(base as *mut T, size)
+    };
+    #[cfg(feature = "profiling")]
+    profiling::emit(self.inner.name, data.as_ptr(), mem::size_of_val(data), true);
+    let scratch = Scratch {
+      pool: self,
+      token: chunk.token(),
+      #[cfg(feature = "lifetime-histogram")]
+      allocated: Instant::now(),
+      data,
+    };
+    Some(f(scratch.data))
+  }
+
+  /// Allocates a new slice from the pool, returning why it failed.
+  ///
+  /// Requests larger than `max_possible_alloc` fail immediately with
+  /// `AllocError::TooLarge`, since they can never succeed.
+  pub fn try_alloc(&self, size: usize) -> Result<SliceBox<T, F>, AllocError> {
+    let max = self.max_possible_alloc();
+    if size > max {
+      self.inner.chain.recorder().allocation(false);
+      return Err(AllocError::TooLarge { size, max });
+    }
+
+    self.alloc(size).ok_or_else(|| {
+      if let Some(limit) = self.inner.chain.size_cap_exceeded(size) {
+        return AllocError::Capped { size, limit };
+      }
+      if let Some(max) = self
+        .max_allocations
+        .filter(|&max| self.allocation_count() >= max)
+      {
+        return AllocError::Limited { max };
+      }
+
+      // The rounding to the granularity occupies elements too
+      let available = self.available();
+      let occupied = size.div_ceil(self.granularity) * self.granularity;
+      if occupied > available {
+        AllocError::Exhausted { size, available }
+      } else {
+        AllocError::Fragmented { size, available }
+      }
+    })
+  }
+
+  /// Returns statistics of the fragmentation of the pool's free memory.
+  ///
+  /// These cover the chunk chain, excluding the small-object region.
+  pub fn fragmentation(&self) -> FragmentationStats {
+    self.inner.chain.fragmentation()
+  }
+
+  /// Returns the number of allocations made and released by the pool.
+  ///
+  /// The counters are updated without locking the pool, so they are cheap
+  /// enough to be sampled periodically, e.g. by a metrics exporter.
+  pub fn counters(&self) -> PoolCounters {
+    self.inner.chain.recorder().counters()
+  }
+
+  /// Returns the recorder of the pool's usage.
+  ///
+  /// The recorder is updated with relaxed atomics, so sampling it never
+  /// locks the pool, unlike `stats`.
+  pub fn recorder(&self) -> &StatsRecorder {
+    self.inner.chain.recorder()
+  }
+
+  /// Returns the highest number of allocated elements and live
+  /// allocations, since the pool was constructed.
+  ///
+  /// This helps sizing a pool empirically, by running a representative
+  /// workload and inspecting how much of the pool it needed.
+  pub fn peak_usage(&self) -> PeakUsage {
+    self.inner.chain.peak_usage()
+  }
+
+  /// Returns a snapshot of the statistics, counters and chunks of the pool.
+  ///
+  /// Everything but the counters is read while the pool is locked once,
+  /// so they are consistent with each other.
+  pub fn snapshot(&self) -> PoolSnapshot {
+    self.inner.chain.snapshot(self.len())
+  }
+
+  /// Verifies that the chunks of the pool are ordered, and cover every
+  /// element of it without overlapping or leaving gaps.
+  ///
+  /// The pool upholds this on its own, so an error indicates a bug, or the
+  /// misuse of an unsafe method such as `slice_box_from_raw`. Validating
+  /// locks the pool and walks every chunk, so it is best suited to debug
+  /// builds and tests.
+  pub fn validate(&self) -> Result<(), ChainError> {
+    self.inner.chain.check(self.len())
+  }
+
+  /// Returns the number of free elements in the pool.
+  ///
+  /// The elements are not necessarily contiguous, see `largest_free_block`.
+  /// This reads the pool's `StatsRecorder`, so it is wait-free, and never
+  /// contends with allocating threads for the lock of the pool.
+  pub fn available(&self) -> usize {
+    self.len() - self.header - self.used_len()
+  }
+
+  /// Returns whether every element of the pool, besides the header, is
+  /// allocated.
+  ///
+  /// Like `available`, this never locks the pool.
+  pub fn is_full(&self) -> bool {
+    self.available() == 0
+  }
+
+  /// Returns the number of elements in the largest contiguous range of
+  /// free elements.
+  ///
+  /// This bounds the size of an allocation that could currently succeed,
+  /// but does not guarantee it: an allocation may still fail e.g. due to
+  /// the placement strategy, padding for tagged pointers, size caps, class
+  /// limits or the limit of live allocations. Free ranges of the
+  /// small-object region only count up to its threshold. Since other
+  /// threads may allocate concurrently, this is a snapshot.
+  pub fn largest_free_block(&self) -> usize {
+    self.inner.chain.largest_free_block()
+  }
+
+  /// Returns the largest allocation the pool could ever satisfy.
+  ///
+  /// This is the capacity of an empty pool, excluding its header and
+  /// small-object region, and any padding required by tagged pointers.
+  /// Larger requests fail no matter how much memory is released.
+  pub fn max_possible_alloc(&self) -> usize {
+    let max = self.inner.chain.max_possible_alloc(self.len());
+    let max = max - max % self.granularity;
+    if self.tag_bits > 0 {
+      let shift = mem::size_of::<T>().trailing_zeros().min(self.tag_bits);
+      max.saturating_sub((1 << (self.tag_bits - shift)) - 1)
+    } else {
+      max
+    }
+  }
+
+  /// Allocates a slice for each size, until an allocation fails.
+  ///
+  /// The allocations are yielded as they are made, so a pipeline can
+  /// proceed with a partial set of buffers. The iterator reports how many
+  /// allocations succeeded and which size could not be allocated.
+  pub fn try_alloc_each<I>(&self, sizes: I) -> AllocEach<'_, T, I, F>
+  where
+    I: Iterator<Item = usize>,
+  {
+    AllocEach {
+      pool: self,
+      sizes,
+      allocated: 0,
+      failed: None,
+    }
+  }
+
+  /// Allocates a new slice on behalf of an allocation class.
+  ///
+  /// Once the number of allocated elements exceeds the admission
+  /// threshold, an allocation is only admitted if its class stays within
+  /// its share of the pool, proportional to its weight. Classes which have
+  /// not been configured have a weight of one.
+  pub fn alloc_class(&self, class: &'static str, size: usize) -> Option<SliceBox<T, F>> {
+    // The class is charged the elements occupied, including any rounding
+    let charged = size
+      .checked_next_multiple_of(self.granularity)
+      .unwrap_or(size);
+    let Some(index) = self.inner.chain.admit(class, charged, self.len()) else {
+      self.inner.chain.recorder().allocation(false);
+      return None;
+    };
+    match self.alloc(size) {
+      Some(mut data) => {
+        // Tagged allocations are trimmed, so they may occupy fewer elements
+        let occupied = data.before + data.len() + data.after;
+        self.inner.chain.recharge(index, charged, occupied);
+        data.class = Some(index);
+        Some(data)
+      }
+      None => {
+        self.inner.chain.uncharge(index, charged);
+        None
+      }
+    }
+  }
+
+  /// Sets the weight of an allocation class.
+  pub fn set_class_weight(&self, class: &'static str, weight: usize) {
+    self.inner.chain.set_class_weight(class, weight)
+  }
+
+  /// Sets the number of allocated elements above which allocation classes
+  /// are admitted proportionally to their weights, or disables it.
+  ///
+  /// Allocations made without a class are never rejected by admission.
+  pub fn set_admission_threshold(&self, threshold: Option<usize>) {
+    self.inner.chain.set_admission_threshold(threshold)
+  }
+
+  /// Caps the fraction of the pool which allocations with sizes within a
+  /// range may occupy, or removes the cap of the range.
+  ///
+  /// Allocations which would exceed a cap fail, even if the pool has
+  /// enough free memory, so a burst of e.g. large buffers cannot starve
+  /// small control allocations. The fraction is converted to a number of
+  /// elements using the current length of the pool. Caps are checked when
+  /// allocating, so growing, splitting or merging allocations is never
+  /// rejected.
+  pub fn set_size_cap(&self, sizes: Range<usize>, fraction: Option<f64>) {
+    let limit = fraction.map(|fraction| (self.len() as f64 * fraction.clamp(0.0, 1.0)) as usize);
+    self.inner.chain.set_size_cap(sizes, limit)
+  }
+
+  /// Returns the usage of each allocation class.
+  pub fn class_stats(&self) -> Vec<ClassStats> {
+    self.inner.chain.class_stats()
+  }
+
+  /// Allocates a new slice immediately preceding or following another.
+  ///
+  /// This only succeeds if the neighboring memory is free, which allows a
+  /// logical buffer to grow across successive allocations without copying.
+  /// Returns `None` if `other` was allocated from a different pool.
+  pub fn alloc_contiguous_with(
+    &self,
+    other: &SliceBox<T, F>,
+    size: usize,
+    order: Order,
+  ) -> Option<SliceBox<T, F>> {
+    if !F::ptr_eq(&self.inner, &other.inner) {
+      return None;
+    }
+
+    let chunk = self.inner.chain.allocate_adjacent(other.token, size, order);
+    self.inner.chain.recorder().allocation(chunk.is_some());
+    self.report();
+    chunk.map(|chunk| self.slice_box(chunk))
+  }
+
+  /// Allocates a new slice as close as possible to an element offset.
+  ///
+  /// The free memory nearest to the hint is used, e.g. so code allocated
+  /// by a JIT stays within relative branch range of existing code. This
+  /// bypasses the placement strategy, the small-object region and the
+  /// soft limit.
+  pub fn alloc_near(&self, hint: usize, size: usize) -> Option<SliceBox<T, F>> {
+    let chunk = self.inner.chain.allocate_near(hint, size);
+    self.inner.chain.recorder().allocation(chunk.is_some());
+    self.report();
+    chunk.map(|chunk| self.slice_box(chunk))
+  }
+
+  /// Sets a soft limit on the number of allocated elements.
+  ///
+  /// When an allocation would exceed the limit, or fails, the eviction
+  /// callback is invoked with the number of elements that should be
+  /// released. It is expected to drop some allocations and return whether
+  /// it did, in which case the allocation is retried. Once the callback
+  /// returns false, the allocation proceeds even if the limit is exceeded.
+  ///
+  /// Allocations made by the callback itself bypass the limit.
+  pub fn set_soft_limit<E>(&self, limit: usize, evict: E)
+  where
+    E: FnMut(usize) -> bool + Erase<F, F::Evict>,
+  {
+    *self.soft_limit.lock() = Some(SoftLimit {
+      limit,
+      evict: evict.erase(),
+    });
+  }
+
+  /// Removes the soft limit and its eviction callback.
+  pub fn clear_soft_limit(&self) {
+    *self.soft_limit.lock() = None;
+  }
+
+  /// Registers an owner which releases its allocations on request.
+  ///
+  /// Subsystems sharing a pool register under a tag, with a callback that
+  /// drops the allocations they can do without (e.g. cached data). The
+  /// callback must not register or unregister owners itself.
+  pub fn register_owner<R>(&self, tag: &'static str, release: R) -> OwnerId
+  where
+    R: FnMut() + Erase<F, F::Release>,
+  {
+    let mut owners = self.owners.lock();
+    let id = OwnerId(owners.next);
+    owners.next += 1;
+    owners.list.push(Owner {
+      id,
+      tag,
+      release: release.erase(),
+    });
+    id
+  }
+
+  /// Unregisters an owner, returning whether it was registered.
+  pub fn unregister_owner(&self, id: OwnerId) -> bool {
+    let mut owners = self.owners.lock();
+    let len = owners.list.len();
+    owners.list.retain(|owner| owner.id != id);
+    owners.list.len() != len
+  }
+
+  /// Asks every owner with a tag rejected by the predicate to release its
+  /// allocations, returning the number of elements released.
+  pub fn retain_allocations<P>(&self, mut predicate: P) -> usize
+  where
+    P: FnMut(&str) -> bool,
+  {
+    let used = self.inner.chain.used();
+    let mut owners = self.owners.lock();
+    for owner in owners.list.iter_mut().filter(|owner| !predicate(owner.tag)) {
+      (owner.release)();
+    }
+
+    used.saturating_sub(self.inner.chain.used())
+  }
+
+  /// Sets whether released elements are reset to their default value.
+  ///
+  /// This drops stale values (e.g. strings or handles) as soon as their
+  /// allocation is released, instead of when the pool is dropped. The
+  /// setting applies to allocations made after it is changed.
+  pub fn set_scrubbing(&mut self, enabled: bool)
+  where
+    T: Default,
+  {
+    self.scrub = if enabled { Some(scrub::<T>) } else { None };
+  }
+
+  /// Sets whether released memory is validated when it is reused.
+  ///
+  /// A checksum of every released range is computed, and validated once
+  /// any of it is allocated again. This catches code writing to memory it
+  /// no longer owns (e.g. through stale pointers or FFI). Violations are
+  /// reported through the error hook, or cause a panic if there is none.
+  /// The setting applies to memory released after it is changed.
+  pub fn set_checksums(&mut self, enabled: bool)
+  where
+    T: Plain,
+  {
+    let integrity = Some(Integrity::new(self.as_ptr())).filter(|_| enabled);
+    self.inner.chain.set_integrity(integrity);
+    self.checksums = enabled;
+  }
+
+  /// Sets the hook invoked with errors detected by the pool.
+  ///
+  /// Errors detected by allocations made within the hook cause a panic.
+  pub fn set_error_hook<H>(&self, hook: H)
+  where
+    H: FnMut(PoolError) + Erase<F, F::ErrorHook>,
+  {
+    *self.error_hook.lock() = Some(hook.erase());
+  }
+
+  /// Removes the error hook.
+  pub fn clear_error_hook(&self) {
+    *self.error_hook.lock() = None;
+  }
+
+  /// Reserves the start of the slice, following the header, for small
+  /// allocations.
+  ///
+  /// Allocations of at most `threshold` elements are served from a region
+  /// of `len` elements tracked by a bitmap, keeping the chunk chain short
+  /// for larger allocations. Small allocations use the chain once the
+  /// region is full.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the pool has live allocations, or if the reserved regions
+  /// exceed its size.
+  pub fn with_small_region(self, len: usize, threshold: usize) -> Self {
+    self.inner.chain.reserve_small(self.len(), len, threshold);
+    self
+  }
+
+  /// Reserves the first `len` elements of the slice as a header.
+  ///
+  /// The header is never allocated, so it can hold e.g. the control block
+  /// of shared memory or a file header. It is accessed through `header`
+  /// and `header_mut`, and precedes the small-object region, if any.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the pool has live allocations, or if the reserved regions
+  /// exceed its size.
+  pub fn with_header(mut self, len: usize) -> Self {
+    self.inner.chain.reserve_header(self.len(), len);
+    self.header = len;
+    self
+  }
+
+  /// Sets the name of the pool in frame profilers.
+  ///
+  /// Allocations are reported to Tracy as a named memory pool, so pooled
+  /// memory shows up alongside heap allocations. Pools are named
+  /// `slice-pool` unless named otherwise.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the pool has live allocations.
+  #[cfg(feature = "profiling")]
+  pub fn with_profiling_name(mut self, name: &'static CStr) -> Self {
+    F::get_mut(&mut self.inner)
+      .expect("naming a pool with live allocations")
+      .name = name;
+    self
+  }
+
+  /// Returns the header of the slice.
+  pub fn header(&self) -> &[T] {
+    &(*self.inner.slice).as_ref()[..self.header]
+  }
+
+  /// Returns the header of the slice mutably.
+  pub fn header_mut(&mut self) -> &mut [T] {
+    // The header is never allocated, so the pool is its only owner
+    unsafe { slice::from_raw_parts_mut(self.as_ptr() as *mut T, self.header) }
+  }
+
+  /// Returns the statistics of the pool.
+  pub fn stats(&self) -> PoolStats {
+    self.inner.chain.stats(self.len())
+  }
+
+  /// Touches every page of the free memory within a range of elements.
+  ///
+  /// This ensures the memory is backed by physical pages before it is
+  /// allocated, so latency-critical code does not incur page faults when
+  /// first using it. The chain is locked while the pages are touched, so
+  /// allocated memory, and the small-object region, are left untouched.
+  pub fn prefault_range(&self, range: Range<usize>) {
+    let base = self.as_ptr() as *mut T;
+    self
+      .inner
+      .chain
+      .with_free_ranges(range, |offset, len| unsafe {
+        touch(base.add(offset), len);
+      });
+  }
+
+  /// Returns a snapshot of the chunks of the pool.
+  ///
+  /// The chunks are ordered by their offsets, and cover every element of
+  /// the pool without overlapping, including the header and the
+  /// small-object region. Adjacent chunks may have the same state.
+  ///
+  /// The snapshot is taken at once, so the pool is not locked while the
+  /// chunks are iterated.
+  pub fn chunks(&self) -> Chunks {
+    Chunks(self.inner.chain.chunks().into_iter())
+  }
+
+  /// Renders the occupancy of the pool as a bar of `width` characters.
+  ///
+  /// Each character covers an equal share of the pool, and shows whether
+  /// most of it is allocated (`#`), free (`.`) or reserved (`=`), e.g.
+  /// `####....##..`. An empty pool is rendered as free.
+  pub fn render_map(&self, width: usize) -> String {
+    let len = self.len();
+    if len == 0 {
+      return ".".repeat(width);
+    }
+
+    // Positions are scaled by the width, and cell bounds by the length
+    let mut cells = vec![[0usize; 3]; width];
+    for chunk in self.chunks() {
+      let state = match chunk.state {
+        ChunkState::Free => 0,
+        ChunkState::Allocated => 1,
+        _ => 2,
+      };
+      let (start, end) = (chunk.offset * width, (chunk.offset + chunk.len) * width);
+      for (index, cell) in cells
+        .iter_mut()
+        .enumerate()
+        .take(end.div_ceil(len))
+        .skip(start / len)
+      {
+        let overlap = end.min((index + 1) * len) - start.max(index * len);
+        cell[state] += overlap;
+      }
+    }
+
+    cells
+      .iter()
+      .map(|cell| match (0..3).max_by_key(|&state| cell[state]) {
+        Some(0) => '.',
+        Some(1) => '#',
+        _ => '=',
+      })
+      .collect()
+  }
+
+  /// Returns how allocations are split between the small-object region
+  /// and the chunk chain.
+  pub fn region_stats(&self) -> RegionStats {
+    self.inner.chain.region_stats()
+  }
+
+  /// Sets the number of low address bits kept zero in every allocation.
+  ///
+  /// Allocations are aligned to `2^bits` bytes, so the bits can store a
+  /// tag, e.g. using a `TaggedPoolPtr`. The setting applies to allocations
+  /// made after it is changed. Allocations fail if the address of the
+  /// slice and the element size make the alignment unreachable.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `bits` is not less than the number of bits in a pointer.
+  pub fn set_tag_bits(&mut self, bits: u32) {
+    assert!(bits < usize::BITS, "too many tag bits");
+    self.tag_bits = bits;
+    self.inner.chain.set_align(self.tag_period());
+  }
+
+  /// Returns the number of low address bits kept zero in every allocation.
+  pub fn tag_bits(&self) -> u32 {
+    self.tag_bits
+  }
+
+  /// Sets the granularity of allocations, which rounds their sizes up to a
+  /// multiple of `granularity` elements.
+  ///
+  /// The rounding is hidden from the allocations, as if they were sliced,
+  /// and lets them grow in place into it. Chunks are then carved in coarse
+  /// units, so long-running pools do not accumulate tiny fragments which
+  /// are too small for any allocation. Allocations placed explicitly, e.g.
+  /// by `alloc_near`, and the remainders of splitting or shrinking an
+  /// allocation, are not rounded. The setting applies to allocations made
+  /// after it is changed.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let mut pool = SlicePool::new(vec![0; 64]);
+  /// pool.set_granularity(16);
+  /// let data = pool.alloc(3).unwrap();
+  /// assert_eq!((data.len(), pool.used_len()), (3, 16));
+  /// ```
+  ///
+  /// # Panics
+  ///
+  /// Panics if `granularity` is zero.
+  pub fn set_granularity(&mut self, granularity: usize) {
+    assert!(granularity > 0, "zero granularity");
+    self.granularity = granularity;
+  }
+
+  /// Limits the number of live allocations, or removes the limit.
+  ///
+  /// Allocations fail once the limit is reached, regardless of the free
+  /// memory, e.g. to bound the bookkeeping of a pool shared by untrusted
+  /// clients. Zero-sized allocations, and allocations placed explicitly,
+  /// e.g. by `alloc_near`, are not counted against the limit.
+  pub fn set_max_allocations(&mut self, max: Option<usize>) {
+    self.max_allocations = max;
+  }
+
+  /// Sets whether allocations dropped while the pool is locked by another
+  /// thread are queued instead of blocking.
+  ///
+  /// Queued allocations are released by the next thread allocating from
+  /// the pool. This is intended for threads which must not block, e.g.
+  /// FFI callbacks or signal-sensitive code. Queueing does not allocate,
+  /// but the queue is bounded, and drops block once it is full.
+  pub fn set_deferred_release(&self, enabled: bool) {
+    self.inner.chain.set_deferred(enabled)
+  }
+
+  /// Returns the unique identifier of the pool.
+  ///
+  /// Identifiers are never reused within a process, so external
+  /// registries and logs can refer to a pool without using its address.
+  pub fn id(&self) -> u64 {
+    self.inner.chain.id()
+  }
+
+  /// Returns the address of the underlying slice.
+  pub fn as_ptr(&self) -> *const T {
+    (*self.inner.slice).as_ref().as_ptr()
+  }
+
+  /// Returns the size of the underlying slice.
+  ///
+  /// This never locks the pool, so monitoring threads can read it without
+  /// delaying allocations.
+  pub fn len(&self) -> usize {
+    (*self.inner.slice).as_ref().len()
+  }
+
+  /// Returns whether the pool has no live allocations.
+  ///
+  /// Zero-sized allocations occupy no memory, so they are disregarded.
+  pub fn is_empty(&self) -> bool {
+    self.allocation_count() == 0
+  }
+
+  /// Returns the number of allocated elements.
+  ///
+  /// Like `available`, this never locks the pool.
+  pub fn used_len(&self) -> usize {
+    self.inner.chain.recorder().used()
+  }
+
+  /// Returns the number of free elements, excluding the header.
+  ///
+  /// This is the same as `available`.
+  pub fn free_len(&self) -> usize {
+    self.available()
+  }
+
+  /// Returns the number of chunks the pool is divided into, as reported by
+  /// `chunks`.
+  pub fn chunk_count(&self) -> usize {
+    self.inner.chain.chunk_count()
+  }
+
+  /// Returns the number of live allocations, excluding zero-sized ones.
+  pub fn allocation_count(&self) -> usize {
+    self.inner.chain.live()
+  }
+
+  /// Places allocations using a custom policy, instead of the strategy.
+  ///
+  /// The policy chooses among the free chunks of the chain, so it is not
+  /// consulted for allocations served by the small-object region, or
+  /// allocations placed explicitly, e.g. by `alloc_near`.
+  pub fn set_alloc_policy<P>(&self, policy: P)
+  where
+    P: AllocPolicy + Erase<F, F::Policy>,
+  {
+    self.inner.chain.set_policy(Some(policy.erase()))
+  }
+
+  /// Removes the allocation policy, placing allocations using the strategy.
+  pub fn clear_alloc_policy(&self) {
+    self.inner.chain.set_policy(None)
+  }
+
+  /// Sets the strategy used for placing allocations, when constructing the
+  /// pool.
+  ///
+  /// ```
+  /// use slice_pool::{sync::SlicePool, Strategy};
+  ///
+  /// let pool = SlicePool::new(vec![0; 16]).with_strategy(Strategy::NextFit);
+  /// assert_eq!(pool.strategy(), Strategy::NextFit);
+  /// ```
+  pub fn with_strategy(self, strategy: Strategy) -> Self {
+    self.set_strategy(strategy);
+    self
+  }
+
+  /// Places allocations using free lists segregated by size, when
+  /// constructing the pool.
+  ///
+  /// Each bound is the largest size of a class, and a final class holds the
+  /// sizes exceeding every bound. An allocation is placed in the first
+  /// chunk large enough within its class, or any chunk of a larger class,
+  /// so it only scans the chunks of similar sizes. This takes precedence
+  /// over the strategy, unless placement is randomized or prefers thread
+  /// affinity.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let pool = SlicePool::new(vec![0; 64]).with_size_classes(&[1, 4, 16]);
+  /// let small = pool.alloc(4).unwrap();
+  /// let _large = pool.alloc(20).unwrap();
+  /// drop(small);
+  /// assert_eq!(pool.alloc(3).unwrap().offset(), 0);
+  /// ```
+  pub fn with_size_classes(self, bounds: &[usize]) -> Self {
+    self.inner.chain.set_size_classes(Some(bounds.to_vec()));
+    self
+  }
+
+  /// Sets the strategy used for placing allocations.
+  ///
+  /// The default strategy is best-fit.
+  pub fn set_strategy(&self, strategy: Strategy) {
+    self.inner.chain.set_strategy(strategy)
+  }
+
+  /// Randomizes placement using a seed, or restores regular placement.
+  ///
+  /// This is intended for tests. Allocations are placed in any free chunk
+  /// large enough, at either end of it, so code relying on adjacency or
+  /// specific offsets fails. A given seed reproduces the same layout for
+  /// the same sequence of operations.
+  pub fn set_shuffle_seed(&self, seed: Option<u64>) {
+    self.inner.chain.set_shuffle(seed)
+  }
+
+  /// Reclaims the whole pool at once, once every allocation has been
+  /// released.
+  ///
+  /// Released memory is coalesced with both of its neighbors, so a pool
+  /// without live allocations is already a single free chunk. Resetting it
+  /// additionally applies pending deferred releases, shrinks the chunk
+  /// store back to its initial size and forgets the recently released
+  /// chunk. Returns whether the pool had no live allocations, otherwise it
+  /// is unchanged.
+  ///
+  /// ```
+  /// use slice_pool::{sync::SlicePool, Strategy};
+  ///
+  /// let pool = SlicePool::new(vec![0; 8]).with_strategy(Strategy::Bump);
+  /// let (first, second) = (pool.alloc(4).unwrap(), pool.alloc(2).unwrap());
+  /// drop(first);
+  /// assert!(pool.alloc(3).is_none());
+  /// assert!(!pool.reset());
+  ///
+  /// drop(second);
+  /// assert!(pool.reset());
+  /// assert_eq!(pool.alloc(8).unwrap().offset(), 0);
+  /// ```
+  pub fn reset(&self) -> bool {
+    self.inner.chain.reset_empty(self.len())
+  }
+
+  /// Returns the strategy used for placing allocations.
+  pub fn strategy(&self) -> Strategy {
+    self.inner.chain.strategy()
+  }
+
+  /// Returns the strategy currently in effect.
+  ///
+  /// This differs from `strategy` for adaptive placement, for which it
+  /// returns whether first-fit or best-fit is used at the moment.
+  pub fn active_strategy(&self) -> Strategy {
+    self.inner.chain.active_strategy()
+  }
+
+  /// Returns whether a raw chunk refers to a live allocation in this pool.
+  pub fn is_allocated(&self, raw: &RawChunk) -> bool {
+    self.inner.chain.is_allocated(raw.token(), raw.len)
+  }
+
+  /// Reconstructs an allocation dissolved by `SliceBox::into_raw`.
+  ///
+  /// Returns `None` if the pointer and length do not describe a live
+  /// allocation in this pool.
+  ///
+  /// # Safety
+  ///
+  /// The allocation must have been dissolved, and not reconstructed since.
+  /// Otherwise two boxes would own the same memory.
+  pub unsafe fn slice_box_from_raw(&self, ptr: *mut T, len: usize) -> Option<SliceBox<T, F>> {
+    if len == 0 {
+      return self.alloc(0);
+    }
+
+    let delta = (ptr as usize).checked_sub(self.as_ptr() as usize)?;
+    let size = mem::size_of::<T>().max(1);
+    if delta % size != 0 {
+      return None;
+    }
+
+    let offset = delta / size;
+    let token = self.inner.chain.token_at(offset, len)?;
+    Some(self.slice_box(Chunk {
+      free: false,
+      generation: token.generation,
+      ..Chunk::with_offset(len, offset)
+    }))
+  }
+
+  /// Sets whether allocations prefer chunks freed by the same thread.
+  ///
+  /// Memory recently released by a thread is likely to still be in its
+  /// cache. When enabled, a chunk last freed by the allocating thread is
+  /// used if one fits, falling back to any other chunk otherwise.
+  pub fn set_thread_affinity(&self, enabled: bool) {
+    self.inner.chain.set_affinity(enabled)
+  }
+
+  /// Returns the thread affinity statistics, if affinity is enabled.
+  pub fn affinity_stats(&self) -> Option<AffinityStats> {
+    self.inner.chain.affinity_stats()
+  }
+
+  /// Returns the number of allocations which reused the most recently
+  /// released chunk without scanning the chain.
+  pub fn fast_path_hits(&self) -> usize {
+    self.inner.chain.fast_path_hits()
+  }
+}
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Wraps an allocated chunk of the underlying slice.
+  fn slice_box(&self, chunk: Chunk) -> SliceBox<T, F> {
+    // Since the 'SliceBox' is a self-referential type, Rust does not allow us
+    // to express this with its current lifetime semantics. To avoid this
+    // restriction, the slice is transmuted to a static and mutable slice. It
+    // can be treated as static, since the shared pointer of the box is keeping
+    // the data alive. It can also be treated as mutable since the 'SliceBox'
+    // becomes the only way to access the slice.
+    let data: &'static mut [T] = unsafe {
+      let offset = chunk.offset as isize;
+      let base = (*self.inner.slice).as_ref().as_ptr().offset(offset);
+      slice::from_raw_parts_mut(base as *mut _, chunk.size)
+    };
+
+    let data = SliceBox {
+      inner: self.inner.clone(),
+      token: chunk.token(),
+      scrub: self.scrub,
+      class: None,
+      #[cfg(feature = "lifetime-histogram")]
+      allocated: Instant::now(),
+      before: 0,
+      after: 0,
+      data,
+    };
+    #[cfg(feature = "profiling")]
+    data.profile(true);
+    data
+  }
+
+  /// Allocates a slice with an address aligned for pointer tagging.
+  fn alloc_aligned(&self, size: usize) -> Option<SliceBox<T, F>> {
+    let extra = self.tag_period() - 1;
+    let chunk = self.allocate(size.checked_add(extra)?)?;
+
+    // Trim the allocation to the aligned slice
+    let mut data = self.slice_box(chunk);
+    let padding = data.as_ptr().align_offset(1 << self.tag_bits);
+    if padding > extra {
+      return None;
+    }
+    if padding + size < data.len() {
+      data.release_tail(data.len() - padding - size);
+    }
+    if padding > 0 {
+      data.release_head(padding);
+    }
+    Some(data)
+  }
+
+  /// Returns the number of elements between addresses aligned for the tag
+  /// bits, which depends on the element size.
+  fn tag_period(&self) -> usize {
+    let shift = mem::size_of::<T>().trailing_zeros().min(self.tag_bits);
+    1 << (self.tag_bits - shift)
+  }
+
+  /// Reports the errors detected by the chain through the error hook.
+  fn report(&self) {
+    if !self.checksums {
+      return;
+    }
+
+    for error in self.inner.chain.take_violations() {
+      // The hook is taken while it reports an error
+      match self
+        .error_hook
+        .try_lock()
+        .as_mut()
+        .and_then(|hook| hook.as_mut())
+      {
+        Some(hook) => hook(error),
+        None => panic!("{}", error),
+      }
+    }
+  }
+
+  /// Allocates a chunk of the size rounded up to the granularity, counting
+  /// the attempt.
+  fn allocate(&self, size: usize) -> Option<Chunk> {
+    let chunk = size
+      .checked_next_multiple_of(self.granularity)
+      .filter(|_| {
+        self
+          .max_allocations
+          .is_none_or(|max| self.allocation_count() < max)
+      })
+      .and_then(|size| self.allocate_limited(size));
+    self.inner.chain.recorder().allocation(chunk.is_some());
+    chunk
+  }
+
+  /// Allocates a chunk, evicting allocations to honor the soft limit.
+  fn allocate_limited(&self, size: usize) -> Option<Chunk> {
+    // The limit is not enforced for allocations made during eviction
+    let mut soft_limit = match self.soft_limit.try_lock() {
+      Some(soft_limit) => soft_limit,
+      None => return self.inner.chain.allocate(size),
+    };
+
+    if let Some(SoftLimit { limit, evict }) = soft_limit.as_mut() {
+      loop {
+        let total = self.inner.chain.used() + size;
+        let chunk = Some(total)
+          .filter(|&total| total <= *limit)
+          .and_then(|_| self.inner.chain.allocate(size));
+
+        if chunk.is_some() {
+          return chunk;
+        }
+
+        if !evict(total.saturating_sub(*limit).max(1)) {
+          break;
+        }
+      }
+    }
+
+    self.inner.chain.allocate(size)
+  }
+}
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Converts a uniquely held pool into another flavor, given conversions
+  /// of its slice and type-erased callbacks.
+  #[allow(clippy::type_complexity)]
+  fn into_flavor<G: Flavor>(
+    mut self,
+    slice: fn(Box<F::Slice<T>>) -> Box<G::Slice<T>>,
+    evict: fn(Box<F::Evict>) -> Box<G::Evict>,
+    release: fn(Box<F::Release>) -> Box<G::Release>,
+    hook: fn(Box<F::ErrorHook>) -> Box<G::ErrorHook>,
+    policy: fn(Box<F::Policy>) -> Box<G::Policy>,
+  ) -> Result<SlicePool<T, G>, Self> {
+    // Live allocations, including leaked ones, keep the slice and chain
+    if F::get_mut(&mut self.inner).is_none() || self.grow.is_some() {
+      return Err(self);
+    }
+
+    let inner = F::try_unwrap(self.inner).ok().expect("uniquely held pool");
+    let soft_limit = self.soft_limit.into_inner().map(|soft_limit| SoftLimit {
+      limit: soft_limit.limit,
+      evict: evict(soft_limit.evict),
+    });
+    let owners = self.owners.into_inner();
+    let list = owners.list.into_iter().map(|owner| Owner {
+      id: owner.id,
+      tag: owner.tag,
+      release: release(owner.release),
+    });
+
+    Ok(SlicePool {
+      inner: G::share(Inner {
+        chain: inner.chain.into_flavor(policy),
+        slice: slice(inner.slice),
+        #[cfg(feature = "profiling")]
+        name: inner.name,
+      }),
+      scrub: self.scrub,
+      grow: None,
+      tag_bits: self.tag_bits,
+      granularity: self.granularity,
+      max_allocations: self.max_allocations,
+      header: self.header,
+      checksums: self.checksums,
+      soft_limit: Lock::new(soft_limit),
+      owners: Lock::new(Owners {
+        next: owners.next,
+        list: list.collect(),
+      }),
+      error_hook: Lock::new(self.error_hook.into_inner().map(hook)),
+    })
+  }
+}
+
+impl<T: 'static> SlicePool<T, ThreadSafe> {
+  /// Converts the pool into a non thread-safe pool, without copying its
+  /// slice.
+  ///
+  /// The pool keeps its configuration, callbacks and identifier. It is
+  /// returned unchanged if any of its allocations are alive (including
+  /// leaked ones), or if it is growable.
+  #[allow(clippy::result_large_err)]
+  pub fn into_unsync(self) -> Result<SlicePool<T, Local>, Self> {
+    self.into_flavor(
+      |slice| Box::new(Converted::<T, ThreadSafe>(slice)) as Box<dyn unsync::Sliceable<T>>,
+      |evict| evict as Box<dyn FnMut(usize) -> bool>,
+      |release| release as Box<dyn FnMut()>,
+      |hook| hook as Box<dyn FnMut(PoolError)>,
+      |policy| policy as Box<dyn AllocPolicy>,
+    )
+  }
+}
+
+impl<T: Send + 'static> SlicePool<T, Local> {
+  /// Converts the pool into a thread-safe pool, without copying its slice.
+  ///
+  /// This allows a pool to be populated on a single thread, and shared
+  /// afterwards. It is returned unchanged if any of its allocations are
+  /// alive (including leaked ones), or if it is growable.
+  ///
+  /// # Safety
+  ///
+  /// The slice of the pool, and any callbacks registered with it, must be
+  /// safe to send to and share with other threads.
+  #[allow(clippy::result_large_err)]
+  pub unsafe fn into_sync(self) -> Result<SlicePool<T, ThreadSafe>, Self> {
+    // Auto traits do not change the layout of trait objects
+    self.into_flavor(
+      |slice| Box::new(Converted::<T, Local>(slice)) as Box<dyn sync::Sliceable<T>>,
+      |evict| unsafe { mem::transmute::<Box<dyn FnMut(usize) -> bool>, _>(evict) },
+      |release| unsafe { mem::transmute::<Box<dyn FnMut()>, _>(release) },
+      |hook| unsafe { mem::transmute::<Box<dyn FnMut(PoolError)>, _>(hook) },
+      |policy| unsafe { mem::transmute::<Box<dyn AllocPolicy>, _>(policy) },
+    )
+  }
+}
+
+/// The slice of a pool converted from another flavor.
+struct Converted<T: 'static, F: Flavor>(Box<F::Slice<T>>);
+
+// The slice is only converted to a thread-safe pool if it is thread-safe
+unsafe impl<T: 'static> Send for Converted<T, Local> {}
+unsafe impl<T: 'static> Sync for Converted<T, Local> {}
+
+impl<T: 'static, F: Flavor> AsRef<[T]> for Converted<T, F> {
+  fn as_ref(&self) -> &[T] {
+    (*self.0).as_ref()
+  }
+}
+
+impl<T: 'static, F: Flavor> AsMut<[T]> for Converted<T, F> {
+  fn as_mut(&mut self) -> &mut [T] {
+    (*self.0).as_mut()
+  }
+}
+
+/// Prints the chunks of the pool, without their elements.
+impl<T: 'static, F: Flavor> fmt::Debug for SlicePool<T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("SlicePool")
+      .field("id", &self.id())
+      .field("len", &self.len())
+      .field("used", &self.inner.chain.used())
+      .field("chunks", &self.chunks().collect::<Vec<_>>())
+      .finish()
+  }
+}
+
+/// An iterator allocating a slice for each size, until one fails.
+pub struct AllocEach<'a, T: 'static, I, F: Flavor = ThreadSafe> {
+  pool: &'a SlicePool<T, F>,
+  sizes: I,
+  allocated: usize,
+  failed: Option<usize>,
+}
+
+impl<T: 'static, I, F: Flavor> AllocEach<'_, T, I, F> {
+  /// Returns the number of successful allocations.
+  pub fn allocated(&self) -> usize {
+    self.allocated
+  }
+
+  /// Returns the size which could not be allocated, if any.
+  pub fn failed(&self) -> Option<usize> {
+    self.failed
+  }
+}
+
+impl<T: 'static, I: Iterator<Item = usize>, F: Flavor> Iterator for AllocEach<'_, T, I, F> {
+  type Item = SliceBox<T, F>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.failed.is_some() {
+      return None;
+    }
+
+    let size = self.sizes.next()?;
+    let data = self.pool.alloc(size);
+    match data {
+      Some(_) => self.allocated += 1,
+      None => self.failed = Some(size),
+    }
+    data
+  }
+}
+
+impl<T: 'static, I: Iterator<Item = usize>, F: Flavor> FusedIterator for AllocEach<'_, T, I, F> {}
+
+/// An iterator over a snapshot of the chunks of a pool.
+#[derive(Debug, Clone)]
+pub struct Chunks(vec::IntoIter<ChunkInfo>);
+
+impl Iterator for Chunks {
+  type Item = ChunkInfo;
+
+  fn next(&mut self) -> Option<ChunkInfo> {
+    self.0.next()
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.0.size_hint()
+  }
+}
+
+impl DoubleEndedIterator for Chunks {
+  fn next_back(&mut self) -> Option<ChunkInfo> {
+    self.0.next_back()
+  }
+}
+
+impl ExactSizeIterator for Chunks {}
+
+impl FusedIterator for Chunks {}
+
+/// An allocation in an owned `SlicePool`.
+///
+/// The elements live in the memory of the pool, not within the box, so
+/// they keep their address when the box is moved. With the `stable-deref`
+/// feature, it therefore implements `StableDeref` for self-referential
+/// wrappers such as `owning_ref`. Pinning with `into_pin` additionally
+/// requires the elements to be dropped before their memory is reused,
+/// which the box does by resetting them to their default value.
+pub struct SliceBox<T: 'static, F: Flavor = ThreadSafe> {
+  inner: F::Ptr<Inner<T, F>>,
+  token: ChunkToken,
+  scrub: Option<fn(&mut [T])>,
+  class: Option<usize>,
+  #[cfg(feature = "lifetime-histogram")]
+  allocated: Instant,
+  // The number of elements of the chunk preceding and following the data
+  before: usize,
+  after: usize,
+  data: &'static mut [T],
+}
+
+impl<T: 'static, F: Flavor> SliceBox<T, F> {
+  /// Returns the unique identifier of the pool the allocation belongs to.
+  pub fn pool_id(&self) -> u64 {
+    self.inner.chain.id()
+  }
+
+  /// Returns the element offset of the allocation within the pool.
+  pub fn offset(&self) -> usize {
+    self.token.offset + self.before
+  }
+
+  /// Returns whether another allocation belongs to the same pool.
+  pub fn same_pool(&self, other: &Self) -> bool {
+    F::ptr_eq(&self.inner, &other.inner)
+  }
+
+  /// Returns a C compatible descriptor of the allocation's chunk.
+  pub fn as_raw_chunk(&self) -> RawChunk {
+    RawChunk::new(self.token, self.before + self.data.len() + self.after)
+  }
+
+  /// Narrows the allocation to a range of its elements.
+  ///
+  /// The allocation keeps ownership of its entire chunk, which is released
+  /// once it is dropped, so a view can be handed to another component
+  /// without tracking the original allocation. The hidden elements are
+  /// released early if the allocation is shrunk, split off or dissolved.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the range is out of bounds, or empty while the allocation
+  /// is not.
+  pub fn slice<R: RangeBounds<usize>>(mut self, range: R) -> Self {
+    let start = match range.start_bound() {
+      Bound::Included(&start) => start,
+      Bound::Excluded(&start) => start + 1,
+      Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+      Bound::Included(&end) => end + 1,
+      Bound::Excluded(&end) => end,
+      Bound::Unbounded => self.data.len(),
+    };
+    assert!(
+      start <= end && end <= self.data.len(),
+      "slicing out of bounds"
+    );
+    assert!(
+      start < end || self.data.is_empty(),
+      "slicing an empty range"
+    );
+
+    self.before += start;
+    self.after += self.data.len() - end;
+    self.data = &mut mem::take(&mut self.data)[start..end];
+    self
+  }
+
+  /// Dissolves the allocation into a pointer and a length, without
+  /// releasing it.
+  ///
+  /// This allows ownership to pass through e.g. FFI, after which the
+  /// allocation is reconstructed with `SlicePool::slice_box_from_raw`.
+  /// Otherwise, its memory is leaked. The allocation no longer counts
+  /// towards its class, and elements hidden by `slice` are released.
+  pub fn into_raw(mut self) -> (*mut T, usize) {
+    self.trim();
+    self.uncharge(self.data.len());
+    #[cfg(feature = "profiling")]
+    self.profile(false);
+    let raw = (self.data.as_mut_ptr(), self.data.len());
+    self.disown();
+    raw
+  }
+
+  /// Detaches the allocation from its elements, returning a descriptor of
+  /// its chunk and a handle releasing it once dropped.
+  ///
+  /// Releasing an allocation locks its pool, and scrubs its elements if
+  /// enabled. Detaching is cheap, so a latency-critical thread can hand the
+  /// release off to e.g. a janitor thread. The elements are no longer
+  /// accessible, but the chunk stays allocated until the handle is dropped.
+  pub fn detach(mut self) -> (RawChunk, Releaser<T, F>) {
+    let chunk = self.as_raw_chunk();
+    // The elements are only kept if they are scrubbed once released
+    let data = match self.scrub {
+      Some(_) => {
+        self.expose(self.before, self.after);
+        mem::take(&mut self.data)
+      }
+      None => &mut [],
+    };
+    let releaser = Releaser {
+      inner: self.inner.clone(),
+      chunk,
+      class: self.class,
+      scrub: self.scrub,
+      data,
+      #[cfg(feature = "lifetime-histogram")]
+      allocated: self.allocated,
+    };
+    self.disown();
+    (chunk, releaser)
+  }
+
+  /// Leaks the allocation, returning a mutable reference to its elements.
+  ///
+  /// Like `Box::leak`, the elements live for the rest of the program. The
+  /// chunk stays allocated, and the memory of the pool is kept alive, even
+  /// after the pool is dropped. Elements hidden by `slice` are released.
+  pub fn leak(mut self) -> &'static mut [T] {
+    self.trim();
+    // The shared pointer is leaked, keeping the memory of the pool alive
+    mem::take(&mut ManuallyDrop::new(self).data)
+  }
+
+  /// Pins the allocation, so its elements cannot be moved out.
+  ///
+  /// The elements of an allocation are never moved by the pool. Once a
+  /// pinned allocation is dropped, its elements are dropped in place and
+  /// reset to their default value before the memory can be allocated again,
+  /// like the pool does with scrubbing enabled.
+  pub fn into_pin(mut self) -> Pin<Self>
+  where
+    T: Default,
+  {
+    // Pinned values must be dropped before their memory is reused
+    self.scrub = Some(scrub::<T>);
+    unsafe { Pin::new_unchecked(self) }
+  }
+
+  /// Returns a position-independent pointer to an element.
+  ///
+  /// Returns `None` if the index is out of bounds.
+  pub fn pool_ptr(&self, index: usize) -> Option<PoolPtr<T>> {
+    Some(index)
+      .filter(|&index| index < self.data.len())
+      .map(|index| PoolPtr::from_offset(self.offset() + index))
+  }
+
+  /// Resolves a pointer to an element within this allocation.
+  ///
+  /// Returns `None` if the element belongs to another allocation.
+  pub fn resolve(&self, ptr: PoolPtr<T>) -> Option<&T> {
+    let index = ptr.offset().checked_sub(self.offset())?;
+    self.data.get(index)
+  }
+
+  /// Resolves a pointer to a mutable element within this allocation.
+  ///
+  /// Returns `None` if the element belongs to another allocation.
+  pub fn resolve_mut(&mut self, ptr: PoolPtr<T>) -> Option<&mut T> {
+    let index = ptr.offset().checked_sub(self.offset())?;
+    self.data.get_mut(index)
+  }
+
+  /// Touches every page of the allocation.
+  ///
+  /// This ensures the allocation is backed by physical pages, so
+  /// latency-critical code does not incur page faults when first using it.
+  /// The contents are left unchanged.
+  pub fn prefault(&mut self) {
+    unsafe { touch(self.data.as_mut_ptr(), self.data.len()) };
+  }
+
+  /// Returns a shared copy of the allocation's current contents.
+  ///
+  /// This publishes a stable copy to any number of readers, while the
+  /// allocation itself can continue to be reused.
+  pub fn snapshot(&self) -> Arc<[T]>
+  where
+    T: Clone,
+  {
+    Arc::from(&*self.data)
+  }
+
+  /// Swaps the contents with a slice of the same length, e.g. another
+  /// allocation, which may belong to any pool.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let pool = SlicePool::new(vec![1, 2, 3, 4]);
+  /// let mut front = pool.alloc(2).unwrap();
+  /// let mut back = pool.alloc(2).unwrap();
+  /// front.swap_contents(&mut back).unwrap();
+  /// assert_eq!((&*front, &*back), (&[3, 4][..], &[1, 2][..]));
+  /// assert!(front.swap_contents(&mut [0]).is_err());
+  /// ```
+  pub fn swap_contents(&mut self, other: &mut [T]) -> Result<(), LengthError> {
+    self.check_length(other.len())?;
+    self.data.swap_with_slice(other);
+    Ok(())
+  }
+
+  /// Replaces the contents with clones of a slice of the same length.
+  pub fn replace_contents(&mut self, values: &[T]) -> Result<(), LengthError>
+  where
+    T: Clone,
+  {
+    self.check_length(values.len())?;
+    self.data.clone_from_slice(values);
+    Ok(())
+  }
+
+  fn check_length(&self, actual: usize) -> Result<(), LengthError> {
+    let expected = self.data.len();
+    if actual == expected {
+      Ok(())
+    } else {
+      Err(LengthError { expected, actual })
+    }
+  }
+
+  /// Splits the allocation in two at an index.
+  ///
+  /// The first allocation contains the elements before `mid`, the second
+  /// the remaining ones. Each of them can be released independently.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `mid` is zero or not less than the length of the allocation.
+  pub fn split_at(self, mid: usize) -> (Self, Self) {
+    match self.try_split_at(mid) {
+      Ok(halves) => halves,
+      Err(_) => panic!("splitting at the bounds"),
+    }
+  }
+
+  /// Splits the allocation in two at an index, like `split_at`.
+  ///
+  /// Returns the allocation unchanged if `mid` is zero or not less than its
+  /// length.
+  pub fn try_split_at(mut self, mid: usize) -> Result<(Self, Self), Self> {
+    if mid == 0 || mid >= self.data.len() {
+      return Err(self);
+    }
+
+    #[cfg(feature = "profiling")]
+    self.profile(false);
+    let token = self
+      .inner
+      .chain
+      .split_allocation(self.token, self.before + mid);
+    self.inner.chain.recorder().allocation(true);
+    let (head, tail) = mem::take(&mut self.data).split_at_mut(mid);
+    let after = mem::replace(&mut self.after, 0);
+    self.data = head;
+
+    let other = SliceBox {
+      inner: self.inner.clone(),
+      token,
+      scrub: self.scrub,
+      class: self.class,
+      #[cfg(feature = "lifetime-histogram")]
+      allocated: self.allocated,
+      before: 0,
+      after,
+      data: tail,
+    };
+    #[cfg(feature = "profiling")]
+    {
+      self.profile(true);
+      other.profile(true);
+    }
+    Ok((self, other))
+  }
+
+  /// Grows the allocation in place, into the free memory following it.
+  ///
+  /// Returns whether there was enough free memory, in which case the added
+  /// elements keep the values they had in the pool. The allocation is
+  /// never moved, so no elements are copied. Elements hidden by `slice`
+  /// are uncovered before any memory is allocated.
+  pub fn grow(&mut self, additional: usize) -> bool {
+    let uncovered = additional.min(self.after);
+    let additional = additional - uncovered;
+    if additional > 0
+      && !self
+        .inner
+        .chain
+        .grow_allocation(self.token, additional, self.class)
+    {
+      return false;
+    }
+
+    self.expose(0, uncovered);
+    let len = self.data.len() + additional;
+    self.data = unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr(), len) };
+    #[cfg(feature = "profiling")]
+    if additional > 0 {
+      self.profile(false);
+      self.profile(true);
+    }
+    true
+  }
+
+  /// Merges the allocation with another one adjacent to it.
+  ///
+  /// This succeeds if the allocations are contiguous in the same pool, in
+  /// either order, and belong to the same allocation class. The memory is
+  /// never released, so it cannot fail due to memory pressure. Otherwise
+  /// both allocations are returned unchanged.
+  #[allow(clippy::result_large_err)]
+  pub fn try_merge(self, other: Self) -> Result<Self, (Self, Self)> {
+    let swapped = other.data.as_ptr() < self.data.as_ptr();
+    let (mut head, tail) = if swapped {
+      (other, self)
+    } else {
+      (self, other)
+    };
+
+    // Hidden elements between the two would become visible
+    let len = tail.data.len();
+    let absorbed = match (head.after, tail.before) {
+      (0, 0) => head.absorb(tail),
+      _ => Err(tail),
+    };
+
+    match absorbed {
+      Ok(()) => {
+        head.expose(0, len);
+        Ok(head)
+      }
+      Err(tail) if swapped => Err((tail, head)),
+      Err(tail) => Err((head, tail)),
+    }
+  }
+
+  /// Merges the allocation following this one into it, hiding its elements
+  /// after the data, as if they were sliced off.
+  ///
+  /// Returns the other allocation if they are not contiguous in the same
+  /// pool, or belong to different allocation classes.
+  #[allow(clippy::result_large_err)]
+  pub(crate) fn absorb(&mut self, tail: Self) -> Result<(), Self> {
+    let merged = F::ptr_eq(&self.inner, &tail.inner)
+      && self.class == tail.class
+      && self.inner.chain.merge_allocations(self.token, tail.token);
+    if !merged {
+      return Err(tail);
+    }
+
+    #[cfg(feature = "profiling")]
+    {
+      self.profile(false);
+      tail.profile(false);
+    }
+    self.after += tail.before + tail.data.len() + tail.after;
+    #[cfg(feature = "lifetime-histogram")]
+    {
+      self.allocated = self.allocated.min(tail.allocated);
+    }
+    tail.disown();
+    self.inner.chain.recorder().release();
+    #[cfg(feature = "profiling")]
+    self.profile(true);
+    Ok(())
+  }
+
+  /// Returns the first `n` elements of the allocation to the pool.
+  ///
+  /// The remaining elements stay allocated and keep their values. Hidden
+  /// elements preceding the allocation are released too.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `n` is not less than the length of the allocation.
+  pub fn release_head(&mut self, n: usize) {
+    assert!(self.try_release_head(n), "releasing the entire allocation");
+  }
+
+  /// Returns the first `n` elements of the allocation to the pool, like
+  /// `release_head`, returning whether `n` was less than its length.
+  pub fn try_release_head(&mut self, n: usize) -> bool {
+    if n >= self.data.len() {
+      return false;
+    }
+
+    let hidden = self.before;
+    self.expose(hidden, 0);
+
+    let n = hidden + n;
+    if n > 0 {
+      #[cfg(feature = "profiling")]
+      self.profile(false);
+      let (head, tail) = mem::take(&mut self.data).split_at_mut(n);
+      if let Some(scrub) = self.scrub {
+        scrub(head);
+      }
+
+      self.inner.chain.release_head(self.token, n);
+      self.uncharge(n);
+      self.token.offset += n;
+      self.data = tail;
+      #[cfg(feature = "profiling")]
+      self.profile(true);
+    }
+    true
+  }
+
+  /// Returns the last `n` elements of the allocation to the pool.
+  ///
+  /// The remaining elements stay allocated and keep their values. Hidden
+  /// elements following the allocation are released too.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `n` is not less than the length of the allocation.
+  pub fn release_tail(&mut self, n: usize) {
+    assert!(self.try_release_tail(n), "releasing the entire allocation");
+  }
+
+  /// Returns the last `n` elements of the allocation to the pool, like
+  /// `release_tail`, returning whether `n` was less than its length.
+  pub fn try_release_tail(&mut self, n: usize) -> bool {
+    if n >= self.data.len() {
+      return false;
+    }
+
+    let hidden = self.after;
+    self.expose(0, hidden);
+
+    let n = hidden + n;
+    if n > 0 {
+      #[cfg(feature = "profiling")]
+      self.profile(false);
+      let len = self.data.len() - n;
+      let (head, tail) = mem::take(&mut self.data).split_at_mut(len);
+      if let Some(scrub) = self.scrub {
+        scrub(tail);
+      }
+
+      self.inner.chain.release_tail(self.token, n);
+      self.uncharge(n);
+      self.data = head;
+      #[cfg(feature = "profiling")]
+      self.profile(true);
+    }
+    true
+  }
+
+  /// Shrinks the allocation to its first `len` elements, returning the
+  /// rest to the pool.
+  ///
+  /// This has no effect if the allocation is not longer than `len`. The
+  /// released elements can be reused by other allocations immediately.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `len` is zero.
+  pub fn shrink(&mut self, len: usize) {
+    if len < self.data.len() {
+      self.release_tail(self.data.len() - len);
+    }
+  }
+
+  /// Releases the elements hidden by `slice`.
+  fn trim(&mut self) {
+    if self.before > 0 {
+      self.release_head(0);
+    }
+    if self.after > 0 {
+      self.release_tail(0);
+    }
+  }
+
+  /// Extends the data over elements hidden by `slice`.
+  fn expose(&mut self, before: usize, after: usize) {
+    let len = before + self.data.len() + after;
+    self.data = unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr().sub(before), len) };
+    self.before -= before;
+    self.after -= after;
+  }
+
+  /// Reports the chunk of the allocation to profilers, as allocated or
+  /// released.
+  #[cfg(feature = "profiling")]
+  fn profile(&self, allocated: bool) {
+    let data = self.data.as_ptr().wrapping_sub(self.before);
+    let size = (self.before + self.data.len() + self.after) * mem::size_of::<T>();
+    profiling::emit(self.inner.name, data, size, allocated);
+  }
+
+  /// Drops the allocation without releasing its chunk.
+  fn disown(self) {
+    let mut data = ManuallyDrop::new(self);
+    unsafe { ptr::drop_in_place(&mut data.inner) };
+  }
+
+  /// Removes released elements from the usage of the allocation's class.
+  fn uncharge(&self, size: usize) {
+    if let Some(class) = self.class {
+      self.inner.chain.uncharge(class, size);
+    }
+  }
+}
+
+impl<W: Plain, F: Flavor> SliceBox<W, F> {
+  /// Converts the allocation into a byte-granular pool over its memory.
+  ///
+  /// This allows a pool of larger elements, chosen for their alignment, to
+  /// also serve arbitrary byte allocations from the same backing slice.
+  /// The chunk is returned to the original pool once the byte pool and all
+  /// of its allocations have been dropped.
+  pub fn into_byte_pool(self) -> SlicePool<u8, F>
+  where
+    ByteRegion<W, F>: Erase<F, F::Slice<u8>>,
+  {
+    SlicePool::new(ByteRegion(self))
+  }
+}
+
+/// The bytes of an allocation of plain elements.
+pub struct ByteRegion<W: Plain, F: Flavor>(SliceBox<W, F>);
+
+impl<W: Plain, F: Flavor> AsRef<[u8]> for ByteRegion<W, F> {
+  fn as_ref(&self) -> &[u8] {
+    let len = mem::size_of_val::<[W]>(&self.0);
+    unsafe { slice::from_raw_parts(self.0.as_ptr() as *const u8, len) }
+  }
+}
+
+impl<W: Plain, F: Flavor> AsMut<[u8]> for ByteRegion<W, F> {
+  fn as_mut(&mut self) -> &mut [u8] {
+    let len = mem::size_of_val::<[W]>(&self.0);
+    unsafe { slice::from_raw_parts_mut(self.0.as_mut_ptr() as *mut u8, len) }
+  }
+}
+
+impl<F: Flavor> SliceBox<u8, F> {
+  /// Synchronizes the instruction cache after machine code has been written
+  /// to the allocation.
+  ///
+  /// This must be called before executing the code on architectures with
+  /// separate instruction and data caches, such as AArch64. It is only
+  /// available on x86 and AArch64 targets.
+  #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+  pub fn finalize_code(&self) {
+    ::icache::flush(self.data.as_ptr(), self.data.len());
+  }
+}
+
+impl<T: 'static, F: Flavor> Deref for SliceBox<T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &Self::Target {
+    self.data
+  }
+}
+
+// The elements live in the memory of the pool, which outlives the box
+#[cfg(feature = "stable-deref")]
+unsafe impl<T: 'static, F: Flavor> StableDeref for SliceBox<T, F> {}
+
+impl<T: 'static, F: Flavor> DerefMut for SliceBox<T, F> {
+  fn deref_mut(&mut self) -> &mut [T] {
+    self.data
+  }
+}
+
+impl<T: 'static, F: Flavor> Drop for SliceBox<T, F> {
+  /// Returns the ownership of the slice to the pool.
+  fn drop(&mut self) {
+    #[cfg(feature = "profiling")]
+    self.profile(false);
+    if let Some(scrub) = self.scrub {
+      self.expose(self.before, self.after);
+      scrub(self.data);
+    }
+
+    #[cfg(feature = "lifetime-histogram")]
+    self.inner.chain.record_lifetime(self.allocated.elapsed());
+    self.inner.release(self.token, self.class);
+  }
+}
+
+impl<T: fmt::Debug + 'static, F: Flavor> fmt::Debug for SliceBox<T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+/// Copies the elements to the heap, releasing the allocation.
+impl<T: Clone + 'static, F: Flavor> From<SliceBox<T, F>> for Box<[T]> {
+  fn from(data: SliceBox<T, F>) -> Self {
+    Box::from(&*data)
+  }
+}
+
+/// Copies the elements to the heap, releasing the allocation.
+impl<T: Clone + 'static, F: Flavor> From<SliceBox<T, F>> for Vec<T> {
+  fn from(data: SliceBox<T, F>) -> Self {
+    data.to_vec()
+  }
+}
+
+/// A scratch allocation of `SlicePool::with_alloc`, released once dropped.
+struct Scratch<'a, T: 'static, F: Flavor> {
+  pool: &'a SlicePool<T, F>,
+  token: ChunkToken,
+  #[cfg(feature = "lifetime-histogram")]
+  allocated: Instant,
+  data: &'a mut [T],
+}
+
+impl<'a, T: 'static, F: Flavor> Drop for Scratch<'a, T, F> {
+  fn drop(&mut self) {
+    #[cfg(feature = "profiling")]
+    profiling::emit(
+      self.pool.inner.name,
+      self.data.as_ptr(),
+      mem::size_of_val(self.data),
+      false,
+    );
+    if let Some(scrub) = self.pool.scrub {
+      scrub(self.data);
+    }
+
+    #[cfg(feature = "lifetime-histogram")]
+    self
+      .pool
+      .inner
+      .chain
+      .record_lifetime(self.allocated.elapsed());
+    self.pool.inner.chain.release(self.token, None);
+    self.pool.inner.chain.recorder().release();
+  }
+}
+
+/// A handle releasing a detached allocation once dropped.
+///
+/// This is returned by `SliceBox::detach`. It only holds the pool and the
+/// chunk to release, along with its elements if they are scrubbed.
+pub struct Releaser<T: 'static, F: Flavor = ThreadSafe> {
+  inner: F::Ptr<Inner<T, F>>,
+  chunk: RawChunk,
+  class: Option<usize>,
+  scrub: Option<fn(&mut [T])>,
+  data: &'static mut [T],
+  #[cfg(feature = "lifetime-histogram")]
+  allocated: Instant,
+}
+
+impl<T: 'static, F: Flavor> Releaser<T, F> {
+  /// Returns a descriptor of the chunk released by the handle.
+  pub fn chunk(&self) -> RawChunk {
+    self.chunk
+  }
+
+  /// Releases the allocation.
+  pub fn release(self) {}
+}
+
+impl<T: 'static, F: Flavor> Drop for Releaser<T, F> {
+  fn drop(&mut self) {
+    #[cfg(feature = "profiling")]
+    profiling::emit(
+      self.inner.name,
+      (*self.inner.slice)
+        .as_ref()
+        .as_ptr()
+        .wrapping_add(self.chunk.offset),
+      self.chunk.len * mem::size_of::<T>(),
+      false,
+    );
+    if let Some(scrub) = self.scrub {
+      scrub(self.data);
+    }
+
+    #[cfg(feature = "lifetime-histogram")]
+    self.inner.chain.record_lifetime(self.allocated.elapsed());
+    self.inner.release(self.chunk.token(), self.class);
+  }
+}
+
+impl<T: 'static, F: Flavor> fmt::Debug for Releaser<T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_tuple("Releaser").field(&self.chunk()).finish()
+  }
+}
+
+/// The stride in bytes of touched pages, which is the smallest page size
+/// of common targets.
+const PAGE_SIZE: usize = 4096;
+
+/// Writes a byte of every page spanned by a range of elements, without
+/// changing its value.
+unsafe fn touch<T>(base: *mut T, len: usize) {
+  let bytes = base as *mut MaybeUninit<u8>;
+  let size = len * mem::size_of::<T>();
+  let offsets = (0..size).step_by(PAGE_SIZE).chain(size.checked_sub(1));
+  for offset in offsets {
+    let byte = bytes.add(offset);
+    ptr::write_volatile(byte, ptr::read_volatile(byte));
+  }
+}
+
+/// Resets each element to its default value, dropping the previous one.
+fn scrub<T: Default>(data: &mut [T]) {
+  for value in data {
+    *value = T::default();
+  }
+}
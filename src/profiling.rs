@@ -0,0 +1,25 @@
+use std::ffi::CStr;
+use tracy_client_sys as sys;
+
+/// The profiling name of pools which have not been named.
+pub const DEFAULT_NAME: &CStr = match CStr::from_bytes_with_nul(b"slice-pool\0") {
+  Ok(name) => name,
+  Err(_) => panic!("invalid pool name"),
+};
+
+/// Reports a chunk of a named pool to Tracy, as allocated or released.
+///
+/// Empty chunks have no distinct address, so they are never reported.
+pub fn emit<T>(name: &'static CStr, data: *const T, size: usize, allocated: bool) {
+  if size == 0 {
+    return;
+  }
+
+  unsafe {
+    if allocated {
+      sys::___tracy_emit_memory_alloc_named(data.cast(), size, name.as_ptr());
+    } else {
+      sys::___tracy_emit_memory_free_named(data.cast(), name.as_ptr());
+    }
+  }
+}
@@ -0,0 +1,283 @@
+use flavor::Flavor;
+use std::fmt;
+use std::io::{self, Read};
+use {ErrorCode, SliceBox, SlicePool, ThreadSafe};
+
+/// Reads a stream into frames allocated from a byte `SlicePool`.
+///
+/// Data is read ahead into a pooled buffer, and each completed frame is
+/// split off the buffer as its own `SliceBox`, so frames are never copied
+/// unless the buffer has to move to make room for a partial frame. Frames
+/// return their memory to the pool once dropped, where it is reused by
+/// later buffers.
+///
+/// ```
+/// use slice_pool::sync::{PooledReader, SlicePool};
+///
+/// let pool = SlicePool::new(vec![0u8; 64]);
+/// let stream = &b"first\nsecond\nrest"[..];
+/// let frames = PooledReader::delimited(&pool, stream, b'\n', 16)
+///   .collect::<Result<Vec<_>, _>>()
+///   .unwrap();
+/// assert_eq!(frames[1].as_ref(), b"second\n");
+/// assert_eq!(frames[2].as_ref(), b"rest");
+/// ```
+pub struct PooledReader<'a, R, F: Flavor = ThreadSafe> {
+  pool: &'a SlicePool<u8, F>,
+  reader: R,
+  boundary: Boundary,
+  capacity: usize,
+  /// The read-ahead buffer, starting with the bytes of the next frame.
+  buffer: Option<SliceBox<u8, F>>,
+  /// The number of bytes read into the buffer.
+  filled: usize,
+  /// Whether the end of the stream has been reached, or reading stopped.
+  done: bool,
+}
+
+/// The end of a frame.
+#[derive(Debug, Copy, Clone)]
+enum Boundary {
+  Delimiter(u8),
+  Fixed(usize),
+}
+
+impl<'a, R: Read, F: Flavor> PooledReader<'a, R, F> {
+  /// Constructs a reader of frames ending with a delimiter, which is
+  /// included in each frame.
+  ///
+  /// The capacity is the size of the read-ahead buffer, and the longest
+  /// possible frame. Once a longer frame is encountered, an error is
+  /// returned and reading stops. Any bytes following the last delimiter are
+  /// returned as a final frame.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `capacity` is zero.
+  pub fn delimited(pool: &'a SlicePool<u8, F>, reader: R, delimiter: u8, capacity: usize) -> Self {
+    Self::new(pool, reader, Boundary::Delimiter(delimiter), capacity)
+  }
+
+  /// Constructs a reader of frames with `len` bytes each.
+  ///
+  /// If the stream ends within a frame, an error is returned instead.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `len` is zero.
+  pub fn fixed(pool: &'a SlicePool<u8, F>, reader: R, len: usize) -> Self {
+    Self::new(pool, reader, Boundary::Fixed(len), len)
+  }
+
+  fn new(pool: &'a SlicePool<u8, F>, reader: R, boundary: Boundary, capacity: usize) -> Self {
+    assert!(capacity > 0, "zero capacity");
+    PooledReader {
+      pool,
+      reader,
+      boundary,
+      capacity,
+      buffer: None,
+      filled: 0,
+      done: false,
+    }
+  }
+
+  /// Returns the bytes read ahead, which precede the next frame.
+  pub fn buffered(&self) -> &[u8] {
+    self
+      .buffer
+      .as_ref()
+      .map_or(&[], |buffer| &buffer[..self.filled])
+  }
+
+  /// Returns the underlying reader.
+  pub fn get_ref(&self) -> &R {
+    &self.reader
+  }
+
+  /// Returns the underlying reader mutably.
+  ///
+  /// Reading from it directly skips the bytes in between frames.
+  pub fn get_mut(&mut self) -> &mut R {
+    &mut self.reader
+  }
+
+  /// Returns the underlying reader, discarding the buffered bytes.
+  pub fn into_inner(self) -> R {
+    self.reader
+  }
+
+  /// Reads until a frame is completed, or the stream ends.
+  fn next_frame(&mut self) -> io::Result<Option<SliceBox<u8, F>>> {
+    loop {
+      if let Some(len) = self.frame_len() {
+        return Ok(Some(self.take(len)));
+      }
+
+      if self.done {
+        if self.filled == 0 {
+          return Ok(None);
+        }
+        return match self.boundary {
+          Boundary::Delimiter(_) => Ok(Some(self.take(self.filled))),
+          Boundary::Fixed(_) => {
+            Err(self.stop(io::ErrorKind::UnexpectedEof, "stream ends within a frame"))
+          }
+        };
+      }
+      self.fill()?;
+    }
+  }
+
+  /// Returns the length of the first frame, if it has been read entirely.
+  fn frame_len(&self) -> Option<usize> {
+    let buffered = self.buffered();
+    match self.boundary {
+      Boundary::Delimiter(delimiter) => buffered
+        .iter()
+        .position(|&byte| byte == delimiter)
+        .map(|index| index + 1),
+      Boundary::Fixed(len) => Some(len).filter(|&len| buffered.len() >= len),
+    }
+  }
+
+  /// Splits the first `len` buffered bytes off as a frame.
+  fn take(&mut self, len: usize) -> SliceBox<u8, F> {
+    let buffer = self.buffer.take().expect("buffered frame");
+    self.filled -= len;
+    match buffer.try_split_at(len) {
+      Ok((frame, mut rest)) => {
+        // Read ahead into the memory following the buffer, if it is free
+        rest.grow(self.capacity.saturating_sub(rest.len()));
+        self.buffer = Some(rest);
+        frame
+      }
+      Err(frame) => frame,
+    }
+  }
+
+  /// Reads more bytes into the buffer, making room for them if needed.
+  fn fill(&mut self) -> io::Result<()> {
+    let full = self
+      .buffer
+      .as_ref()
+      .is_none_or(|buffer| buffer.len() == self.filled);
+    if full {
+      if self.filled >= self.capacity {
+        return Err(self.stop(io::ErrorKind::InvalidData, "frame exceeds the capacity"));
+      }
+
+      let capacity = self.capacity;
+      let grown = self
+        .buffer
+        .as_mut()
+        .is_some_and(|buffer| buffer.grow(capacity - buffer.len()));
+      if !grown {
+        // The partial frame moves to a new buffer, releasing the old one
+        let mut buffer = self
+          .pool
+          .try_alloc(self.capacity)
+          .map_err(|error| io::Error::from(ErrorCode::from(error)))?;
+        buffer[..self.filled].copy_from_slice(self.buffered());
+        self.buffer = Some(buffer);
+      }
+    }
+
+    let buffer = self.buffer.as_mut().expect("read-ahead buffer");
+    match self.reader.read(&mut buffer[self.filled..]) {
+      Ok(0) => self.done = true,
+      Ok(read) => self.filled += read,
+      Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+      Err(error) => return Err(error),
+    }
+    Ok(())
+  }
+
+  /// Stops reading, discarding the buffered bytes.
+  fn stop(&mut self, kind: io::ErrorKind, message: &str) -> io::Error {
+    self.buffer = None;
+    self.filled = 0;
+    self.done = true;
+    io::Error::new(kind, message)
+  }
+}
+
+impl<'a, R: Read, F: Flavor> Iterator for PooledReader<'a, R, F> {
+  type Item = io::Result<SliceBox<u8, F>>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.next_frame().transpose()
+  }
+}
+
+impl<'a, R, F: Flavor> fmt::Debug for PooledReader<'a, R, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("PooledReader")
+      .field("boundary", &self.boundary)
+      .field("capacity", &self.capacity)
+      .field("filled", &self.filled)
+      .field("done", &self.done)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Reads at most a few bytes at a time, like a socket.
+  struct Trickle<'a>(&'a [u8]);
+
+  impl<'a> Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      let len = buf.len().min(self.0.len()).min(4);
+      buf[..len].copy_from_slice(&self.0[..len]);
+      self.0 = &self.0[len..];
+      Ok(len)
+    }
+  }
+
+  #[test]
+  fn reader_delimited() {
+    let pool = SlicePool::<u8, ThreadSafe>::new(vec![0; 32]);
+    let mut reader = PooledReader::delimited(&pool, Trickle(b"ab;cdefg;;hi"), b';', 8);
+
+    let first = reader.next().unwrap().unwrap();
+    assert_eq!(
+      (first.as_ref(), reader.buffered()),
+      (&b"ab;"[..], &b"c"[..])
+    );
+    let frames = reader.by_ref().collect::<io::Result<Vec<_>>>().unwrap();
+    let frames = frames
+      .iter()
+      .map(|frame| frame.as_ref())
+      .collect::<Vec<_>>();
+    assert_eq!(frames, [&b"cdefg;"[..], b";", b"hi"]);
+    assert!(reader.next().is_none());
+    drop(reader);
+    assert_eq!(pool.stats().used, 3 + 6 + 1 + 2);
+
+    // Frames longer than the capacity stop the reader
+    let mut reader = PooledReader::delimited(&pool, &b"abcdefghij;"[..], b';', 4);
+    let error = reader.next().unwrap().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(reader.next().is_none());
+  }
+
+  #[test]
+  fn reader_fixed() {
+    let pool = SlicePool::<u8, ThreadSafe>::new(vec![0; 8]);
+    let mut reader = PooledReader::fixed(&pool, Trickle(b"abcdefghij"), 4);
+    let first = reader.next().unwrap().unwrap();
+    let second = reader.next().unwrap().unwrap();
+    assert_eq!((&*first, &*second), (&b"abcd"[..], &b"efgh"[..]));
+
+    // Frames in use keep the pool from buffering the next one
+    let error = reader.next().unwrap().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::OutOfMemory);
+    drop((first, second));
+    let error = reader.next().unwrap().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(reader.next().is_none());
+  }
+}
@@ -0,0 +1,127 @@
+use flavor::Flavor;
+use std::fmt;
+use {Order, SliceBox, SlicePool, ThreadSafe};
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Reserves the `n` elements following an allocation, so it can later
+  /// grow in place even if the pool is under pressure.
+  ///
+  /// The reserved elements are not available to any other allocation, and
+  /// are released once the reservation is dropped. Returns `None` if the
+  /// memory following the allocation is not free, or `data` was allocated
+  /// from a different pool.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  ///
+  /// let pool = SlicePool::new(vec![0; 8]);
+  /// let mut data = pool.alloc(2).unwrap();
+  /// let mut reservation = pool.reserve_tail(&data, 4).unwrap();
+  /// assert!(pool.alloc(3).is_none());
+  ///
+  /// assert!(reservation.grow(&mut data, 3));
+  /// assert_eq!((data.len(), reservation.len()), (5, 1));
+  /// drop(reservation);
+  /// assert_eq!(pool.available(), 3);
+  /// ```
+  pub fn reserve_tail(&self, data: &SliceBox<T, F>, n: usize) -> Option<TailReservation<T, F>> {
+    let reserved = self.alloc_contiguous_with(data, n, Order::Following)?;
+    Some(TailReservation {
+      reserved: Some(reserved),
+    })
+  }
+}
+
+/// Elements reserved for an allocation to grow into.
+///
+/// The reservation is consumed from its start as the allocation grows, and
+/// the rest is released once it is dropped.
+pub struct TailReservation<T: 'static, F: Flavor = ThreadSafe> {
+  reserved: Option<SliceBox<T, F>>,
+}
+
+impl<T: 'static, F: Flavor> TailReservation<T, F> {
+  /// Returns the number of reserved elements.
+  pub fn len(&self) -> usize {
+    self.reserved.as_ref().map_or(0, |reserved| reserved.len())
+  }
+
+  /// Returns whether every reserved element has been used.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Grows an allocation in place by `additional` reserved elements.
+  ///
+  /// The added elements keep the values they had in the pool. Returns
+  /// whether enough elements are reserved, and the allocation is the one
+  /// they follow. Elements hidden by `slice` are uncovered first, like
+  /// `SliceBox::grow` does. The allocation cannot grow into a reservation
+  /// if it belongs to an allocation class.
+  pub fn grow(&mut self, data: &mut SliceBox<T, F>, additional: usize) -> bool {
+    if additional == 0 {
+      return true;
+    }
+    if additional > self.len() {
+      return false;
+    }
+
+    let reserved = self.reserved.take().expect("reserved elements");
+    let (head, rest) = match reserved.try_split_at(additional) {
+      Ok((head, rest)) => (head, Some(rest)),
+      Err(reserved) => (reserved, None),
+    };
+
+    match data.absorb(head) {
+      Ok(()) => {
+        self.reserved = rest;
+        assert!(data.grow(additional), "growing into absorbed elements");
+        true
+      }
+      Err(head) => {
+        let reserved = match rest {
+          Some(rest) => head.try_merge(rest).ok().expect("rejoining a reservation"),
+          None => head,
+        };
+        self.reserved = Some(reserved);
+        false
+      }
+    }
+  }
+}
+
+impl<T: 'static, F: Flavor> fmt::Debug for TailReservation<T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("TailReservation")
+      .field("len", &self.len())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pool_reserve_tail() {
+    let pool = SlicePool::<_, ThreadSafe>::new(vec![0; 10]);
+    let mut data = pool.alloc(2).unwrap();
+    let mut other = pool.alloc(1).unwrap();
+    assert!(pool.reserve_tail(&data, 1).is_none());
+    drop(other);
+
+    let mut reservation = pool.reserve_tail(&data, 5).unwrap();
+    other = pool.alloc(3).unwrap();
+    assert!(!data.grow(1));
+    assert!(!reservation.grow(&mut other, 1));
+    assert!(!reservation.grow(&mut data, 6));
+    assert_eq!(reservation.len(), 5);
+
+    // The allocation grows into the reservation, until it is used up
+    assert!(reservation.grow(&mut data, 2));
+    assert!(reservation.grow(&mut data, 3));
+    assert_eq!((data.len(), reservation.is_empty()), (7, true));
+    assert_eq!(pool.stats().used, 10);
+    assert_eq!(pool.validate(), Ok(()));
+  }
+}
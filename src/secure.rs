@@ -0,0 +1,123 @@
+use std::marker::PhantomData;
+use std::{io, mem, ptr};
+use Plain;
+
+/// A slice locked into memory, suitable for sensitive data.
+///
+/// The memory is locked into RAM, so it is never written to swap, and is
+/// excluded from core dumps where supported. It is zeroed and unlocked once
+/// the slice is dropped. Locking applies to entire pages, so it extends to
+/// any other data sharing the pages of the slice.
+pub struct SecureSlice<S: AsRef<[T]> + AsMut<[T]>, T: Plain> {
+  slice: S,
+  _marker: PhantomData<T>,
+}
+
+impl<S: AsRef<[T]> + AsMut<[T]>, T: Plain> SecureSlice<S, T> {
+  /// Locks the memory of a slice.
+  pub fn new(mut slice: S) -> io::Result<Self> {
+    let data = slice.as_mut();
+    unsafe { lock(data.as_mut_ptr() as *mut u8, mem::size_of_val(data))? };
+
+    Ok(SecureSlice {
+      slice,
+      _marker: PhantomData,
+    })
+  }
+}
+
+impl<S: AsRef<[T]> + AsMut<[T]>, T: Plain> AsRef<[T]> for SecureSlice<S, T> {
+  fn as_ref(&self) -> &[T] {
+    self.slice.as_ref()
+  }
+}
+
+impl<S: AsRef<[T]> + AsMut<[T]>, T: Plain> AsMut<[T]> for SecureSlice<S, T> {
+  fn as_mut(&mut self) -> &mut [T] {
+    self.slice.as_mut()
+  }
+}
+
+impl<S: AsRef<[T]> + AsMut<[T]>, T: Plain> Drop for SecureSlice<S, T> {
+  fn drop(&mut self) {
+    let data = self.slice.as_mut();
+    zero(data);
+    let _ = unsafe { unlock(data.as_mut_ptr() as *mut u8, mem::size_of_val(data)) };
+  }
+}
+
+/// Zeroes plain elements, without the writes being optimized away.
+pub fn zero<T: Plain>(data: &mut [T]) {
+  for value in data {
+    unsafe { ptr::write_volatile(value, mem::zeroed()) };
+  }
+}
+
+#[cfg(unix)]
+unsafe fn lock(base: *mut u8, len: usize) -> io::Result<()> {
+  if len == 0 {
+    return Ok(());
+  }
+
+  if libc::mlock(base as *const _, len) != 0 {
+    return Err(io::Error::last_os_error());
+  }
+
+  // Advice only applies to entire pages, starting at a page boundary
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  {
+    let page = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+    let start = base as usize & !(page - 1);
+    let end = base as usize + len;
+    if libc::madvise(start as *mut _, end - start, libc::MADV_DONTDUMP) != 0 {
+      let error = io::Error::last_os_error();
+      libc::munlock(base as *const _, len);
+      return Err(error);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(unix)]
+unsafe fn unlock(base: *mut u8, len: usize) -> io::Result<()> {
+  if len > 0 && libc::munlock(base as *const _, len) != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+  fn VirtualLock(address: *mut u8, size: usize) -> i32;
+  fn VirtualUnlock(address: *mut u8, size: usize) -> i32;
+}
+
+#[cfg(windows)]
+unsafe fn lock(base: *mut u8, len: usize) -> io::Result<()> {
+  if len > 0 && VirtualLock(base, len) == 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(windows)]
+unsafe fn unlock(base: *mut u8, len: usize) -> io::Result<()> {
+  if len > 0 && VirtualUnlock(base, len) == 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+unsafe fn lock(_base: *mut u8, _len: usize) -> io::Result<()> {
+  Err(io::Error::new(
+    io::ErrorKind::Unsupported,
+    "memory cannot be locked on this platform",
+  ))
+}
+
+#[cfg(not(any(unix, windows)))]
+unsafe fn unlock(_base: *mut u8, _len: usize) -> io::Result<()> {
+  Ok(())
+}
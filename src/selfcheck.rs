@@ -0,0 +1,239 @@
+use flavor::Flavor;
+use std::fmt;
+use {ChainError, SliceBox, SlicePool, Strategy};
+
+/// The sizes of the allocations made by a self-check, in order.
+const SIZES: [usize; 8] = [1, 2, 3, 5, 8, 13, 21, 34];
+
+/// The outcome of `SlicePool::self_check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfCheckReport {
+  /// The strategy in effect when the check was made.
+  pub strategy: Strategy,
+  /// The number of test allocations made, including reallocations.
+  pub allocations: usize,
+  /// The number of test allocations skipped for lack of free memory.
+  pub skipped: usize,
+  /// The problems found, in the order they were detected.
+  pub issues: Vec<SelfCheckIssue>,
+}
+
+impl SelfCheckReport {
+  /// Returns whether no problems were found.
+  pub fn is_ok(&self) -> bool {
+    self.issues.is_empty()
+  }
+}
+
+/// A problem found by `SlicePool::self_check`.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SelfCheckIssue {
+  /// The chunks of the pool are inconsistent.
+  Inconsistent(ChainError),
+  /// An allocation exceeds the bounds of the pool.
+  OutOfBounds {
+    /// The element offset of the allocation.
+    offset: usize,
+    /// The number of elements in the allocation.
+    len: usize,
+  },
+  /// An allocation overlaps another live allocation.
+  Overlapping {
+    /// The element offset of the allocation.
+    offset: usize,
+    /// The element offset of the allocation it overlaps.
+    other: usize,
+  },
+  /// An allocation is not aligned for the configured tag bits.
+  Misaligned {
+    /// The element offset of the allocation.
+    offset: usize,
+    /// The required alignment, in bytes.
+    align: usize,
+  },
+  /// Memory which was just released could not be allocated again.
+  NotReused {
+    /// The number of requested elements.
+    size: usize,
+  },
+  /// Releasing every test allocation did not restore the free elements.
+  Leaked {
+    /// The number of free elements before the check.
+    expected: usize,
+    /// The number of free elements after the check.
+    available: usize,
+  },
+}
+
+impl fmt::Display for SelfCheckIssue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SelfCheckIssue::Inconsistent(error) => write!(f, "inconsistent chunks: {}", error),
+      SelfCheckIssue::OutOfBounds { offset, len } => write!(
+        f,
+        "allocation at offset {} ({} elements) exceeds the pool",
+        offset, len
+      ),
+      SelfCheckIssue::Overlapping { offset, other } => write!(
+        f,
+        "allocation at offset {} overlaps the allocation at offset {}",
+        offset, other
+      ),
+      SelfCheckIssue::Misaligned { offset, align } => write!(
+        f,
+        "allocation at offset {} is not aligned to {} bytes",
+        offset, align
+      ),
+      SelfCheckIssue::NotReused { size } => {
+        write!(f, "released memory for {} elements was not reused", size)
+      }
+      SelfCheckIssue::Leaked {
+        expected,
+        available,
+      } => write!(
+        f,
+        "{} of {} free elements remain after releasing every allocation",
+        available, expected
+      ),
+    }
+  }
+}
+
+impl<T: 'static, F: Flavor> SlicePool<T, F> {
+  /// Tests the pool with its current configuration, e.g. at startup.
+  ///
+  /// The pool is validated, a pattern of allocations is made, released in
+  /// an interleaved order and made again, and every allocation is checked
+  /// against the bounds of the pool, the other allocations and the
+  /// alignment of the tag bits. The pool is then validated again, and must
+  /// have as many free elements as before. Allocations which do not fit
+  /// are skipped, and live allocations are left untouched, but the check
+  /// must not run concurrently with other threads using the pool.
+  ///
+  /// ```
+  /// use slice_pool::{sync::SlicePool, Strategy};
+  ///
+  /// let pool = SlicePool::builder(vec![0u8; 256])
+  ///   .strategy(Strategy::Tlsf)
+  ///   .tag_bits(2)
+  ///   .build();
+  /// let report = pool.self_check();
+  /// assert!(report.is_ok(), "{:?}", report.issues);
+  /// assert_eq!(report.strategy, Strategy::Tlsf);
+  /// ```
+  pub fn self_check(&self) -> SelfCheckReport {
+    let mut report = SelfCheckReport {
+      strategy: self.active_strategy(),
+      allocations: 0,
+      skipped: 0,
+      issues: Vec::new(),
+    };
+    if let Err(error) = self.validate() {
+      report.issues.push(SelfCheckIssue::Inconsistent(error));
+    }
+
+    let expected = self.available();
+    let mut live = Vec::new();
+    for &size in &SIZES {
+      self.check_alloc(size, &mut live, &mut report);
+    }
+
+    // Release every other allocation, leaving holes for the sizes to reuse
+    let mut index = 0;
+    let mut released = Vec::new();
+    live.retain(|data: &SliceBox<T, F>| {
+      index += 1;
+      if index % 2 == 0 {
+        released.push(data.len());
+      }
+      index % 2 == 1
+    });
+    for size in released {
+      if !self.check_alloc(size, &mut live, &mut report) {
+        report.issues.push(SelfCheckIssue::NotReused { size });
+      }
+    }
+
+    drop(live);
+    if let Err(error) = self.validate() {
+      report.issues.push(SelfCheckIssue::Inconsistent(error));
+    }
+    let available = self.available();
+    if available != expected {
+      report.issues.push(SelfCheckIssue::Leaked {
+        expected,
+        available,
+      });
+    }
+    report
+  }
+
+  /// Makes a test allocation and checks it against the live ones, returning
+  /// whether it was made.
+  fn check_alloc(
+    &self,
+    size: usize,
+    live: &mut Vec<SliceBox<T, F>>,
+    report: &mut SelfCheckReport,
+  ) -> bool {
+    let data = match self.alloc(size) {
+      Some(data) => data,
+      None => {
+        report.skipped += 1;
+        return false;
+      }
+    };
+    report.allocations += 1;
+
+    let (offset, len) = (data.offset(), data.len());
+    if offset + len > self.len() {
+      report
+        .issues
+        .push(SelfCheckIssue::OutOfBounds { offset, len });
+    }
+    if let Some(other) = live
+      .iter()
+      .find(|other| offset < other.offset() + other.len() && other.offset() < offset + len)
+    {
+      report.issues.push(SelfCheckIssue::Overlapping {
+        offset,
+        other: other.offset(),
+      });
+    }
+
+    let align = 1usize << self.tag_bits();
+    if !(data.as_ptr() as usize).is_multiple_of(align) {
+      report
+        .issues
+        .push(SelfCheckIssue::Misaligned { offset, align });
+    }
+    live.push(data);
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ThreadSafe;
+
+  #[test]
+  fn pool_self_check() {
+    let pool = SlicePool::<_, ThreadSafe>::new(vec![0u32; 40]).with_header(4);
+    let _live = pool.alloc(6).unwrap();
+    let report = pool.self_check();
+    assert!(report.is_ok(), "{:?}", report.issues);
+    assert_eq!((report.allocations, report.skipped), (7, 3));
+    assert_eq!((pool.allocation_count(), pool.available()), (1, 30));
+
+    let issue = SelfCheckIssue::Leaked {
+      expected: 30,
+      available: 29,
+    };
+    assert_eq!(
+      issue.to_string(),
+      "29 of 30 free elements remain after releasing every allocation"
+    );
+  }
+}
@@ -0,0 +1,115 @@
+use {ChunkInfo, ChunkState};
+
+/// A reference allocator, mirroring the occupancy of a chain.
+///
+/// The shadow tracks the state of every element in a flat vector, which is
+/// far too slow for regular use, but simple enough to be obviously correct.
+/// The chain updates it alongside its own bookkeeping, and verifies that
+/// both agree once it is unlocked, so a bug in the chain is caught by the
+/// operation which caused it.
+pub struct Shadow(Vec<ChunkState>);
+
+impl Shadow {
+  /// Constructs a shadow of free elements, preceded by a reserved header.
+  pub fn new(len: usize, header: usize) -> Self {
+    let mut shadow = Shadow(vec![ChunkState::Free; len]);
+    shadow.mark(0, header, ChunkState::Reserved);
+    shadow
+  }
+
+  /// Appends free elements.
+  pub fn extend(&mut self, additional: usize) {
+    let len = self.0.len();
+    self.0.resize(len + additional, ChunkState::Free);
+  }
+
+  /// Changes the state of a range of elements.
+  ///
+  /// # Panics
+  ///
+  /// Panics if any of the elements already has the state, e.g. if an
+  /// allocation overlaps another, or memory is released twice.
+  pub fn mark(&mut self, offset: usize, len: usize, state: ChunkState) {
+    for (index, element) in self.0[offset..offset + len].iter_mut().enumerate() {
+      assert_ne!(
+        *element,
+        state,
+        "shadow: element {} is already {:?}",
+        offset + index,
+        state
+      );
+      *element = state;
+    }
+  }
+
+  /// Verifies that chunks describe the same elements as the shadow.
+  ///
+  /// # Panics
+  ///
+  /// Panics at the first element whose state differs.
+  pub fn verify(&self, chunks: &[ChunkInfo]) {
+    let mut states = chunks.iter().flat_map(|chunk| {
+      (chunk.offset..chunk.offset + chunk.len).map(move |offset| (offset, chunk.state))
+    });
+
+    for (index, &expected) in self.0.iter().enumerate() {
+      let (offset, state) = states
+        .next()
+        .unwrap_or_else(|| panic!("shadow: element {} is not covered by any chunk", index));
+      assert_eq!(
+        (offset, state),
+        (index, expected),
+        "shadow: chunks diverged at element {}",
+        index
+      );
+    }
+    assert!(states.next().is_none(), "shadow: chunks exceed the pool");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn chunk(offset: usize, len: usize, state: ChunkState) -> ChunkInfo {
+    ChunkInfo { offset, len, state }
+  }
+
+  #[test]
+  fn shadow_verify() {
+    let mut shadow = Shadow::new(6, 1);
+    shadow.mark(1, 2, ChunkState::Allocated);
+    shadow.verify(&[
+      chunk(0, 1, ChunkState::Reserved),
+      chunk(1, 2, ChunkState::Allocated),
+      chunk(3, 3, ChunkState::Free),
+    ]);
+
+    shadow.extend(2);
+    shadow.verify(&[
+      chunk(0, 1, ChunkState::Reserved),
+      chunk(1, 2, ChunkState::Allocated),
+      chunk(3, 5, ChunkState::Free),
+    ]);
+  }
+
+  #[test]
+  #[should_panic(expected = "shadow: chunks diverged at element 2")]
+  fn shadow_divergence() {
+    let mut shadow = Shadow::new(4, 0);
+    shadow.mark(0, 2, ChunkState::Allocated);
+    shadow.verify(&[
+      chunk(0, 3, ChunkState::Allocated),
+      chunk(3, 1, ChunkState::Free),
+    ]);
+  }
+
+  #[test]
+  #[should_panic(expected = "shadow: element 1 is already Free")]
+  fn shadow_double_release() {
+    let mut shadow = Shadow::new(4, 0);
+    shadow.mark(0, 2, ChunkState::Allocated);
+    shadow.mark(0, 2, ChunkState::Free);
+    shadow.mark(1, 1, ChunkState::Free);
+  }
+}
@@ -0,0 +1,214 @@
+use flavor::Flavor;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use {BorrowError, SliceBox, ThreadSafe};
+
+/// The borrow state of an exclusively borrowed allocation.
+const WRITING: usize = usize::MAX;
+
+struct Shared<T: 'static, F: Flavor> {
+  borrows: AtomicUsize,
+  data: UnsafeCell<SliceBox<T, F>>,
+}
+
+/// A shareable handle to an allocation in a `SlicePool`.
+///
+/// Handles are cheap to clone and access the allocation through `read` and
+/// `write` guards. Borrows are tracked at runtime like a `RefCell`, but
+/// across threads for the thread-safe flavor; conflicting borrows fail
+/// instead of blocking.
+pub struct SharedBox<T: 'static, F: Flavor = ThreadSafe>(F::Ptr<Shared<T, F>>);
+
+unsafe impl<T: Send + Sync> Send for SharedBox<T, ThreadSafe> {}
+unsafe impl<T: Send + Sync> Sync for SharedBox<T, ThreadSafe> {}
+
+impl<T: 'static, F: Flavor> SharedBox<T, F> {
+  /// Constructs a new shareable handle from an allocation.
+  pub fn new(data: SliceBox<T, F>) -> Self {
+    SharedBox(F::share(Shared {
+      borrows: AtomicUsize::new(0),
+      data: UnsafeCell::new(data),
+    }))
+  }
+
+  /// Immutably borrows the allocation.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the allocation is currently mutably borrowed.
+  pub fn read(&self) -> SharedRef<'_, T, F> {
+    self.try_read().expect("already mutably borrowed")
+  }
+
+  /// Mutably borrows the allocation.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the allocation is currently borrowed.
+  pub fn write(&self) -> SharedRefMut<'_, T, F> {
+    self.try_write().expect("already borrowed")
+  }
+
+  /// Immutably borrows the allocation, failing if it is mutably borrowed.
+  pub fn try_read(&self) -> Result<SharedRef<'_, T, F>, BorrowError> {
+    let mut borrows = self.0.borrows.load(Ordering::Relaxed);
+    loop {
+      if borrows >= WRITING - 1 {
+        return Err(BorrowError);
+      }
+
+      match self.0.borrows.compare_exchange_weak(
+        borrows,
+        borrows + 1,
+        Ordering::Acquire,
+        Ordering::Relaxed,
+      ) {
+        Ok(_) => return Ok(SharedRef(&self.0)),
+        Err(current) => borrows = current,
+      }
+    }
+  }
+
+  /// Mutably borrows the allocation, failing if it is borrowed.
+  pub fn try_write(&self) -> Result<SharedRefMut<'_, T, F>, BorrowError> {
+    self
+      .0
+      .borrows
+      .compare_exchange(0, WRITING, Ordering::Acquire, Ordering::Relaxed)
+      .map(|_| SharedRefMut(&self.0))
+      .map_err(|_| BorrowError)
+  }
+
+  /// Returns the allocation if this is the only handle to it.
+  pub fn try_unwrap(self) -> Result<SliceBox<T, F>, Self> {
+    F::try_unwrap(self.0)
+      .map(|shared| shared.data.into_inner())
+      .map_err(SharedBox)
+  }
+}
+
+impl<T: 'static, F: Flavor> Clone for SharedBox<T, F> {
+  fn clone(&self) -> Self {
+    SharedBox(self.0.clone())
+  }
+}
+
+impl<T: 'static, F: Flavor> From<SliceBox<T, F>> for SharedBox<T, F> {
+  fn from(data: SliceBox<T, F>) -> Self {
+    SharedBox::new(data)
+  }
+}
+
+impl<T: fmt::Debug + 'static, F: Flavor> fmt::Debug for SharedBox<T, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.try_read() {
+      Ok(data) => write!(f, "{:?}", data.deref()),
+      Err(_) => write!(f, "<borrowed>"),
+    }
+  }
+}
+
+/// An immutable borrow of a `SharedBox`.
+pub struct SharedRef<'a, T: 'static, F: Flavor>(&'a Shared<T, F>);
+
+impl<'a, T: 'static, F: Flavor> Deref for SharedRef<'a, T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    unsafe { &*self.0.data.get() }
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> Drop for SharedRef<'a, T, F> {
+  fn drop(&mut self) {
+    self.0.borrows.fetch_sub(1, Ordering::Release);
+  }
+}
+
+/// A mutable borrow of a `SharedBox`.
+pub struct SharedRefMut<'a, T: 'static, F: Flavor>(&'a Shared<T, F>);
+
+impl<'a, T: 'static, F: Flavor> Deref for SharedRefMut<'a, T, F> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    unsafe { &*self.0.data.get() }
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> DerefMut for SharedRefMut<'a, T, F> {
+  fn deref_mut(&mut self) -> &mut [T] {
+    unsafe { &mut *self.0.data.get() }
+  }
+}
+
+impl<'a, T: 'static, F: Flavor> Drop for SharedRefMut<'a, T, F> {
+  fn drop(&mut self) {
+    self.0.borrows.store(0, Ordering::Release);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::thread;
+  use unsync;
+
+  #[test]
+  fn shared_borrows() {
+    let pool = ::SlicePool::<_, ThreadSafe>::new(vec![10, 20, 30, 40]);
+    let shared = SharedBox::new(pool.alloc(2).unwrap());
+    let other = shared.clone();
+
+    {
+      let read1 = shared.read();
+      let read2 = other.read();
+      assert_eq!(*read1, *read2);
+      assert!(other.try_write().is_err());
+    }
+
+    other.write()[0] = 15;
+    assert_eq!(*shared.read(), [15, 20]);
+
+    let handle = other.clone();
+    thread::spawn(move || handle.write()[1] = 25)
+      .join()
+      .unwrap();
+
+    let write = shared.write();
+    assert!(other.try_read().is_err());
+    assert_eq!(*write, [15, 25]);
+    drop(write);
+
+    drop(other);
+    let data = shared.try_unwrap().unwrap();
+    assert_eq!(*data, [15, 25]);
+  }
+
+  #[test]
+  fn shared_borrows_local() {
+    let pool = unsync::SlicePool::new(vec![10, 20, 30, 40]);
+    let shared = SharedBox::new(pool.alloc(2).unwrap());
+    let other = shared.clone();
+
+    {
+      let read1 = shared.read();
+      let read2 = other.read();
+      assert_eq!(*read1, *read2);
+      assert!(other.try_write().is_err());
+    }
+
+    other.write()[0] = 15;
+    assert_eq!(*shared.read(), [15, 20]);
+
+    let write = shared.write();
+    assert!(other.try_read().is_err());
+    drop(write);
+
+    drop(other);
+    let data = shared.try_unwrap().unwrap();
+    assert_eq!(*data, [15, 20]);
+  }
+}
@@ -0,0 +1,292 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use {Chunk, ChunkInfo, ChunkState, ChunkToken};
+
+/// The number of elements tracked by each word of the bitmap.
+const BITS: usize = 64;
+
+/// A region near the start of a slice serving small allocations.
+///
+/// Elements are tracked by a bitmap instead of a chunk chain, so tiny,
+/// short-lived allocations do not fragment the chain used for larger ones.
+pub struct SmallRegion {
+  threshold: usize,
+  start: usize,
+  len: usize,
+  bitmap: Vec<u64>,
+  owners: Vec<Option<Owner>>,
+  used: usize,
+  allocations: usize,
+}
+
+/// The allocation starting at an element of the region.
+#[derive(Copy, Clone)]
+struct Owner {
+  size: usize,
+  generation: u64,
+}
+
+/// Statistics of the split between the small-object region and the chain.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct RegionStats {
+  /// The number of live allocations in the small-object region.
+  pub small_allocations: usize,
+  /// The number of elements allocated in the small-object region.
+  pub small_used: usize,
+  /// The number of live allocations in the chunk chain.
+  pub chain_allocations: usize,
+  /// The number of elements allocated in the chunk chain.
+  pub chain_used: usize,
+}
+
+impl SmallRegion {
+  pub fn new(start: usize, len: usize, threshold: usize) -> Self {
+    SmallRegion {
+      threshold,
+      start,
+      len,
+      bitmap: vec![0; len.div_ceil(BITS)],
+      owners: vec![None; len],
+      used: 0,
+      allocations: 0,
+    }
+  }
+
+  /// Returns the number of elements in the region.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns the size below which allocations are served by the region.
+  pub fn threshold(&self) -> usize {
+    self.threshold
+  }
+
+  /// Returns the number of elements allocated in the region.
+  pub fn used(&self) -> usize {
+    self.used
+  }
+
+  /// Returns the number of live allocations in the region.
+  pub fn allocations(&self) -> usize {
+    self.allocations
+  }
+
+  /// Returns whether an offset lies within the region.
+  pub fn contains(&self, offset: usize) -> bool {
+    offset >= self.start && offset < self.start + self.len
+  }
+
+  /// Allocates a run of elements if the size is below the threshold.
+  pub fn allocate(&mut self, size: usize, generation: u64) -> Option<Chunk> {
+    if size == 0 || size > self.threshold {
+      return None;
+    }
+
+    let mut run = 0;
+    let end = (0..self.len).find(|&index| {
+      run = if self.is_set(index) { 0 } else { run + 1 };
+      run == size
+    })?;
+    let index = end + 1 - size;
+
+    self.fill(index, size, true);
+    self.owners[index] = Some(Owner { size, generation });
+    self.used += size;
+    self.allocations += 1;
+
+    Some(Chunk {
+      free: false,
+      generation,
+      ..Chunk::with_offset(size, self.start + index)
+    })
+  }
+
+  /// Releases an allocation, returning its size.
+  pub fn release(&mut self, token: ChunkToken) -> Option<usize> {
+    let owner = self.owner(token)?;
+    let index = token.offset - self.start;
+    self.owners[index] = None;
+    self.fill(index, owner.size, false);
+    self.used -= owner.size;
+    self.allocations -= 1;
+    Some(owner.size)
+  }
+
+  /// Releases the first `size` elements of an allocation.
+  pub fn release_head(&mut self, token: ChunkToken, size: usize) -> bool {
+    let owner = match self.owner(token) {
+      Some(owner) => owner,
+      None => return false,
+    };
+
+    let index = token.offset - self.start;
+    self.owners[index] = None;
+    self.owners[index + size] = Some(Owner {
+      size: owner.size - size,
+      ..owner
+    });
+    self.fill(index, size, false);
+    self.used -= size;
+    true
+  }
+
+  /// Releases the last `size` elements of an allocation, returning the
+  /// offset of the released elements.
+  pub fn release_tail(&mut self, token: ChunkToken, size: usize) -> Option<usize> {
+    let owner = self.owner(token)?;
+    let index = token.offset - self.start;
+    let len = owner.size - size;
+    self.owners[index] = Some(Owner { size: len, ..owner });
+    self.fill(index + len, size, false);
+    self.used -= size;
+    Some(token.offset + len)
+  }
+
+  /// Extends an allocation into the free elements following it, returning
+  /// the offset of the added elements.
+  pub fn grow(&mut self, token: ChunkToken, additional: usize) -> Option<usize> {
+    let owner = self.owner(token)?;
+    let index = token.offset - self.start;
+    let end = index + owner.size;
+    if end + additional > self.len || (end..end + additional).any(|index| self.is_set(index)) {
+      return None;
+    }
+
+    self.fill(end, additional, true);
+    self.owners[index] = Some(Owner {
+      size: owner.size + additional,
+      ..owner
+    });
+    self.used += additional;
+    Some(self.start + end)
+  }
+
+  /// Splits an allocation in two, stamping the second half with a new
+  /// generation.
+  pub fn split(&mut self, token: ChunkToken, mid: usize, generation: u64) -> bool {
+    let owner = match self.owner(token) {
+      Some(owner) => owner,
+      None => return false,
+    };
+
+    let index = token.offset - self.start;
+    self.owners[index] = Some(Owner { size: mid, ..owner });
+    self.owners[index + mid] = Some(Owner {
+      size: owner.size - mid,
+      generation,
+    });
+    self.allocations += 1;
+    true
+  }
+
+  /// Fuses an allocation with the one immediately following it.
+  pub fn merge(&mut self, first: ChunkToken, second: ChunkToken) -> bool {
+    match (self.owner(first), self.owner(second)) {
+      (Some(head), Some(tail)) if first.offset + head.size == second.offset => {
+        self.owners[first.offset - self.start] = Some(Owner {
+          size: head.size + tail.size,
+          ..head
+        });
+        self.owners[second.offset - self.start] = None;
+        self.allocations -= 1;
+        true
+      }
+      _ => false,
+    }
+  }
+
+  /// Returns the token of the allocation starting at an offset, if it has
+  /// a given size.
+  pub fn token_at(&self, offset: usize, size: usize) -> Option<ChunkToken> {
+    let owner = self.owners[offset - self.start]?;
+    Some(ChunkToken {
+      offset,
+      generation: owner.generation,
+    })
+    .filter(|_| owner.size == size)
+  }
+
+  /// Returns the sizes of the live allocations in the region.
+  pub fn allocation_sizes(&self) -> impl Iterator<Item = usize> + '_ {
+    self.owners.iter().flatten().map(|owner| owner.size)
+  }
+
+  /// Returns the allocations and free runs of the region, in order.
+  pub fn chunks(&self) -> Vec<ChunkInfo> {
+    let mut chunks = Vec::new();
+    let mut index = 0;
+    while index < self.len {
+      let (len, state) = match self.owners[index] {
+        Some(owner) => (owner.size, ChunkState::Allocated),
+        None => {
+          let run = (index..self.len).take_while(|&index| !self.is_set(index));
+          (run.count(), ChunkState::Free)
+        }
+      };
+
+      chunks.push(ChunkInfo {
+        offset: self.start + index,
+        len,
+        state,
+      });
+      index += len;
+    }
+    chunks
+  }
+
+  /// Returns whether a token refers to a live allocation of a given size.
+  pub fn is_allocated(&self, token: ChunkToken, size: usize) -> bool {
+    self.owner(token).is_some_and(|owner| owner.size == size)
+  }
+
+  fn owner(&self, token: ChunkToken) -> Option<Owner> {
+    let index = token.offset.checked_sub(self.start)?;
+    self
+      .owners
+      .get(index)
+      .cloned()
+      .flatten()
+      .filter(|owner| owner.generation == token.generation)
+  }
+
+  fn is_set(&self, index: usize) -> bool {
+    self.bitmap[index / BITS] & (1 << (index % BITS)) != 0
+  }
+
+  fn fill(&mut self, offset: usize, size: usize, set: bool) {
+    for index in offset..offset + size {
+      let word = &mut self.bitmap[index / BITS];
+      if set {
+        *word |= 1 << (index % BITS);
+      } else {
+        *word &= !(1 << (index % BITS));
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn region_bitmap() {
+    let mut region = SmallRegion::new(0, 70, 4);
+    assert!(region.allocate(5, 1).is_none());
+
+    let first = region.allocate(4, 1).unwrap();
+    let second = region.allocate(2, 2).unwrap();
+    assert_eq!((first.offset, second.offset), (0, 4));
+    assert!(region.release(first.token()).is_some());
+    assert!(region.release(first.token()).is_none());
+
+    // Freed runs are reused
+    let third = region.allocate(3, 3).unwrap();
+    assert_eq!(third.offset, 0);
+    assert_eq!(region.release_tail(third.token(), 1), Some(2));
+    assert!(region.is_allocated(third.token(), 2));
+    assert_eq!((region.used(), region.allocations()), (4, 2));
+  }
+}
@@ -0,0 +1,58 @@
+use std::cell::UnsafeCell;
+
+/// A storage which can grow in place.
+///
+/// A pool constructed with `SlicePool::new_growable` can add elements to
+/// its end, without moving any of its existing allocations.
+pub trait GrowableStorage<T>: AsRef<[T]> + AsMut<[T]> {
+  /// Grows the storage by `additional` elements, returning whether it
+  /// succeeded.
+  ///
+  /// The existing elements must not move. Storages which cannot expand in
+  /// place (e.g. a vector without spare capacity) must fail instead.
+  fn try_grow(&mut self, additional: usize) -> bool;
+}
+
+/// Grows vectors within their spare capacity, with default values.
+impl<T: Default> GrowableStorage<T> for Vec<T> {
+  fn try_grow(&mut self, additional: usize) -> bool {
+    if self.capacity() - self.len() < additional {
+      return false;
+    }
+
+    self.resize_with(self.len() + additional, T::default);
+    true
+  }
+}
+
+/// A growable storage shared by a pool and its allocations.
+///
+/// The storage is only grown through a mutable reference to its pool, so
+/// it is never accessed concurrently.
+pub struct Growable<S>(UnsafeCell<S>);
+
+unsafe impl<S: Sync> Sync for Growable<S> {}
+
+impl<S> Growable<S> {
+  pub fn new(storage: S) -> Self {
+    Growable(UnsafeCell::new(storage))
+  }
+}
+
+impl<T, S: AsRef<[T]>> AsRef<[T]> for Growable<S> {
+  fn as_ref(&self) -> &[T] {
+    unsafe { (*self.0.get()).as_ref() }
+  }
+}
+
+impl<T, S: AsMut<[T]>> AsMut<[T]> for Growable<S> {
+  fn as_mut(&mut self) -> &mut [T] {
+    self.0.get_mut().as_mut()
+  }
+}
+
+/// Grows the storage of a pool, which must be a `Growable<S>`.
+pub unsafe fn grow<T, S: GrowableStorage<T>>(storage: *const (), additional: usize) -> bool {
+  let storage = &*(storage as *const Growable<S>);
+  (*storage.0.get()).try_grow(additional)
+}
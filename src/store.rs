@@ -0,0 +1,412 @@
+use index::FreeIndex;
+use std::collections::{btree_map, BTreeMap};
+use std::{iter, mem, slice};
+use Chunk;
+
+/// The chunk count above which chunks are stored in a tree.
+const TREE_THRESHOLD: usize = 512;
+
+/// The chunk count below which chunks are stored in a vector again.
+const FLAT_THRESHOLD: usize = 128;
+
+/// The chunks of a chain, ordered and addressed by their offsets.
+///
+/// The chunks are kept in a `Backend`, chosen by their count. Tiny chains
+/// store their chunks in a sorted vector, which is compact and fast to
+/// scan. Once a chain is fragmented into many chunks, inserting and
+/// removing in the middle of the vector becomes costly, so the chunks move
+/// to a tree instead. The thresholds differ, so the backend does not flip
+/// back and forth. There is no linked-list backend, since every operation
+/// of a chain addresses its chunks by offset, which a list cannot do faster
+/// than the tree.
+///
+/// The free chunks may additionally be indexed by size, which the store
+/// keeps up to date as chunks are inserted, removed and modified.
+pub struct ChunkStore {
+  chunks: Chunks,
+  index: Option<FreeIndex>,
+}
+
+/// A container of chunks, ordered and addressed by their offsets.
+trait Backend {
+  fn len(&self) -> usize;
+
+  /// Returns the chunks starting at or after an offset, and the chunks
+  /// before it, in order.
+  fn split(&self, offset: usize) -> (Iter<'_>, Iter<'_>);
+
+  fn get(&self, offset: usize) -> Option<&Chunk>;
+
+  fn get_mut(&mut self, offset: usize) -> Option<&mut Chunk>;
+
+  fn last(&self) -> Option<&Chunk>;
+
+  /// Returns the chunk preceding an offset.
+  fn preceding(&self, offset: usize) -> Option<&Chunk>;
+
+  /// Returns the chunk following the one starting at an offset.
+  fn following(&self, offset: usize) -> Option<&Chunk>;
+
+  /// Inserts a chunk, returning false if one starts at the same offset.
+  fn insert(&mut self, chunk: Chunk) -> bool;
+
+  fn remove(&mut self, offset: usize) -> Option<Chunk>;
+}
+
+/// The backend of a store.
+enum Chunks {
+  Flat(Vec<Chunk>),
+  Tree(BTreeMap<usize, Chunk>),
+}
+
+/// An iterator over the chunks of a store, in order.
+#[derive(Clone)]
+pub(crate) enum Iter<'a> {
+  Flat(slice::Iter<'a, Chunk>),
+  Tree(btree_map::Range<'a, usize, Chunk>),
+}
+
+impl ChunkStore {
+  pub fn new<I: IntoIterator<Item = Chunk>>(chunks: I) -> Self {
+    let mut store = ChunkStore {
+      chunks: Chunks::Flat(Vec::new()),
+      index: None,
+    };
+    store.reset(chunks);
+    store
+  }
+
+  /// Replaces the chunks, keeping the index if there is one.
+  pub fn reset<I: IntoIterator<Item = Chunk>>(&mut self, chunks: I) {
+    self.chunks = Chunks::Flat(chunks.into_iter().collect());
+    self.rebalance();
+    let index = self.index.take();
+    self.set_index(index);
+  }
+
+  /// Indexes the free chunks using an index, or removes the index.
+  pub fn set_index(&mut self, index: Option<FreeIndex>) {
+    self.index = index.map(|mut index| {
+      index.clear();
+      for chunk in self.iter().filter(|chunk| chunk.free) {
+        index.insert(chunk.offset, chunk.size);
+      }
+      index
+    });
+  }
+
+  /// Returns whether the free chunks are indexed.
+  pub fn is_indexed(&self) -> bool {
+    self.index.is_some()
+  }
+
+  /// Returns the offset of a free chunk of at least `size` elements, using
+  /// the index.
+  pub fn find_free(&self, size: usize) -> Option<usize> {
+    self.index.as_ref().expect("unindexed store").find(size)
+  }
+
+  pub fn iter(&self) -> Iter<'_> {
+    self.backend().split(0).0
+  }
+
+  /// Returns an iterator over the chunks starting at or after an offset,
+  /// followed by the chunks before it.
+  pub fn iter_from(&self, offset: usize) -> iter::Chain<Iter<'_>, Iter<'_>> {
+    let (tail, head) = self.backend().split(offset);
+    tail.chain(head)
+  }
+
+  /// Returns the chunk starting at an offset.
+  pub fn get(&self, offset: usize) -> Option<&Chunk> {
+    self.backend().get(offset)
+  }
+
+  /// Returns the chunk with the highest offset.
+  pub fn last(&self) -> Option<&Chunk> {
+    self.backend().last()
+  }
+
+  /// Returns the chunk preceding the one starting at an offset.
+  pub fn preceding(&self, offset: usize) -> Option<&Chunk> {
+    self.backend().preceding(offset)
+  }
+
+  /// Returns the chunk following the one starting at an offset.
+  pub fn following(&self, offset: usize) -> Option<&Chunk> {
+    self.backend().following(offset)
+  }
+
+  /// Inserts a chunk, which must not overlap any other.
+  pub fn insert(&mut self, chunk: Chunk) {
+    assert!(self.backend_mut().insert(chunk), "overlapping chunk");
+    if let Some(index) = self.index.as_mut().filter(|_| chunk.free) {
+      index.insert(chunk.offset, chunk.size);
+    }
+    self.rebalance();
+  }
+
+  /// Removes the chunk starting at an offset.
+  pub fn remove(&mut self, offset: usize) -> Option<Chunk> {
+    let chunk = self.backend_mut().remove(offset);
+    if let Some((index, chunk)) = self.index.as_mut().zip(chunk.filter(|chunk| chunk.free)) {
+      index.remove(offset, chunk.size);
+    }
+    self.rebalance();
+    chunk
+  }
+
+  /// Modifies the chunk starting at an offset, reindexing it if needed.
+  ///
+  /// The offset of the chunk must not be changed, use `rekey` instead.
+  pub fn update<R, F: FnOnce(&mut Chunk) -> R>(&mut self, offset: usize, f: F) -> R {
+    // The backend is borrowed alone, so the index can be updated meanwhile
+    let chunk = match &mut self.chunks {
+      Chunks::Flat(chunks) => Backend::get_mut(chunks, offset),
+      Chunks::Tree(chunks) => Backend::get_mut(chunks, offset),
+    };
+    let chunk = chunk.expect("updating a missing chunk");
+
+    let (free, size) = (chunk.free, chunk.size);
+    let result = f(chunk);
+    debug_assert_eq!(chunk.offset, offset, "rekeying an updated chunk");
+
+    if let Some(index) = self.index.as_mut() {
+      if (free, size) != (chunk.free, chunk.size) {
+        if free {
+          index.remove(offset, size);
+        }
+        if chunk.free {
+          index.insert(offset, chunk.size);
+        }
+      }
+    }
+    result
+  }
+
+  /// Modifies the chunk starting at an offset, including its offset.
+  pub fn rekey<F: FnOnce(&mut Chunk)>(&mut self, offset: usize, f: F) {
+    let mut chunk = self.remove(offset).expect("rekeying a missing chunk");
+    f(&mut chunk);
+    self.insert(chunk);
+  }
+
+  /// Returns whether the chunks are stored in a tree.
+  #[cfg(test)]
+  pub fn is_tree(&self) -> bool {
+    matches!(self.chunks, Chunks::Tree(_))
+  }
+
+  fn backend(&self) -> &dyn Backend {
+    match &self.chunks {
+      Chunks::Flat(chunks) => chunks,
+      Chunks::Tree(chunks) => chunks,
+    }
+  }
+
+  fn backend_mut(&mut self) -> &mut dyn Backend {
+    match &mut self.chunks {
+      Chunks::Flat(chunks) => chunks,
+      Chunks::Tree(chunks) => chunks,
+    }
+  }
+
+  /// Moves the chunks to the backend suited for their count.
+  fn rebalance(&mut self) {
+    let len = self.backend().len();
+    match &mut self.chunks {
+      Chunks::Flat(chunks) if len > TREE_THRESHOLD => {
+        let chunks = chunks.drain(..).map(|chunk| (chunk.offset, chunk));
+        self.chunks = Chunks::Tree(chunks.collect());
+      }
+      Chunks::Tree(chunks) if len < FLAT_THRESHOLD => {
+        let chunks = mem::take(chunks).into_values();
+        self.chunks = Chunks::Flat(chunks.collect());
+      }
+      _ => (),
+    }
+  }
+}
+
+/// A sorted vector of chunks.
+impl Backend for Vec<Chunk> {
+  fn len(&self) -> usize {
+    <[Chunk]>::len(self)
+  }
+
+  fn split(&self, offset: usize) -> (Iter<'_>, Iter<'_>) {
+    let (head, tail) = self.split_at(position(self, offset).unwrap_or_else(|index| index));
+    (Iter::Flat(tail.iter()), Iter::Flat(head.iter()))
+  }
+
+  fn get(&self, offset: usize) -> Option<&Chunk> {
+    position(self, offset).ok().map(|index| &self[index])
+  }
+
+  fn get_mut(&mut self, offset: usize) -> Option<&mut Chunk> {
+    position(self, offset)
+      .ok()
+      .map(move |index| &mut self[index])
+  }
+
+  fn last(&self) -> Option<&Chunk> {
+    <[Chunk]>::last(self)
+  }
+
+  fn preceding(&self, offset: usize) -> Option<&Chunk> {
+    let index = position(self, offset).unwrap_or_else(|index| index);
+    index.checked_sub(1).map(|index| &self[index])
+  }
+
+  fn following(&self, offset: usize) -> Option<&Chunk> {
+    let index = position(self, offset).map_or_else(|index| index, |index| index + 1);
+    <[Chunk]>::get(self, index)
+  }
+
+  fn insert(&mut self, chunk: Chunk) -> bool {
+    match position(self, chunk.offset) {
+      Ok(_) => false,
+      Err(index) => {
+        Vec::insert(self, index, chunk);
+        true
+      }
+    }
+  }
+
+  fn remove(&mut self, offset: usize) -> Option<Chunk> {
+    position(self, offset)
+      .ok()
+      .map(|index| Vec::remove(self, index))
+  }
+}
+
+/// A tree of chunks keyed by their offsets.
+impl Backend for BTreeMap<usize, Chunk> {
+  fn len(&self) -> usize {
+    BTreeMap::len(self)
+  }
+
+  fn split(&self, offset: usize) -> (Iter<'_>, Iter<'_>) {
+    (
+      Iter::Tree(self.range(offset..)),
+      Iter::Tree(self.range(..offset)),
+    )
+  }
+
+  fn get(&self, offset: usize) -> Option<&Chunk> {
+    BTreeMap::get(self, &offset)
+  }
+
+  fn get_mut(&mut self, offset: usize) -> Option<&mut Chunk> {
+    BTreeMap::get_mut(self, &offset)
+  }
+
+  fn last(&self) -> Option<&Chunk> {
+    self.values().next_back()
+  }
+
+  fn preceding(&self, offset: usize) -> Option<&Chunk> {
+    self.range(..offset).next_back().map(|(_, chunk)| chunk)
+  }
+
+  fn following(&self, offset: usize) -> Option<&Chunk> {
+    self.range(offset + 1..).next().map(|(_, chunk)| chunk)
+  }
+
+  fn insert(&mut self, chunk: Chunk) -> bool {
+    match self.entry(chunk.offset) {
+      btree_map::Entry::Occupied(_) => false,
+      btree_map::Entry::Vacant(entry) => {
+        entry.insert(chunk);
+        true
+      }
+    }
+  }
+
+  fn remove(&mut self, offset: usize) -> Option<Chunk> {
+    BTreeMap::remove(self, &offset)
+  }
+}
+
+/// Returns the index of the chunk starting at an offset, or the index at
+/// which it would be inserted.
+fn position(chunks: &[Chunk], offset: usize) -> Result<usize, usize> {
+  chunks.binary_search_by_key(&offset, |chunk| chunk.offset)
+}
+
+impl<'a> Iterator for Iter<'a> {
+  type Item = &'a Chunk;
+
+  fn next(&mut self) -> Option<&'a Chunk> {
+    match self {
+      Iter::Flat(iter) => iter.next(),
+      Iter::Tree(iter) => iter.next().map(|(_, chunk)| chunk),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tlsf::TlsfIndex;
+
+  #[test]
+  fn store_backends() {
+    let mut store = ChunkStore::new(Some(Chunk::new(1)));
+    for offset in 1..=TREE_THRESHOLD {
+      store.insert(Chunk::with_offset(1, offset));
+    }
+    assert!(store.is_tree());
+
+    // Both backends agree on the neighbors of a chunk
+    let neighbors = |store: &ChunkStore, offset| {
+      let preceding = store.preceding(offset).map(|chunk| chunk.offset);
+      let following = store.following(offset).map(|chunk| chunk.offset);
+      (preceding, following)
+    };
+    assert_eq!(neighbors(&store, 0), (None, Some(1)));
+    assert_eq!(neighbors(&store, 7), (Some(6), Some(8)));
+
+    store.rekey(8, |chunk| chunk.offset = 1000);
+    // The chunk at offset 8 has moved, so 399 chunks are removed
+    for offset in 0..400 {
+      store.remove(offset);
+    }
+    assert!(!store.is_tree());
+    assert_eq!(neighbors(&store, 1000), (Some(TREE_THRESHOLD), None));
+    assert_eq!(store.get(1000).map(|chunk| chunk.size), Some(1));
+    assert_eq!(store.iter().count(), TREE_THRESHOLD + 1 - 399);
+
+    // Iterating from an offset wraps around to the start
+    let offsets = |store: &ChunkStore, offset| {
+      let mut chunks = store.iter_from(offset).map(|chunk| chunk.offset);
+      (chunks.next(), chunks.last())
+    };
+    assert_eq!(offsets(&store, 450), (Some(450), Some(449)));
+    assert_eq!(offsets(&store, 2000), (Some(400), Some(1000)));
+    for offset in 400..TREE_THRESHOLD {
+      store.remove(offset);
+    }
+    assert!(!store.is_tree());
+    assert_eq!(offsets(&store, 600), (Some(1000), Some(TREE_THRESHOLD)));
+  }
+
+  #[test]
+  fn store_index() {
+    let mut store = ChunkStore::new(Some(Chunk::new(10)));
+    store.update(0, |chunk| chunk.size = 4);
+    store.insert(Chunk::with_offset(6, 4));
+    store.set_index(Some(FreeIndex::Tlsf(TlsfIndex::new())));
+    assert_eq!(store.find_free(5), Some(4));
+
+    // The index follows the chunks as they are modified
+    store.update(4, |chunk| chunk.free = false);
+    assert_eq!((store.find_free(4), store.find_free(5)), (Some(0), None));
+    store.rekey(0, |chunk| chunk.offset = 20);
+    store.remove(4);
+    assert_eq!(store.find_free(4), Some(20));
+
+    store.reset(Some(Chunk::new(8)));
+    assert_eq!((store.find_free(8), store.find_free(9)), (Some(0), None));
+  }
+}
@@ -0,0 +1,408 @@
+use index::{FreeIndex, SegregatedIndex, SizeIndex};
+use std::cmp::Reverse;
+use tlsf::TlsfIndex;
+use Chunk;
+
+/// The number of allocation attempts between adaptive re-evaluations.
+const ADAPTIVE_WINDOW: usize = 32;
+
+/// The free chunk count above which adaptive placement uses best-fit.
+const ADAPTIVE_FRAGMENTED: usize = 16;
+
+/// The free chunk count below which adaptive placement uses first-fit.
+const ADAPTIVE_COMPACT: usize = 4;
+
+/// A placement strategy used when searching for a free chunk.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Strategy {
+  /// Use the first free chunk large enough for the allocation.
+  FirstFit,
+  /// Use the smallest free chunk large enough for the allocation.
+  ///
+  /// The free chunks are indexed by size, so the chunk is found in
+  /// logarithmic time, except while adaptive placement uses best-fit.
+  #[default]
+  BestFit,
+  /// Use the largest free chunk, leaving large remainders which are
+  /// likely to fit subsequent allocations.
+  WorstFit,
+  /// Use the first free chunk large enough for the allocation, starting
+  /// from where the previous allocation ended and wrapping around.
+  ///
+  /// This spreads allocations across the pool, instead of repeatedly
+  /// reusing the chunks at its start, which suits ring-like streaming
+  /// workloads. The chunks are scanned from the cursor, so an allocation
+  /// usually only visits the chunks following the previous one.
+  NextFit,
+  /// Use a two-level segregated fit (TLSF) index of the free chunks, which
+  /// usually finds a chunk without searching, regardless of fragmentation.
+  ///
+  /// Allocations are placed in a chunk from the smallest size range which
+  /// is guaranteed to fit, instead of the smallest chunk, so the chunk may
+  /// be up to 1/16th larger than the best fit. Only if there is none, the
+  /// chunks in the size range of the allocation itself are scanned, e.g.
+  /// for one of exactly its size. The index is updated as chunks are split
+  /// and coalesced, and bypassed when placement prefers thread affinity or
+  /// is randomized. Placement is therefore not constant-time: splitting and
+  /// coalescing still update the chunks of the pool, which takes linear
+  /// time while they are few, and logarithmic time once they are many.
+  Tlsf,
+  /// Allocate from the free chunk at the end of the pool, like an arena
+  /// bumping an offset.
+  ///
+  /// Placement only considers the last chunk, so it takes constant time
+  /// regardless of fragmentation. Released memory is reused once it
+  /// coalesces with a free chunk at the end of the pool, e.g. when the
+  /// latest allocation is released, and holes elsewhere are left unused
+  /// until then. Once every allocation has been released, the pool is a
+  /// single free chunk again, which suits per-request arenas.
+  Bump,
+  /// Switch between first-fit and best-fit depending on fragmentation.
+  ///
+  /// First-fit is used while the pool has few free chunks, since it stops
+  /// at the first candidate. Once the free chunks multiply, or allocations
+  /// start failing, best-fit is used until the pool has recovered.
+  Adaptive,
+}
+
+/// The state of a chain's placement strategy.
+pub struct Placer {
+  strategy: Strategy,
+  active: Strategy,
+  attempts: usize,
+  failures: usize,
+  shuffle: Option<u64>,
+  /// The offset following the previous next-fit allocation.
+  cursor: usize,
+  /// The bounds of the size classes of segregated free lists.
+  classes: Option<Vec<usize>>,
+}
+
+impl Placer {
+  pub fn new(strategy: Strategy) -> Self {
+    Placer {
+      strategy,
+      active: Self::initial(strategy),
+      attempts: 0,
+      failures: 0,
+      shuffle: None,
+      cursor: 0,
+      classes: None,
+    }
+  }
+
+  /// Changes the strategy, resetting any adaptive state.
+  pub fn set_strategy(&mut self, strategy: Strategy) {
+    *self = Placer {
+      shuffle: self.shuffle,
+      classes: self.classes.take(),
+      ..Placer::new(strategy)
+    };
+  }
+
+  /// Sets the bounds of the size classes of segregated free lists, or
+  /// removes them.
+  pub fn set_size_classes(&mut self, bounds: Option<Vec<usize>>) {
+    self.classes = bounds;
+  }
+
+  /// Returns an empty index of the free chunks for placement, if it uses
+  /// one.
+  ///
+  /// Segregated free lists take precedence over the strategy.
+  pub fn index(&self) -> Option<FreeIndex> {
+    match &self.classes {
+      Some(bounds) => Some(FreeIndex::Segregated(SegregatedIndex::new(bounds))),
+      None => match self.strategy {
+        Strategy::BestFit => Some(FreeIndex::Sizes(SizeIndex::default())),
+        Strategy::Tlsf => Some(FreeIndex::Tlsf(TlsfIndex::new())),
+        _ => None,
+      },
+    }
+  }
+
+  /// Sets the seed used for randomizing placement, or disables it.
+  pub fn set_shuffle(&mut self, seed: Option<u64>) {
+    self.shuffle = seed;
+  }
+
+  /// Returns whether placement is randomized.
+  pub fn is_shuffled(&self) -> bool {
+    self.shuffle.is_some()
+  }
+
+  /// Returns whether an allocation should be carved from the end of its
+  /// chunk, which is only ever the case when placement is randomized.
+  pub fn carve_tail(&mut self) -> bool {
+    self.random().is_some_and(|value| value & 1 == 1)
+  }
+
+  /// Returns the offset the chunks are scanned from, wrapping around to
+  /// the start, which is the cursor while next-fit is in use.
+  pub fn scan_start(&self) -> usize {
+    match self.active {
+      Strategy::NextFit if !self.is_shuffled() => self.cursor,
+      _ => 0,
+    }
+  }
+
+  /// Returns the configured strategy.
+  pub fn strategy(&self) -> Strategy {
+    self.strategy
+  }
+
+  /// Returns the strategy currently used for placement.
+  pub fn active(&self) -> Strategy {
+    self.active
+  }
+
+  /// Finds a free chunk for an allocation and records the outcome.
+  ///
+  /// Returns the offset of the chunk.
+  pub fn find<'a, I>(&mut self, chunks: I, size: usize) -> Option<usize>
+  where
+    I: Iterator<Item = &'a Chunk> + Clone,
+  {
+    self
+      .find_preferring(chunks, size, |_| false)
+      .map(|(index, _)| index)
+  }
+
+  /// Finds a free chunk, preferring chunks accepted by a predicate.
+  ///
+  /// Returns the offset of the chunk and whether it was a preferred one.
+  pub fn find_preferring<'a, I, F>(
+    &mut self,
+    chunks: I,
+    size: usize,
+    prefer: F,
+  ) -> Option<(usize, bool)>
+  where
+    I: Iterator<Item = &'a Chunk> + Clone,
+    F: Fn(&Chunk) -> bool,
+  {
+    let preferred = self
+      .search(chunks.clone(), size, prefer)
+      .map(|offset| (offset, true));
+    let result = preferred.or_else(|| {
+      self
+        .search(chunks.clone(), size, |_| true)
+        .map(|offset| (offset, false))
+    });
+
+    if self.strategy == Strategy::Adaptive {
+      self.record(chunks, result.is_none());
+    }
+
+    result
+  }
+
+  fn search<'a, I, F>(&mut self, chunks: I, size: usize, accept: F) -> Option<usize>
+  where
+    I: Iterator<Item = &'a Chunk>,
+    F: Fn(&Chunk) -> bool,
+  {
+    let candidates = chunks.filter(|chunk| chunk.free && chunk.size >= size && accept(chunk));
+
+    if self.is_shuffled() {
+      // Pick any candidate, so consumers cannot rely on a specific layout
+      let candidates = candidates.map(|chunk| chunk.offset).collect::<Vec<_>>();
+      let choice = self.random()? % candidates.len().max(1) as u64;
+      return candidates.get(choice as usize).cloned();
+    }
+
+    match self.active {
+      Strategy::FirstFit => first_fit(candidates),
+      Strategy::WorstFit => worst_fit(candidates),
+      Strategy::Bump => candidates.last().map(|chunk| chunk.offset),
+      Strategy::NextFit => {
+        let offset = next_fit(candidates, self.cursor)?;
+        self.cursor = offset + size;
+        Some(offset)
+      }
+      _ => best_fit(candidates),
+    }
+  }
+
+  /// Advances the shuffle state, returning the next pseudo-random value.
+  fn random(&mut self) -> Option<u64> {
+    // This uses SplitMix64, which is well distributed for any seed
+    let state = self.shuffle.as_mut()?;
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+    let mut value = *state;
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    Some(value ^ (value >> 31))
+  }
+
+  fn record<'a, I: Iterator<Item = &'a Chunk>>(&mut self, chunks: I, failed: bool) {
+    self.attempts += 1;
+    self.failures += failed as usize;
+
+    if self.attempts < ADAPTIVE_WINDOW && !failed {
+      return;
+    }
+
+    // The thresholds differ, so the strategy does not flip back and forth
+    let free = chunks.filter(|chunk| chunk.free).count();
+    self.active = match self.active {
+      Strategy::FirstFit if free > ADAPTIVE_FRAGMENTED || self.failures > 0 => Strategy::BestFit,
+      Strategy::BestFit if free < ADAPTIVE_COMPACT && self.failures == 0 => Strategy::FirstFit,
+      active => active,
+    };
+
+    self.attempts = 0;
+    self.failures = 0;
+  }
+
+  fn initial(strategy: Strategy) -> Strategy {
+    match strategy {
+      Strategy::Adaptive => Strategy::FirstFit,
+      strategy => strategy,
+    }
+  }
+}
+
+/// Selects the first candidate chunk.
+fn first_fit<'a, I>(mut candidates: I) -> Option<usize>
+where
+  I: Iterator<Item = &'a Chunk>,
+{
+  candidates.next().map(|chunk| chunk.offset)
+}
+
+/// Selects the candidate chunk with the least amount of memory.
+fn best_fit<'a, I>(candidates: I) -> Option<usize>
+where
+  I: Iterator<Item = &'a Chunk>,
+{
+  candidates
+    .min_by_key(|chunk| chunk.size)
+    .map(|chunk| chunk.offset)
+}
+
+/// Selects the first candidate chunk with the most amount of memory.
+fn worst_fit<'a, I>(candidates: I) -> Option<usize>
+where
+  I: Iterator<Item = &'a Chunk>,
+{
+  candidates
+    .min_by_key(|chunk| Reverse(chunk.size))
+    .map(|chunk| chunk.offset)
+}
+
+/// Selects the first candidate chunk at or after a cursor, wrapping around
+/// to the first candidate.
+fn next_fit<'a, I>(candidates: I, cursor: usize) -> Option<usize>
+where
+  I: Iterator<Item = &'a Chunk>,
+{
+  let mut first = None;
+  for chunk in candidates {
+    if chunk.offset >= cursor {
+      return Some(chunk.offset);
+    }
+    first = first.or(Some(chunk.offset));
+  }
+  first
+}
+
+/// Selects the position within a candidate chunk closest to a hint.
+///
+/// Returns the offset of the chunk and the offset of the allocation.
+pub fn nearest<'a, I>(chunks: I, size: usize, hint: usize) -> Option<(usize, usize)>
+where
+  I: Iterator<Item = &'a Chunk>,
+{
+  chunks
+    .filter(|chunk| chunk.free && chunk.size >= size)
+    .map(|chunk| {
+      let last = chunk.offset + chunk.size - size;
+      (chunk.offset, hint.clamp(chunk.offset, last))
+    })
+    .min_by_key(|&(_, offset)| offset.abs_diff(hint))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fragmented(count: usize) -> Vec<Chunk> {
+    (0..count * 2)
+      .map(|index| Chunk {
+        free: index % 2 == 0,
+        ..Chunk::with_offset(1, index)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn adaptive_hysteresis() {
+    let mut placer = Placer::new(Strategy::Adaptive);
+    assert_eq!(placer.active(), Strategy::FirstFit);
+
+    // A failed allocation switches to best-fit immediately
+    placer.find(fragmented(2).iter(), 2);
+    assert_eq!(placer.active(), Strategy::BestFit);
+
+    // Moderate fragmentation is not enough to switch back
+    let chunks = fragmented(8);
+    for _ in 0..ADAPTIVE_WINDOW {
+      placer.find(chunks.iter(), 1);
+    }
+    assert_eq!(placer.active(), Strategy::BestFit);
+
+    let chunks = fragmented(2);
+    for _ in 0..ADAPTIVE_WINDOW {
+      placer.find(chunks.iter(), 1);
+    }
+    assert_eq!(placer.active(), Strategy::FirstFit);
+
+    let chunks = fragmented(ADAPTIVE_FRAGMENTED + 1);
+    for _ in 0..ADAPTIVE_WINDOW {
+      placer.find(chunks.iter(), 1);
+    }
+    assert_eq!(placer.active(), Strategy::BestFit);
+  }
+
+  #[test]
+  fn worst_and_next_fit() {
+    let mut chunks = fragmented(4);
+    chunks[4].size = 3;
+    chunks.truncate(5);
+
+    let mut placer = Placer::new(Strategy::WorstFit);
+    assert_eq!(placer.find(chunks.iter(), 1), Some(4));
+
+    // Next-fit resumes after the previous allocation, and wraps around
+    placer.set_strategy(Strategy::NextFit);
+    let offsets = (0..4)
+      .map(|_| placer.find(chunks.iter(), 1).unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(offsets, [0, 2, 4, 0]);
+  }
+
+  #[test]
+  fn shuffle_seeded() {
+    let chunks = fragmented(16);
+    let choices = |seed| {
+      let mut placer = Placer::new(Strategy::FirstFit);
+      placer.set_shuffle(Some(seed));
+      (0..8)
+        .map(|_| placer.find(chunks.iter(), 1).unwrap())
+        .collect::<Vec<_>>()
+    };
+
+    // The same seed reproduces the same placements
+    assert_eq!(choices(1), choices(1));
+    assert_ne!(choices(1), choices(2));
+    assert!(choices(1).iter().all(|&offset| chunks[offset].free));
+
+    let mut placer = Placer::new(Strategy::FirstFit);
+    placer.set_shuffle(Some(1));
+    placer.set_strategy(Strategy::BestFit);
+    assert!(placer.is_shuffled());
+    assert!(placer.find(fragmented(2).iter(), 2).is_none());
+  }
+}
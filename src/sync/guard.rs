@@ -0,0 +1,143 @@
+use super::SliceBox;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+
+/// A slot holding an attached allocation until it is released.
+type Slot<T> = Mutex<Option<SliceBox<T>>>;
+
+/// A scope which releases its attached allocations when dropped.
+///
+/// Allocations attached to a guard are accessed through `GuardedBox`
+/// handles, which may be stashed anywhere (e.g. in task-local structures).
+/// When the guard is dropped, because its task completed, was cancelled or
+/// panicked, every attached allocation is returned to the pool and the
+/// handles can no longer access it.
+pub struct PoolGuard<T: Send + 'static> {
+  slots: Mutex<Vec<Arc<Slot<T>>>>,
+}
+
+impl<T: Send> PoolGuard<T> {
+  /// Constructs a new guard without any attached allocations.
+  pub fn new() -> Self {
+    PoolGuard {
+      slots: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// Attaches an allocation to the guard, returning a handle to it.
+  pub fn attach(&self, data: SliceBox<T>) -> GuardedBox<T> {
+    let slot = Arc::new(Mutex::new(Some(data)));
+    let mut slots = self.slots.lock().expect("poisoned guard");
+
+    // Forget the slots of allocations released through their handles
+    slots.retain(|slot| lock(slot).is_some());
+    slots.push(slot.clone());
+
+    GuardedBox(Arc::downgrade(&slot))
+  }
+
+  /// Returns the number of allocations attached to the guard.
+  pub fn attached(&self) -> usize {
+    let slots = self.slots.lock().expect("poisoned guard");
+    slots.iter().filter(|slot| lock(slot).is_some()).count()
+  }
+}
+
+impl<T: Send> Default for PoolGuard<T> {
+  fn default() -> Self {
+    PoolGuard::new()
+  }
+}
+
+impl<T: Send> Drop for PoolGuard<T> {
+  /// Releases all attached allocations.
+  fn drop(&mut self) {
+    // The guard may be dropped while unwinding, so poisoning is ignored
+    let slots = self.slots.get_mut().unwrap_or_else(PoisonError::into_inner);
+    for slot in slots.drain(..) {
+      lock(&slot).take();
+    }
+  }
+}
+
+/// A handle to an allocation attached to a `PoolGuard`.
+///
+/// Dropping the handle releases the allocation early.
+pub struct GuardedBox<T: Send + 'static>(Weak<Slot<T>>);
+
+impl<T: Send> GuardedBox<T> {
+  /// Calls a closure with the allocation, unless the guard has released it.
+  pub fn with<F, R>(&self, f: F) -> Option<R>
+  where
+    F: FnOnce(&mut [T]) -> R,
+  {
+    let slot = self.0.upgrade()?;
+    let mut data = slot.lock().expect("poisoned slot");
+    data.as_mut().map(|data| f(data))
+  }
+
+  /// Returns whether the allocation has been released by the guard.
+  pub fn is_released(&self) -> bool {
+    self.0.upgrade().is_none_or(|slot| lock(&slot).is_none())
+  }
+
+  /// Detaches the allocation from the guard, unless it has been released.
+  pub fn detach(self) -> Option<SliceBox<T>> {
+    self.0.upgrade().and_then(|slot| lock(&slot).take())
+  }
+}
+
+impl<T: Send> Drop for GuardedBox<T> {
+  fn drop(&mut self) {
+    if let Some(slot) = self.0.upgrade() {
+      lock(&slot).take();
+    }
+  }
+}
+
+/// Locks a slot, ignoring whether a closure accessing it has panicked.
+fn lock<T: Send>(slot: &Slot<T>) -> MutexGuard<'_, Option<SliceBox<T>>> {
+  slot.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// The guard is only shared across threads with the thread-safe flavor
+#[cfg(all(test, not(feature = "single-thread")))]
+mod tests {
+  use super::super::SlicePool;
+  use super::*;
+  use std::sync::mpsc;
+  use std::thread;
+
+  #[test]
+  fn guard_cancellation() {
+    let pool = Arc::new(SlicePool::new(vec![0; 8]));
+    let (sender, receiver) = mpsc::channel();
+
+    // The task stashes a handle elsewhere, then panics
+    let task = pool.clone();
+    let result = thread::spawn(move || {
+      let guard = PoolGuard::new();
+      let stashed = guard.attach(task.alloc(4).unwrap());
+      let _local = guard.attach(task.alloc(4).unwrap());
+      stashed.with(|data| data[0] = 1);
+      sender.send(stashed).unwrap();
+      assert_eq!(guard.attached(), 2);
+      panic!("task cancelled");
+    })
+    .join();
+
+    assert!(result.is_err());
+    let stashed = receiver.recv().unwrap();
+    assert!(stashed.is_released());
+    assert!(stashed.with(|data| data[0]).is_none());
+    assert!(pool.alloc(8).is_some());
+
+    let guard = PoolGuard::new();
+    let handle = guard.attach(pool.alloc(2).unwrap());
+    assert_eq!(handle.with(|data| data.len()), Some(2));
+
+    let data = handle.detach().unwrap();
+    assert_eq!(guard.attached(), 0);
+    drop(guard);
+    assert_eq!(data.len(), 2);
+  }
+}
@@ -1,93 +1,79 @@
 //! Synchronized memory pools.
+//!
+//! When the `single-thread` feature is enabled, the pools of this module
+//! use the `Local` flavor instead, which compiles out their locking. This
+//! is intended for builds that never use threads, but depend on libraries
+//! written against this module. Static pools remain thread-safe.
+
+pub use self::guard::{GuardedBox, PoolGuard};
+pub use self::owned::{Sliceable, ThreadSafe};
+pub use self::statics::StaticPool;
+pub use self::tiered::{Tier, TieredBox, TieredGuard, TieredPool};
+pub use AffinityStats;
+
+mod guard;
+mod owned;
+mod statics;
+mod tiered;
 
-pub use self::owned::{SliceBox, SlicePool, Sliceable};
-use std::sync::Mutex;
-use Chunk;
+/// The flavor of the pools in this module.
+#[cfg(not(feature = "single-thread"))]
+type Synchronization = ThreadSafe;
 
-mod owned;
+/// The flavor of the pools in this module.
+#[cfg(feature = "single-thread")]
+type Synchronization = ::Local;
+
+/// A thread-safe interface for allocating chunks in an owned slice.
+pub type SlicePool<T> = ::SlicePool<T, Synchronization>;
+
+/// An allocation in a thread-safe `SlicePool`.
+pub type SliceBox<T> = ::SliceBox<T, Synchronization>;
+
+/// A handle releasing a detached allocation of a thread-safe `SlicePool`.
+pub type Releaser<T> = ::Releaser<T, Synchronization>;
+
+/// A thread-safe, shareable handle to an allocation in a `SlicePool`.
+pub type SharedBox<T> = ::SharedBox<T, Synchronization>;
+
+/// An immutable borrow of a thread-safe `SharedBox`.
+pub type SharedRef<'a, T> = ::SharedRef<'a, T, Synchronization>;
+
+/// A mutable borrow of a thread-safe `SharedBox`.
+pub type SharedRefMut<'a, T> = ::SharedRefMut<'a, T, Synchronization>;
+
+/// A thread-safe, copy-on-write handle to an allocation in a `SlicePool`.
+pub type CowSliceBox<'a, T> = ::CowSliceBox<'a, T, Synchronization>;
+
+/// A growable vector stored in a thread-safe `SlicePool`.
+pub type PoolVec<'a, T> = ::PoolVec<'a, T, Synchronization>;
+
+/// A growable string stored in a thread-safe byte `SlicePool`.
+pub type PoolString<'a> = ::PoolString<'a, Synchronization>;
+
+/// A typed array allocated from a thread-safe byte `SlicePool`.
+pub type TypedSlice<U> = ::TypedSlice<U, Synchronization>;
+
+/// A length-prefixed frame allocated from a thread-safe byte `SlicePool`.
+pub type FrameBox = ::FrameBox<Synchronization>;
+
+/// An open allocation of a thread-safe `SlicePool`, committed once appended.
+pub type OpenAlloc<'a, T> = ::OpenAlloc<'a, T, Synchronization>;
+
+/// Elements which are either borrowed, on the heap, or in a thread-safe `SlicePool`.
+pub type BorrowedOrPooled<'a, T> = ::BorrowedOrPooled<'a, T, Synchronization>;
+
+/// Two thread-safe allocations, of which one is presented while the other is prepared.
+pub type DoubleBuffer<T> = ::DoubleBuffer<T, Synchronization>;
+
+/// A ring of thread-safe allocations, passing data from a producer to a consumer.
+pub type SwapChain<T> = ::SwapChain<T, Synchronization>;
+
+/// Elements of a thread-safe `SlicePool` reserved for an allocation to grow into.
+pub type TailReservation<T> = ::TailReservation<T, Synchronization>;
+
+/// A builder of a thread-safe `SlicePool`.
+pub type SlicePoolBuilder<T> = ::SlicePoolBuilder<T, Synchronization>;
 
-enum Order {
-  Preceding,
-  Following,
-}
-
-/// A thread-safe chunk chain.
-struct ChunkChain(Mutex<Vec<Chunk>>);
-
-impl ChunkChain {
-  pub fn new(size: usize) -> Self {
-    ChunkChain(Mutex::new(vec![Chunk::new(size)]))
-  }
-
-  pub fn allocate(&self, size: usize) -> Option<Chunk> {
-    let mut chunks = self.0.lock().expect("poisoned chain");
-
-    // Find a chunk with the least amount of memory required
-    let (index, _) = chunks
-      .iter()
-      .enumerate()
-      .filter(|(_, chunk)| chunk.free && chunk.size >= size)
-      .min_by_key(|(_, chunk)| chunk.size)?;
-
-    // Determine whether there is any memory surplus
-    let delta = chunks[index].size - size;
-
-    if delta > 0 {
-      // Deduct the left over memory from the allocation
-      chunks[index].size -= delta;
-
-      if Self::has_free_adjacent(&chunks, index, Order::Preceding) {
-        // Increase the size of the preceding chunk
-        chunks[index - 1].size += delta;
-
-        // Shift the offset of the allocated chunk
-        chunks[index].offset += delta;
-      } else if Self::has_free_adjacent(&chunks, index, Order::Following) {
-        // Update the size and offset of the next chunk
-        chunks[index + 1].offset -= delta;
-        chunks[index + 1].size += delta;
-      } else {
-        // Insert a new chunk representing the surplus memory
-        let offset = chunks[index].offset + size;
-        chunks.insert(index + 1, Chunk::with_offset(delta, offset));
-        chunks[index].free = false;
-      }
-    } else {
-      // The allocation covers a single chunk
-      chunks[index].free = false;
-    }
-
-    Some(chunks[index])
-  }
-
-  pub fn release(&self, offset: usize) {
-    let mut chunks = self.0.lock().expect("poisoned chain");
-
-    let index = chunks
-      .binary_search_by_key(&offset, |chunk| chunk.offset)
-      .expect("releasing chunk");
-    let size = chunks[index].size;
-
-    if Self::has_free_adjacent(&chunks, index, Order::Preceding) {
-      // Increase the preceding chunk's size
-      chunks[index - 1].size += size;
-    } else if Self::has_free_adjacent(&chunks, index, Order::Following) {
-      // Increase the extent of the next chunk
-      chunks[index + 1].offset -= size;
-      chunks[index + 1].size += size;
-    } else {
-      // No free adjacent chunks, simply mark this one as free
-      chunks[index].free = true;
-      return;
-    }
-
-    chunks.remove(index);
-  }
-
-  fn has_free_adjacent(chunks: &[Chunk], index: usize, order: Order) -> bool {
-    match order {
-      Order::Preceding => index > 0 && chunks[index - 1].free,
-      Order::Following => index + 1 < chunks.len() && chunks[index + 1].free,
-    }
-  }
-}
+/// A reader of a stream into frames allocated from a thread-safe byte `SlicePool`.
+pub type PooledReader<'a, R> = ::PooledReader<'a, R, Synchronization>;
@@ -1,7 +1,6 @@
-use super::ChunkChain;
-use std::ops::{Deref, DerefMut};
-use std::sync::Arc;
-use std::{fmt, mem, slice};
+use flavor::{Erase, Flavor, Sealed};
+use std::sync::{Arc, Mutex};
+use {AllocPolicy, PoolError};
 
 /// Interface for any slice compatible with a thread-safe `SlicePool`.
 pub trait Sliceable<T>: Send + Sync + AsMut<[T]> + AsRef<[T]> {}
@@ -14,100 +13,85 @@ where
 {
 }
 
-/// A thread-safe interface for allocating chunks in an owned slice.
-pub struct SlicePool<T: Send> {
-  chain: Arc<ChunkChain>,
-  slice: Arc<Sliceable<T>>,
-}
+/// The flavor of thread-safe pools, synchronized using `Arc` and `Mutex`.
+pub enum ThreadSafe {}
 
-impl<T: Send + 'static> SlicePool<T> {
-  /// Constructs a new owned slice pool from a sliceable object.
-  pub fn new<S: Sliceable<T> + 'static>(slice: S) -> Self {
-    let size = slice.as_ref().len();
+impl Sealed for ThreadSafe {}
 
-    SlicePool {
-      chain: Arc::new(ChunkChain::new(size)),
-      slice: Arc::new(slice),
-    }
-  }
+impl Flavor for ThreadSafe {
+  type Ptr<U: ?Sized + 'static> = Arc<U>;
+  type Lock<U: 'static> = Mutex<U>;
+  type Slice<T: 'static> = dyn Sliceable<T>;
+  type Evict = dyn FnMut(usize) -> bool + Send;
+  type Release = dyn FnMut() + Send;
+  type ErrorHook = dyn FnMut(PoolError) + Send;
+  type Policy = dyn AllocPolicy + Send;
 
-  /// Allocates a new slice from the pool.
-  pub fn alloc(&self, size: usize) -> Option<SliceBox<T>> {
-    let chunk = self.chain.allocate(size)?;
-
-    // The following code uses unsafe, and is the only occurring instance of it.
-    // Since the 'SliceBox' is a self-referential type, Rust does not allow us
-    // to express this with its current lifetime semantics. To avoid this
-    // restriction, the slice is transmuted to a static and mutable slice. It
-    // can be treated as static, since it's next to the 'Arc', which is keeping
-    // the data alive. It can also be treated as mutable since the 'SliceBox'
-    // becomes the only way to access the slice.
-    let data: &'static mut [T] = unsafe {
-      let offset = chunk.offset as isize;
-      let base = (*self.slice).as_ref().as_ptr().offset(offset);
-      slice::from_raw_parts_mut(base as *mut _, size)
-    };
+  fn share<U: 'static>(value: U) -> Arc<U> {
+    Arc::new(value)
+  }
 
-    Some(SliceBox {
-      chain: self.chain.clone(),
-      slice: self.slice.clone(),
-      data,
-    })
+  fn ptr_eq<U: ?Sized + 'static>(this: &Arc<U>, other: &Arc<U>) -> bool {
+    Arc::ptr_eq(this, other)
   }
 
-  /// Returns the address of the underlying slice.
-  pub fn as_ptr(&self) -> *const T {
-    (*self.slice).as_ref().as_ptr()
+  fn try_unwrap<U: 'static>(ptr: Arc<U>) -> Result<U, Arc<U>> {
+    Arc::try_unwrap(ptr)
   }
 
-  /// Returns the size of the underlying slice.
-  pub fn len(&self) -> usize {
-    (*self.slice).as_ref().len()
+  fn get_mut<U: ?Sized + 'static>(ptr: &mut Arc<U>) -> Option<&mut U> {
+    Arc::get_mut(ptr)
   }
 }
 
-/// An allocation in an owned `SlicePool`.
-pub struct SliceBox<T: Send + 'static> {
-  #[allow(unused)]
-  slice: Arc<Sliceable<T>>,
-  chain: Arc<ChunkChain>,
-  data: &'static mut [T],
+impl<T: 'static, S: Sliceable<T> + 'static> Erase<ThreadSafe, dyn Sliceable<T>> for S {
+  fn erase(self) -> Box<dyn Sliceable<T>> {
+    Box::new(self)
+  }
 }
 
-impl<T: Send> Deref for SliceBox<T> {
-  type Target = [T];
-
-  fn deref(&self) -> &Self::Target {
-    &self.data
+impl<E: FnMut(usize) -> bool + Send + 'static> Erase<ThreadSafe, dyn FnMut(usize) -> bool + Send>
+  for E
+{
+  fn erase(self) -> Box<dyn FnMut(usize) -> bool + Send> {
+    Box::new(self)
   }
 }
 
-impl<T: Send> DerefMut for SliceBox<T> {
-  fn deref_mut<'b>(&'b mut self) -> &'b mut [T] {
-    self.data
+impl<E: FnMut() + Send + 'static> Erase<ThreadSafe, dyn FnMut() + Send> for E {
+  fn erase(self) -> Box<dyn FnMut() + Send> {
+    Box::new(self)
   }
 }
 
-impl<T: Send> Drop for SliceBox<T> {
-  /// Returns the ownership of the slice to the pool.
-  fn drop(&mut self) {
-    let base = (*self.slice).as_ref().as_ptr();
-    let diff = (self.data.as_ptr() as isize).wrapping_sub(base as isize);
-    self.chain.release(diff as usize / mem::size_of::<T>())
+impl<E: FnMut(PoolError) + Send + 'static> Erase<ThreadSafe, dyn FnMut(PoolError) + Send> for E {
+  fn erase(self) -> Box<dyn FnMut(PoolError) + Send> {
+    Box::new(self)
   }
 }
 
-impl<T: Send + fmt::Debug> fmt::Debug for SliceBox<T> {
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "{:?}", self.deref())
+impl<P: AllocPolicy + Send + 'static> Erase<ThreadSafe, dyn AllocPolicy + Send> for P {
+  fn erase(self) -> Box<dyn AllocPolicy + Send> {
+    Box::new(self)
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use super::*;
-  use std::sync::Arc;
+  use std::marker::PhantomPinned;
+  use std::mem;
+  use std::panic::{self, AssertUnwindSafe};
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::{Arc, Mutex};
   use std::thread;
+  use sync::ThreadSafe;
+
+  /// The tests cover this flavor, regardless of the `single-thread` feature.
+  type SlicePool<T> = ::SlicePool<T, ThreadSafe>;
+  use {
+    AffinityStats, AllocError, AllocPolicy, ChunkInfo, ChunkState, ClassStats, ErrorCode,
+    LengthError, Order, PeakUsage, PoolError, RawChunk, RegionStats, Strategy, TaggedPoolPtr,
+  };
 
   #[test]
   fn pool_owned_lifetime() {
@@ -145,12 +129,122 @@ mod tests {
     thread::spawn(move || {
       let val = pool2.alloc(2).unwrap();
       assert_eq!(*val, [30, 40]);
-    }).join()
+    })
+    .join()
     .unwrap();
 
     assert_eq!(pool.len(), 4);
   }
 
+  #[test]
+  fn pool_thread_affinity() {
+    let pool = Arc::new(SlicePool::new(vec![0; 8]));
+    pool.set_thread_affinity(true);
+
+    let val1 = pool.alloc(2).unwrap();
+    let _val2 = pool.alloc(2).unwrap();
+    let val3 = pool.alloc(2).unwrap();
+    let _val4 = pool.alloc(2).unwrap();
+    mem::drop(val1);
+
+    // The other thread frees the third chunk and allocates it again
+    let remote = pool.clone();
+    thread::spawn(move || {
+      mem::drop(val3);
+      let _val5 = remote.alloc(1).unwrap();
+      assert_eq!(remote.affinity_stats().unwrap().hits, 1);
+    })
+    .join()
+    .unwrap();
+
+    // This thread prefers the first chunk, although the third one is free
+    let val6 = pool.alloc(1).unwrap();
+    assert_eq!(val6.as_ptr(), pool.as_ptr());
+
+    // The initial allocations carve untouched memory and count as misses
+    let stats = pool.affinity_stats().unwrap();
+    assert_eq!(stats, AffinityStats { hits: 2, misses: 4 });
+    assert_eq!(stats.hit_rate(), 2.0 / 6.0);
+  }
+
+  #[test]
+  fn pool_contiguous() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let head = pool.alloc(2).unwrap();
+    let mid = pool.alloc(2).unwrap();
+    mem::drop(head);
+
+    let before = pool
+      .alloc_contiguous_with(&mid, 2, Order::Preceding)
+      .unwrap();
+    assert_eq!(before.as_ptr(), pool.as_ptr());
+    assert!(pool
+      .alloc_contiguous_with(&before, 1, Order::Preceding)
+      .is_none());
+
+    assert!(pool
+      .alloc_contiguous_with(&mid, 5, Order::Following)
+      .is_none());
+    let after = pool
+      .alloc_contiguous_with(&mid, 3, Order::Following)
+      .unwrap();
+    assert_eq!(after.as_ptr(), mid.as_ptr().wrapping_add(2));
+
+    // Allocations from another pool are never adjacent
+    let other = SlicePool::new(vec![0; 8]);
+    assert!(other
+      .alloc_contiguous_with(&after, 1, Order::Following)
+      .is_none());
+
+    mem::drop((after, mid, before));
+    assert!(pool.alloc(8).is_some());
+  }
+
+  #[test]
+  fn pool_small_region() {
+    let pool = SlicePool::new(vec![0; 16]).with_small_region(4, 2);
+    let small = pool.alloc(2).unwrap();
+    let large = pool.alloc(3).unwrap();
+    assert_eq!(small.as_ptr(), pool.as_ptr());
+    assert_eq!(large.as_ptr(), pool.as_ptr().wrapping_add(4));
+
+    // Small allocations use the chain once the region is full
+    let rest = pool.alloc(2).unwrap();
+    let spill = pool.alloc(1).unwrap();
+    assert_eq!(spill.as_ptr(), pool.as_ptr().wrapping_add(7));
+
+    let stats = RegionStats {
+      small_allocations: 2,
+      small_used: 4,
+      chain_allocations: 2,
+      chain_used: 4,
+    };
+    assert_eq!(pool.region_stats(), stats);
+
+    mem::drop((spill, large, small, rest));
+    assert!(pool.alloc(12).is_some());
+    assert!(pool.alloc(13).is_none());
+  }
+
+  #[test]
+  fn pool_shuffle() {
+    let layout = |seed| {
+      let pool = SlicePool::new(vec![0; 16]);
+      pool.set_shuffle_seed(seed);
+      let boxes = (0..8).map(|_| pool.alloc(2).unwrap()).collect::<Vec<_>>();
+      let base = pool.as_ptr();
+      boxes
+        .iter()
+        .map(|data| data.as_ptr() as usize - base as usize)
+        .collect::<Vec<_>>()
+    };
+
+    let regular = layout(None);
+    assert!(regular.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(layout(Some(7)), layout(Some(7)));
+    assert!((0..4).any(|seed| layout(Some(seed)) != regular));
+  }
+
   #[test]
   fn pool_fragmentation() {
     let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80, 90, 100]);
@@ -173,4 +267,1275 @@ mod tests {
     let val5 = pool.alloc(4).unwrap();
     assert_eq!(*val5, [30, 40, 50, 60]);
   }
+
+  #[test]
+  fn pool_fast_path() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80]);
+
+    let val1 = pool.alloc(2).unwrap();
+    let val2 = pool.alloc(3).unwrap();
+    let _val3 = pool.alloc(1).unwrap();
+    mem::drop(val2);
+
+    let val4 = pool.alloc(3).unwrap();
+    assert_eq!(*val4, [30, 40, 50]);
+    assert_eq!(pool.fast_path_hits(), 1);
+
+    mem::drop(val1);
+    let val5 = pool.alloc(2).unwrap();
+    assert_eq!(*val5, [10, 20]);
+    assert_eq!(pool.fast_path_hits(), 2);
+
+    // The released chunk merges with its free neighbour
+    mem::drop(val4);
+    mem::drop(val5);
+    assert_eq!(*pool.alloc(3).unwrap(), [10, 20, 30]);
+    assert_eq!(pool.fast_path_hits(), 2);
+  }
+
+  #[test]
+  fn pool_strategy() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80]);
+    let _val1 = pool.alloc(2).unwrap();
+    let val2 = pool.alloc(3).unwrap();
+    let _val3 = pool.alloc(1).unwrap();
+    mem::drop(val2);
+
+    pool.set_strategy(Strategy::FirstFit);
+    assert_eq!(*pool.alloc(1).unwrap(), [30]);
+
+    pool.set_strategy(Strategy::BestFit);
+    assert_eq!(*pool.alloc(1).unwrap(), [70]);
+
+    pool.set_strategy(Strategy::Adaptive);
+    assert_eq!(pool.strategy(), Strategy::Adaptive);
+    assert_eq!(pool.active_strategy(), Strategy::FirstFit);
+
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60]).with_strategy(Strategy::WorstFit);
+    let val1 = pool.alloc(2).unwrap();
+    let _val2 = pool.alloc(1).unwrap();
+    mem::drop(val1);
+    assert_eq!(*pool.alloc(1).unwrap(), [40]);
+
+    // TLSF placement prefers a size range which is guaranteed to fit
+    let pool = SlicePool::new(vec![0; 100]).with_strategy(Strategy::Tlsf);
+    let mut data = [33, 1, 34, 1]
+      .iter()
+      .map(|&size| pool.alloc(size))
+      .collect::<Vec<_>>();
+    data[0] = None;
+    data[2] = None;
+    let val1 = pool.alloc(33).unwrap();
+    assert_eq!(val1.offset(), 34);
+
+    // The size range of the allocation itself is searched as a fallback
+    let val2 = pool.alloc(33).unwrap();
+    assert_eq!(val2.offset(), 0);
+    assert_eq!(pool.alloc(31).unwrap().offset(), 69);
+
+    // A pool can be filled exactly
+    let pool = SlicePool::new(vec![0; 35]).with_strategy(Strategy::Tlsf);
+    let data = pool.alloc(35).unwrap();
+    assert_eq!(pool.available(), 0);
+    mem::drop(data);
+    assert!(pool.alloc(35).is_some());
+
+    // Bump placement never reuses memory before the end of the pool
+    let pool = SlicePool::new(vec![0; 10]).with_strategy(Strategy::Bump);
+    let val1 = pool.alloc(3).unwrap();
+    let val2 = pool.alloc(2).unwrap();
+    mem::drop(val1);
+    let val3 = pool.alloc(3).unwrap();
+    assert_eq!((val2.offset(), val3.offset()), (3, 5));
+    assert!(pool.alloc(3).is_none());
+    assert_eq!(pool.alloc(2).unwrap().offset(), 8);
+
+    mem::drop((val2, val3));
+    assert!(pool.reset());
+    assert_eq!(
+      (pool.chunk_count(), pool.alloc(10).unwrap().offset()),
+      (1, 0)
+    );
+  }
+
+  #[test]
+  fn pool_alloc_policy() {
+    /// Chooses the chunk at a fixed offset, recording every request.
+    struct Fixed(usize, Arc<Mutex<Vec<usize>>>);
+
+    impl AllocPolicy for Fixed {
+      fn choose(&mut self, chunks: &[ChunkInfo], size: usize, _: usize) -> Option<usize> {
+        assert!(chunks.iter().any(|chunk| chunk.offset == self.0));
+        self.1.lock().unwrap().push(size);
+        Some(self.0)
+      }
+    }
+
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let pool = SlicePool::new(vec![0; 8]);
+    let data = pool.alloc(4).unwrap();
+    pool.set_alloc_policy(Fixed(4, requests.clone()));
+    assert!(pool.alloc(5).is_none());
+    assert_eq!(pool.alloc(2).unwrap().offset(), 4);
+
+    // Choosing an allocated chunk fails the allocation
+    pool.set_alloc_policy(Fixed(0, requests.clone()));
+    assert!(pool.alloc(1).is_none());
+    mem::drop(data);
+    assert_eq!(*requests.lock().unwrap(), [5, 2, 1]);
+
+    // The policy is kept when converting the pool
+    let pool = pool.into_unsync().unwrap();
+    assert_eq!(pool.alloc(1).unwrap().offset(), 0);
+    pool.clear_alloc_policy();
+    assert_eq!(requests.lock().unwrap().len(), 4);
+  }
+
+  #[test]
+  fn pool_alloc_policy_align() {
+    /// Chooses the chunk at a fixed offset, recording the alignment.
+    struct Aligned(usize, Arc<Mutex<usize>>);
+
+    impl AllocPolicy for Aligned {
+      fn choose(&mut self, _: &[ChunkInfo], _: usize, align: usize) -> Option<usize> {
+        *self.1.lock().unwrap() = align;
+        Some(self.0)
+      }
+    }
+
+    let align = Arc::new(Mutex::new(0));
+    let mut pool = SlicePool::new(vec![0u32; 16]);
+    let _data = pool.alloc(1).unwrap();
+    pool.set_tag_bits(4);
+
+    // The policy is given the period of aligned addresses, in elements
+    pool.set_alloc_policy(Aligned(1, align.clone()));
+    assert!(pool.alloc(1).is_none());
+    assert_eq!(*align.lock().unwrap(), 4);
+  }
+
+  #[test]
+  fn pool_soft_limit() {
+    let pool = Arc::new(SlicePool::new(vec![0; 8]));
+    let cache = Arc::new(Mutex::new(Vec::new()));
+
+    let evicted = cache.clone();
+    pool.set_soft_limit(4, move |_| evicted.lock().unwrap().pop().is_some());
+
+    for _ in 0..4 {
+      let value = pool.alloc(2).unwrap();
+      cache.lock().unwrap().insert(0, value);
+    }
+
+    // The limit was exceeded, so the oldest allocations have been evicted
+    assert_eq!(cache.lock().unwrap().len(), 2);
+    assert_eq!(pool.alloc(2).map(|value| value.len()), Some(2));
+
+    // Allocations fail once the callback has nothing left to evict
+    let evicted = cache.clone();
+    pool.set_soft_limit(0, move |_| evicted.lock().unwrap().pop().is_some());
+    assert!(pool.alloc(9).is_none());
+    assert!(cache.lock().unwrap().is_empty());
+  }
+
+  #[test]
+  fn pool_retain_allocations() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let cache = Arc::new(Mutex::new(vec![pool.alloc(3).unwrap()]));
+    let state = Arc::new(Mutex::new(vec![pool.alloc(2).unwrap()]));
+
+    let held = cache.clone();
+    let id = pool.register_owner("cache", move || held.lock().unwrap().clear());
+    let held = state.clone();
+    pool.register_owner("state", move || held.lock().unwrap().clear());
+
+    assert_eq!(pool.retain_allocations(|tag| tag != "cache"), 3);
+    assert!(cache.lock().unwrap().is_empty());
+    assert_eq!(state.lock().unwrap().len(), 1);
+
+    assert!(pool.unregister_owner(id));
+    assert!(!pool.unregister_owner(id));
+    assert_eq!(pool.retain_allocations(|_| false), 2);
+  }
+
+  #[test]
+  fn pool_alloc_with() {
+    let pool = SlicePool::new(vec![0; 6]);
+
+    let val = pool.alloc_with(4, |init| {
+      for index in (0..init.len()).rev() {
+        init.write(index, index * 10);
+      }
+    });
+    assert_eq!(*val.unwrap(), [0, 10, 20, 30]);
+    assert!(pool.alloc_with(7, |_| unreachable!()).is_none());
+  }
+
+  #[test]
+  fn pool_scrubbing() {
+    let values = vec![String::from("a"), String::from("b"), String::from("c")];
+    let mut pool = SlicePool::new(values);
+    pool.set_scrubbing(true);
+
+    let mut val1 = pool.alloc(3).unwrap();
+    val1.release_tail(1);
+    assert_eq!(*val1, ["a", "b"]);
+    mem::drop(val1);
+
+    let val2 = pool.alloc(3).unwrap();
+    assert!(val2.iter().all(String::is_empty));
+  }
+
+  #[test]
+  fn pool_granularity() {
+    let mut pool = SlicePool::new(vec![0; 70]);
+    pool.set_granularity(16);
+    assert_eq!(pool.max_possible_alloc(), 64);
+
+    let mut val1 = pool.alloc(3).unwrap();
+    let val2 = pool.alloc(17).unwrap();
+    assert_eq!((val1.len(), val2.offset(), pool.used_len()), (3, 16, 48));
+    assert_eq!(pool.try_alloc(20).unwrap_err().kind(), ErrorCode::Exhausted);
+
+    // Allocations grow into their rounding without allocating
+    assert!(val1.grow(13));
+    assert_eq!((val1.len(), pool.used_len()), (16, 48));
+    assert!(pool.with_alloc(5, |scratch| scratch.len()) == Some(5));
+    assert_eq!(pool.alloc(0).unwrap().len(), 0);
+  }
+
+  #[test]
+  fn pool_max_allocations() {
+    let pool = SlicePool::builder(vec![0; 16])
+      .header(2)
+      .max_allocations(2)
+      .build();
+    let first = pool.alloc(1).unwrap();
+    let _second = pool.alloc(0).unwrap();
+    let _third = pool.alloc(1).unwrap();
+    assert_eq!(
+      pool.try_alloc(1).unwrap_err(),
+      AllocError::Limited { max: 2 }
+    );
+    assert_eq!(ErrorCode::from_u8(9), Some(ErrorCode::Limited));
+
+    mem::drop(first);
+    assert_eq!(pool.alloc(1).unwrap().offset(), 2);
+  }
+
+  #[test]
+  fn pool_tag_bits() {
+    let mut pool = SlicePool::new(vec![0u16; 64]);
+    let _offset = pool.alloc(1).unwrap();
+    pool.set_tag_bits(3);
+
+    let mut data = pool.alloc(5).unwrap();
+    assert_eq!(data.len(), 5);
+    assert_eq!(data.as_ptr() as usize % 8, 0);
+
+    let ptr = data.as_mut_ptr();
+    let tagged = TaggedPoolPtr::<u16, 3>::new(ptr, 5).unwrap();
+    assert_eq!((tagged.ptr(), tagged.tag()), (ptr, 5));
+    assert_eq!(tagged.with_tag(2).unwrap().ptr(), ptr);
+    assert!(tagged.with_tag(8).is_none());
+    assert!(TaggedPoolPtr::<u16, 3>::new(ptr.wrapping_add(1), 0).is_none());
+
+    // The padding is returned to the pool
+    assert_eq!(data.as_raw_chunk().len, 5);
+    assert!(pool.is_allocated(&data.as_raw_chunk()));
+  }
+
+  #[test]
+  fn pool_checksums() {
+    let mut pool = SlicePool::new(vec![0u32; 8]);
+    pool.set_checksums(true);
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let hook = errors.clone();
+    pool.set_error_hook(move |error| hook.lock().unwrap().push(error));
+
+    let mut val1 = pool.alloc(4).unwrap();
+    let ptr = val1.as_mut_ptr();
+    mem::drop(val1);
+
+    // Reusing untouched memory is fine
+    mem::drop(pool.alloc(4).unwrap());
+    assert!(errors.lock().unwrap().is_empty());
+
+    // A write through a stale pointer is detected once the memory is reused
+    unsafe { *ptr.add(2) = 1 };
+    let _val2 = pool.alloc(2).unwrap();
+    let corrupted = PoolError::Corrupted { offset: 0, len: 4 };
+    assert_eq!(*errors.lock().unwrap(), [corrupted]);
+  }
+
+  #[test]
+  fn pool_weighted_classes() {
+    let pool = SlicePool::new(vec![0; 8]);
+    pool.set_class_weight("rx", 3);
+    pool.set_class_weight("tx", 1);
+    pool.set_admission_threshold(Some(4));
+
+    // Below the threshold, classes are admitted regardless of their share
+    let _tx1 = pool.alloc_class("tx", 2).unwrap();
+    let tx2 = pool.alloc_class("tx", 2).unwrap();
+    let _rx1 = pool.alloc_class("rx", 2).unwrap();
+
+    // Under pressure, a class is limited to its share of the pool
+    assert!(pool.alloc_class("tx", 1).is_none());
+    let _rx2 = pool.alloc_class("rx", 2).unwrap();
+
+    let stats = |tx_used, tx_rejected| {
+      vec![
+        ClassStats {
+          class: "rx",
+          weight: 3,
+          used: 4,
+          rejected: 0,
+        },
+        ClassStats {
+          class: "tx",
+          weight: 1,
+          used: tx_used,
+          rejected: tx_rejected,
+        },
+      ]
+    };
+    assert_eq!(pool.class_stats(), stats(4, 1));
+    mem::drop(tx2);
+    assert_eq!(pool.class_stats(), stats(2, 1));
+  }
+
+  #[test]
+  fn pool_classes_with_granularity() {
+    let mut pool = SlicePool::new(vec![0u32; 32]);
+    pool.set_granularity(4);
+    let rx = pool.alloc_class("rx", 3).unwrap();
+    assert_eq!(pool.class_stats()[0].used, 4);
+    mem::drop(rx);
+
+    // Tagged allocations are charged their trimmed size
+    pool.set_tag_bits(3);
+    let tx = pool.alloc_class("tx", 3).unwrap();
+    let used = |class| {
+      let stats = pool.class_stats();
+      stats
+        .iter()
+        .find(|stats| stats.class == class)
+        .unwrap()
+        .used
+    };
+    assert_eq!((used("rx"), used("tx")), (0, 3));
+    mem::drop(tx);
+    assert_eq!(used("tx"), 0);
+  }
+
+  #[test]
+  fn pool_header() {
+    let mut pool = SlicePool::new(vec![0; 8])
+      .with_small_region(2, 1)
+      .with_header(3);
+    pool.header_mut().copy_from_slice(&[1, 2, 3]);
+    assert_eq!(pool.header(), [1, 2, 3]);
+
+    // The small-object region follows the header, and the chain follows it
+    let small = pool.alloc(1).unwrap();
+    let large = pool.alloc(3).unwrap();
+    assert_eq!(
+      (small.as_raw_chunk().offset, large.as_raw_chunk().offset),
+      (3, 5)
+    );
+    let _small = pool.alloc(1).unwrap();
+    assert!(pool.alloc(1).is_none());
+    assert_eq!(pool.header(), [1, 2, 3]);
+  }
+
+  #[test]
+  fn box_snapshot() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let mut val = pool.alloc(2).unwrap();
+    let snapshot = val.snapshot();
+
+    val[0] = 15;
+    assert_eq!(*snapshot, [10, 20]);
+    assert_eq!(*val.snapshot(), [15, 20]);
+  }
+
+  #[test]
+  fn pool_stats() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let _val1 = pool.alloc(3).unwrap();
+    mem::drop(pool.alloc(2).unwrap());
+
+    let stats = pool.stats();
+    assert_eq!((stats.len, stats.used), (8, 3));
+    #[cfg(feature = "lifetime-histogram")]
+    assert_eq!(stats.lifetimes.count(), 1);
+  }
+
+  #[test]
+  fn pool_coalescing_stats() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let val1 = pool.alloc(2).unwrap();
+    let val2 = pool.alloc(2).unwrap();
+
+    let stats = pool.stats();
+    assert_eq!((stats.splits, stats.coalesces), (2, 0));
+    assert_eq!((stats.free_chunks, stats.free_chunk_len), (1, 4));
+
+    // Each release merges with the free chunk following it
+    mem::drop(val2);
+    assert_eq!(pool.stats().average_free_chunk_len(), 6.0);
+    mem::drop(val1);
+
+    let stats = pool.stats();
+    assert_eq!((stats.free_chunks, stats.free_chunk_len), (1, 8));
+    assert_eq!((stats.splits, stats.coalesces), (2, 2));
+  }
+
+  #[test]
+  fn pool_alloc_each() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let mut sizes = vec![2, 3, 4, 1].into_iter();
+
+    let mut boxes = pool.try_alloc_each(&mut sizes);
+    let allocated = boxes.by_ref().collect::<Vec<_>>();
+    assert_eq!((allocated[0].len(), allocated[1].len()), (2, 3));
+    assert_eq!((boxes.allocated(), boxes.failed()), (2, Some(4)));
+    assert!(boxes.next().is_none());
+
+    // The remaining sizes are not consumed
+    assert_eq!(sizes.next(), Some(1));
+  }
+
+  #[test]
+  fn pool_unique_id() {
+    let pool1 = SlicePool::new(vec![0; 4]);
+    let pool2 = SlicePool::new(vec![0; 4]);
+    assert_ne!(pool1.id(), pool2.id());
+    assert_eq!(pool2.alloc(1).unwrap().pool_id(), pool2.id());
+  }
+
+  #[test]
+  fn box_split_at() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60]).with_small_region(2, 2);
+    let (small1, small2) = pool.alloc(2).unwrap().split_at(1);
+    assert_eq!((&*small1, &*small2), (&[10][..], &[20][..]));
+
+    let (head, tail) = pool.alloc(4).unwrap().split_at(1);
+    assert_eq!((&*head, &*tail), (&[30][..], &[40, 50, 60][..]));
+
+    // The halves are released independently
+    mem::drop(small1);
+    mem::drop(tail);
+    let _large = pool.alloc(3).unwrap();
+    let _small = pool.alloc(1).unwrap();
+    assert!(pool.alloc(1).is_none());
+    assert_eq!(pool.region_stats().small_allocations, 2);
+    mem::drop(head);
+  }
+
+  #[test]
+  fn box_swap_contents() {
+    let pool = SlicePool::new(vec![1, 2, 3, 4, 5]);
+    let other = SlicePool::new(vec![10, 20]);
+    let mut data = pool.alloc(2).unwrap();
+    let mut remote = other.alloc(2).unwrap();
+    data.swap_contents(&mut remote).unwrap();
+    assert_eq!((&*data, &*remote), (&[10, 20][..], &[1, 2][..]));
+
+    let error = data.replace_contents(&[7, 8, 9]).unwrap_err();
+    assert_eq!(
+      (error, error.kind()),
+      (
+        LengthError {
+          expected: 2,
+          actual: 3
+        },
+        ErrorCode::Mismatched
+      )
+    );
+    data.replace_contents(&remote).unwrap();
+    assert_eq!(*data, [1, 2]);
+  }
+
+  #[test]
+  fn box_try_split_and_release() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let data = pool.alloc(3).unwrap();
+    let mut data = data.try_split_at(3).unwrap_err();
+    assert!(!data.try_release_head(3));
+    assert!(!data.try_release_tail(3));
+
+    assert!(data.try_release_tail(1));
+    let (head, tail) = data.try_split_at(1).unwrap();
+    assert_eq!(
+      (&*head, &*tail, pool.stats().used),
+      (&[10][..], &[20][..], 2)
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "recover-poison")]
+  fn pool_poisoned_lock() {
+    let pool = SlicePool::new(vec![0; 4]);
+    pool.set_soft_limit(2, |_| panic!("evicting"));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| pool.alloc(3)));
+    assert!(result.is_err());
+
+    // The soft limit is still usable once its callback panicked
+    pool.clear_soft_limit();
+    assert!(pool.alloc(3).is_some());
+  }
+
+  #[test]
+  fn pool_try_alloc() {
+    let pool = SlicePool::new(vec![0; 6]);
+    let first = pool.try_alloc(2).unwrap();
+    let _second = pool.try_alloc(2).unwrap();
+    let third = pool.try_alloc(2).unwrap();
+    mem::drop(first);
+    mem::drop(third);
+
+    let error = pool.try_alloc(3).unwrap_err();
+    assert_eq!(
+      error,
+      AllocError::Fragmented {
+        size: 3,
+        available: 4
+      }
+    );
+    assert!(error.is_fragmented() && !error.is_exhausted());
+
+    let error = pool.try_alloc(5).unwrap_err();
+    assert_eq!(error.kind(), ErrorCode::Exhausted);
+    assert!(error.is_exhausted());
+  }
+
+  #[test]
+  fn pool_max_possible_alloc() {
+    let pool = SlicePool::new(vec![0; 16])
+      .with_header(2)
+      .with_small_region(4, 2);
+    assert_eq!(pool.max_possible_alloc(), 10);
+
+    // Requests exceeding the capacity fail, even with no live allocations
+    let error = pool.try_alloc(11).unwrap_err();
+    assert_eq!(error, AllocError::TooLarge { size: 11, max: 10 });
+    assert!(error.is_too_large() && !error.is_exhausted());
+
+    let _data = pool.try_alloc(8).unwrap();
+    assert!(pool.try_alloc(10).unwrap_err().is_exhausted());
+  }
+
+  #[test]
+  fn pool_available() {
+    let pool = SlicePool::new(vec![0; 16])
+      .with_header(2)
+      .with_small_region(4, 2);
+    assert_eq!((pool.available(), pool.largest_free_block()), (14, 10));
+
+    let _first = pool.alloc(3).unwrap();
+    let second = pool.alloc(3).unwrap();
+    let _third = pool.alloc(4).unwrap();
+    mem::drop(second);
+    assert_eq!((pool.available(), pool.largest_free_block()), (7, 3));
+
+    // Only the small-object region remains, limited by its threshold
+    let _small = pool.alloc(3).unwrap();
+    assert_eq!((pool.available(), pool.largest_free_block()), (4, 2));
+  }
+
+  #[test]
+  fn pool_fragmentation_stats() {
+    let pool = SlicePool::new(vec![0; 16]);
+    assert_eq!(pool.fragmentation().ratio(), 0.0);
+
+    let mut boxes = (0..4).map(|_| pool.alloc(3).unwrap()).collect::<Vec<_>>();
+    boxes.remove(1);
+
+    let stats = pool.fragmentation();
+    assert_eq!((stats.free_chunks, stats.free_len), (2, 7));
+    assert_eq!(stats.largest_free_chunk, 4);
+    assert_eq!(stats.distribution, [0, 1, 1]);
+    assert!((stats.ratio() - 3.0 / 7.0).abs() < 1e-9);
+
+    boxes.pop();
+    let stats = pool.fragmentation();
+    assert_eq!(
+      (stats.largest_free_chunk, stats.distribution),
+      (7, vec![0, 1, 1])
+    );
+  }
+
+  #[test]
+  fn pool_counters() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let data = pool.alloc(4).unwrap();
+    let (head, tail) = data.split_at(2);
+    assert!(pool.try_alloc(16).unwrap_err().is_too_large());
+    assert!(pool.alloc(5).is_none());
+    assert_eq!(pool.with_alloc(4, |scratch| scratch.len()), Some(4));
+
+    let counters = pool.counters();
+    assert_eq!((counters.allocations, counters.releases), (3, 1));
+    assert_eq!((counters.failures, counters.live()), (2, 2));
+
+    let merged = head.try_merge(tail).unwrap();
+    mem::forget(pool.alloc(1).unwrap());
+    mem::drop(merged);
+    let counters = pool.counters();
+    assert_eq!((counters.allocations, counters.releases), (4, 3));
+    assert_eq!(counters.live(), 1);
+  }
+
+  #[test]
+  fn pool_stats_recorder() {
+    let pool = Arc::new(SlicePool::new(vec![0; 64]));
+    let data = pool.alloc(8).unwrap();
+
+    // The recorder is readable while the pool is locked by another thread
+    let sampler = {
+      let pool = pool.clone();
+      thread::spawn(move || (pool.recorder().used(), pool.recorder().counters()))
+    };
+    let (head, _tail) = data.split_at(4);
+    mem::drop(head);
+    let (used, counters) = sampler.join().unwrap();
+    assert!(used == 8 || used == 4);
+    assert!(counters.allocations >= 1);
+
+    let recorder = pool.recorder();
+    assert_eq!(recorder.used(), pool.stats().used);
+    assert_eq!(recorder.counters(), pool.counters());
+    assert_eq!(recorder.counters().live(), 1);
+  }
+
+  #[test]
+  fn pool_debug() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let _data = pool.alloc(3).unwrap();
+    let id = pool.id();
+    assert_eq!(
+      format!("{:?}", pool),
+      format!(
+        "SlicePool {{ id: {}, len: 8, used: 3, chunks: [{:?}, {:?}] }}",
+        id,
+        ChunkInfo {
+          offset: 0,
+          len: 3,
+          state: ChunkState::Allocated,
+        },
+        ChunkInfo {
+          offset: 3,
+          len: 5,
+          state: ChunkState::Free,
+        },
+      )
+    );
+  }
+
+  #[test]
+  fn pool_render_map() {
+    let pool = SlicePool::new(vec![0; 12]);
+    let _a = pool.alloc(4).unwrap();
+    let b = pool.alloc(4).unwrap();
+    let _c = pool.alloc(2).unwrap();
+    mem::drop(b);
+
+    assert_eq!(pool.render_map(12), "####....##..");
+    assert_eq!(pool.render_map(6), "##..#.");
+    assert_eq!(pool.render_map(5), "##.#.");
+    assert_eq!(pool.render_map(24).len(), 24);
+    assert_eq!(pool.render_map(0), "");
+
+    let pool = SlicePool::new(vec![0; 8]).with_header(2);
+    assert_eq!(pool.render_map(4), "=...");
+    assert_eq!(SlicePool::new(Vec::<u8>::new()).render_map(2), "..");
+  }
+
+  #[test]
+  fn pool_snapshot() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let _data = pool.alloc(3).unwrap();
+    mem::drop(pool.alloc(2).unwrap());
+
+    let snapshot = pool.snapshot();
+    assert_eq!(snapshot.stats, pool.stats());
+    assert_eq!(snapshot.fragmentation, pool.fragmentation());
+    assert_eq!(
+      (snapshot.counters.allocations, snapshot.counters.releases),
+      (2, 1)
+    );
+    assert_eq!(snapshot.peak, PeakUsage { used: 5, live: 2 });
+    assert_eq!(snapshot.chunks, pool.chunks().collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn pool_occupancy() {
+    let pool = SlicePool::new(vec![0; 16]).with_header(2);
+    let _empty = pool.alloc(0).unwrap();
+    assert!(pool.is_empty());
+    assert_eq!((pool.chunk_count(), pool.free_len()), (2, 14));
+
+    let data = pool.alloc(4).unwrap();
+    let _other = pool.alloc(3).unwrap();
+    assert!(!pool.is_empty());
+    assert_eq!(
+      (pool.used_len(), pool.free_len(), pool.allocation_count()),
+      (7, 7, 2)
+    );
+    assert_eq!(pool.chunk_count(), 4);
+
+    mem::drop(data);
+    assert_eq!((pool.allocation_count(), pool.chunk_count()), (1, 4));
+
+    let _rest = (pool.alloc(4).unwrap(), pool.alloc(7).unwrap());
+    assert!(pool.is_full());
+    assert_eq!(pool.available(), 0);
+  }
+
+  #[test]
+  fn pool_size_cap() {
+    let pool = SlicePool::new(vec![0; 32]).with_small_region(8, 2);
+    pool.set_size_cap(4..usize::MAX, Some(0.5));
+    let large = pool.alloc(12).unwrap();
+    assert_eq!(
+      pool.try_alloc(8).unwrap_err(),
+      AllocError::Capped { size: 8, limit: 16 }
+    );
+
+    // Allocations count towards the range of their current size
+    let data = pool.alloc(4).unwrap();
+    assert_eq!(pool.try_alloc(4).unwrap_err().kind(), ErrorCode::Capped);
+    let (_head, _tail) = data.split_at(2);
+    let _small = (pool.alloc(2).unwrap(), pool.alloc(3).unwrap());
+    assert!(pool.alloc(4).is_some());
+
+    mem::drop(large);
+    assert!(pool.alloc(8).is_some());
+
+    pool.set_size_cap(4..usize::MAX, None);
+    assert!(pool.alloc(4).is_some());
+  }
+
+  #[test]
+  fn pool_peak_usage() {
+    let pool = SlicePool::new(vec![0; 16]);
+    let data = pool.alloc(4).unwrap();
+    let (head, tail) = data.split_at(1);
+    let mut other = pool.alloc(2).unwrap();
+    assert!(other.grow(3));
+    mem::drop((head, tail, other));
+
+    let _data = pool.alloc(6).unwrap();
+    assert_eq!(pool.peak_usage(), PeakUsage { used: 9, live: 3 });
+    assert_eq!(pool.stats().used, 6);
+  }
+
+  #[test]
+  fn pool_validate() {
+    let pool = SlicePool::new(vec![0; 64])
+      .with_header(4)
+      .with_small_region(16, 2);
+    let small = pool.alloc(2).unwrap();
+    let (head, _tail) = pool.alloc(8).unwrap().split_at(3);
+    mem::drop((small, head));
+    assert_eq!(pool.validate(), Ok(()));
+  }
+
+  #[test]
+  fn pool_coalesce_both_neighbors() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let first = pool.alloc(2).unwrap();
+    let (second, third) = (pool.alloc(4).unwrap(), pool.alloc(2).unwrap());
+    mem::drop(third);
+    mem::drop(second);
+    assert_eq!(pool.alloc(6).unwrap().offset(), 2);
+    mem::drop(first);
+
+    // Releasing in any order leaves a single free chunk
+    let mut data = (0..8).map(|_| pool.alloc(1)).collect::<Vec<_>>();
+    for index in [3, 5, 0, 7, 1, 6, 2, 4] {
+      data[index].take().unwrap();
+      assert_eq!(pool.validate(), Ok(()));
+    }
+    assert_eq!(pool.alloc(8).unwrap().len(), 8);
+  }
+
+  #[test]
+  fn pool_with_alloc() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let _data = pool.alloc(1).unwrap();
+    let sum = pool.with_alloc(3, |scratch| {
+      assert_eq!(pool.stats().used, 4);
+      scratch.iter().sum::<i32>()
+    });
+    assert_eq!((sum, pool.stats().used), (Some(90), 1));
+    assert!(pool.with_alloc(4, |_| ()).is_none());
+
+    // The scratch slice is released while unwinding
+    let result = panic::catch_unwind(AssertUnwindSafe(|| pool.with_alloc(3, |_| panic!())));
+    assert!(result.is_err());
+    assert_eq!(pool.stats().used, 1);
+  }
+
+  #[test]
+  fn pool_zero_sized() {
+    let pool = SlicePool::new(vec![0; 4]);
+    let _data = pool.alloc(4).unwrap();
+    let empty = pool.alloc(0).unwrap();
+    assert!(empty.is_empty());
+    mem::drop(empty);
+    assert_eq!(pool.stats().used, 4);
+  }
+
+  #[test]
+  fn box_try_merge() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60]);
+    let first = pool.alloc(2).unwrap();
+    let second = pool.alloc(3).unwrap();
+    let third = pool.alloc(1).unwrap();
+
+    // Allocations are merged in either order
+    let (first, third) = first.try_merge(third).unwrap_err();
+    let data = second.try_merge(first).unwrap();
+    assert_eq!(*data, [10, 20, 30, 40, 50]);
+    let data = data.try_merge(third).unwrap();
+    assert_eq!(*data, [10, 20, 30, 40, 50, 60]);
+    assert_eq!(pool.region_stats().chain_allocations, 1);
+
+    mem::drop(data);
+    assert_eq!(pool.stats().used, 0);
+    assert!(pool.alloc(6).is_some());
+  }
+
+  #[test]
+  fn box_grow() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60]).with_small_region(2, 1);
+    let mut small = pool.alloc(1).unwrap();
+    assert!(small.grow(1));
+    assert_eq!(*small, [10, 20]);
+
+    let mut data = pool.alloc(1).unwrap();
+    let next = pool.alloc(1).unwrap();
+    assert!(!data.grow(1));
+    mem::drop(next);
+
+    // The allocation extends into the released memory
+    assert!(data.grow(2));
+    assert_eq!(*data, [30, 40, 50]);
+    assert!(!data.grow(2));
+    assert!(data.grow(1));
+    assert_eq!(pool.stats().used, 6);
+  }
+
+  #[test]
+  fn pool_vec() {
+    let pool = SlicePool::new(vec![0; 16]);
+    let mut values = ::PoolVec::new(&pool);
+    for value in 1..=3 {
+      values.push(value);
+    }
+    assert_eq!((values.len(), values.capacity()), (3, 4));
+
+    // The vector moves once the following memory is taken
+    let _data = pool.alloc(1).unwrap();
+    values.push(4);
+    values.push(5);
+    assert_eq!(*values, [1, 2, 3, 4, 5]);
+    assert_eq!(values.capacity(), 8);
+    assert!(values.try_push(6).is_ok());
+    assert_eq!(
+      values.try_reserve(8).unwrap_err().kind(),
+      ErrorCode::Exhausted
+    );
+
+    assert_eq!(values.pop(), Some(6));
+    values.truncate(1);
+    assert_eq!(*values, [1]);
+  }
+
+  #[test]
+  fn pool_string() {
+    let pool = SlicePool::new(vec![0u8; 8]);
+    let mut text = ::PoolString::new(&pool);
+    text.push_str("pool");
+    text.push('é');
+    assert_eq!(text.pop(), Some('é'));
+    assert!(text.try_push_str("overflow").is_err());
+    assert_eq!(text.as_str(), "pool");
+  }
+
+  #[test]
+  fn pool_prefault() {
+    let pool = SlicePool::new((0..10_000u32).collect::<Vec<_>>());
+    let mut data = pool.alloc(5_000).unwrap();
+    data.prefault();
+    pool.prefault_range(2_000..8_000);
+
+    // The contents are left unchanged
+    assert!(data.iter().copied().eq(0..5_000));
+    let rest = pool.alloc(5_000).unwrap();
+    assert!(rest.iter().copied().eq(5_000..10_000));
+  }
+
+  #[test]
+  fn box_shrink() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80]);
+    let mut data = pool.alloc(6).unwrap();
+    data.shrink(8);
+    assert_eq!(data.len(), 6);
+
+    data.shrink(2);
+    assert_eq!(*data, [10, 20]);
+    let rest = pool.alloc(6).unwrap();
+    assert_eq!(*rest, [30, 40, 50, 60, 70, 80]);
+  }
+
+  #[test]
+  fn box_into_raw() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]).with_small_region(2, 1);
+    let small = pool.alloc(1).unwrap();
+    let (small_ptr, small_len) = small.into_raw();
+    let data = pool.alloc(2).unwrap();
+    let (ptr, len) = data.into_raw();
+    assert_eq!(pool.stats().used, 3);
+
+    unsafe {
+      // Only live allocations of this pool are reconstructed
+      let other = SlicePool::new(vec![0; 4]);
+      assert!(other.slice_box_from_raw(ptr, len).is_none());
+      assert!(pool.slice_box_from_raw(ptr, 1).is_none());
+      assert!(pool.slice_box_from_raw(ptr.add(1), 1).is_none());
+
+      let small = pool.slice_box_from_raw(small_ptr, small_len).unwrap();
+      let data = pool.slice_box_from_raw(ptr, len).unwrap();
+      assert_eq!((&*small, &*data), (&[10][..], &[30, 40][..]));
+    }
+    assert_eq!(pool.stats().used, 0);
+  }
+
+  #[test]
+  #[cfg(feature = "secure")]
+  fn pool_secure() {
+    let pool = SlicePool::new_secure(vec![1u8; 64]).unwrap();
+    let mut key = pool.alloc(32).unwrap();
+    key.copy_from_slice(&[7; 32]);
+    mem::drop(key);
+
+    // Released elements are zeroed
+    let data = pool.alloc(64).unwrap();
+    assert!(data[..32].iter().all(|&byte| byte == 0));
+    assert!(data[32..].iter().all(|&byte| byte == 1));
+  }
+
+  #[test]
+  fn box_offset() {
+    let pool = SlicePool::new(vec![0; 8]).with_header(1);
+    let first = pool.alloc(2).unwrap();
+    let mut second = pool.alloc(3).unwrap();
+    second.release_head(1);
+    assert_eq!((first.offset(), second.offset()), (1, 4));
+
+    let other = SlicePool::new(vec![0; 8]);
+    assert!(first.same_pool(&second));
+    assert!(!first.same_pool(&other.alloc(1).unwrap()));
+  }
+
+  #[test]
+  fn pool_alloc_near() {
+    let pool = SlicePool::new(vec![0; 16]);
+    let boxes = (0..4).map(|_| pool.alloc(4).unwrap()).collect::<Vec<_>>();
+    let mut boxes = boxes.into_iter();
+    mem::drop(boxes.next());
+    let _second = boxes.next();
+    mem::drop(boxes.next());
+
+    // The allocation is carved from the middle of a free chunk
+    let near = pool.alloc_near(10, 1).unwrap();
+    assert_eq!(near.offset(), 10);
+    let before = pool.alloc_near(5, 1).unwrap();
+    let after = pool.alloc_near(15, 1).unwrap();
+    let between = pool.alloc_near(9, 2).unwrap();
+    assert_eq!(
+      (before.offset(), after.offset(), between.offset()),
+      (3, 11, 8)
+    );
+  }
+
+  #[test]
+  fn box_leak() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let data = pool.alloc(2).unwrap().leak();
+    assert_eq!(pool.stats().used, 2);
+    assert!(pool.alloc(3).is_none());
+
+    // The memory outlives the pool
+    mem::drop(pool);
+    data[1] = 25;
+    assert_eq!(*data, [10, 25]);
+  }
+
+  #[test]
+  fn box_into_pin() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let data = pool.alloc(2).unwrap();
+    let address = data.as_ptr();
+
+    let mut data = data.into_pin();
+    data.as_mut().get_mut()[0] = 15;
+    assert_eq!(data.as_ptr(), address);
+    assert_eq!(*data, [15, 20]);
+
+    // Pinned elements are dropped before their memory is allocated again
+    #[derive(Default)]
+    struct Pinned(Option<Arc<AtomicUsize>>, PhantomPinned);
+
+    impl Drop for Pinned {
+      fn drop(&mut self) {
+        if let Some(drops) = &self.0 {
+          drops.fetch_add(1, Ordering::SeqCst);
+        }
+      }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let pool = SlicePool::new(vec![
+      Pinned(Some(drops.clone()), PhantomPinned),
+      Pinned(Some(drops.clone()), PhantomPinned),
+    ]);
+    let data = pool.alloc(2).unwrap().into_pin();
+    mem::drop(data);
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+    assert!(pool.alloc(2).unwrap().iter().all(|value| value.0.is_none()));
+  }
+
+  #[cfg(feature = "stable-deref")]
+  #[test]
+  fn box_stable_deref() {
+    fn address<P: ::stable_deref_trait::StableDeref<Target = [i32]>>(data: P) -> *const i32 {
+      data.as_ptr()
+    }
+
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let data = pool.alloc(2).unwrap();
+    let expected = data.as_ptr();
+    assert_eq!(address(data), expected);
+  }
+
+  #[cfg(feature = "profiling")]
+  #[test]
+  fn pool_profiling_name() {
+    let name = ::std::ffi::CStr::from_bytes_with_nul(b"scratch\0").unwrap();
+    let pool = SlicePool::new(vec![0; 8]).with_profiling_name(name);
+
+    // Every change of a chunk is reported, including resizing it
+    let (mut head, tail) = pool.alloc(6).unwrap().split_at(2);
+    head.release_head(1);
+    assert!(head.grow(0));
+    let data = head.try_merge(tail).unwrap();
+    assert_eq!(pool.with_alloc(2, |scratch| scratch.len()), Some(2));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| pool.with_profiling_name(name)));
+    assert!(result.is_err());
+    mem::drop(data);
+  }
+
+  #[test]
+  fn box_slice() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60]);
+    let data = pool.alloc(5).unwrap().slice(1..3);
+    assert_eq!((&*data, data.offset()), (&[20, 30][..], 1));
+    assert_eq!(pool.stats().used, 5);
+
+    // The entire chunk is released with the view
+    mem::drop(data);
+    assert_eq!(pool.stats().used, 0);
+
+    let mut data = pool.alloc(5).unwrap().slice(1..=2);
+    assert!(data.grow(1));
+    assert_eq!(*data, [20, 30, 40]);
+    data.release_head(1);
+    assert_eq!((&*data, data.offset()), (&[30, 40][..], 2));
+    assert_eq!(pool.stats().used, 3);
+
+    let (head, tail) = data.split_at(1);
+    mem::drop(head);
+    assert_eq!((&*tail, pool.stats().used), (&[40][..], 2));
+  }
+
+  #[test]
+  fn pool_try_grow() {
+    let mut storage = Vec::with_capacity(8);
+    storage.extend_from_slice(&[10, 20, 30, 40]);
+
+    let mut pool = SlicePool::new_growable(storage);
+    let data = pool.alloc(4).unwrap();
+    assert!(pool.try_grow(2));
+    assert_eq!((pool.len(), pool.max_possible_alloc()), (6, 6));
+
+    let other = pool.alloc(2).unwrap();
+    assert_eq!((&*data, &*other), (&[10, 20, 30, 40][..], &[0, 0][..]));
+
+    // The vector cannot grow beyond its capacity without moving
+    assert!(!pool.try_grow(4));
+    mem::drop(other);
+    assert!(pool.try_grow(2));
+    assert_eq!(pool.chunks().len(), 2);
+    assert!(!SlicePool::new(vec![0; 4]).try_grow(1));
+  }
+
+  #[test]
+  fn box_detach() {
+    let pool = SlicePool::new(vec![0; 4]);
+    let data = pool.alloc(2).unwrap();
+    let (chunk, releaser) = data.detach();
+    assert_eq!(releaser.chunk(), chunk);
+    assert!(pool.is_allocated(&chunk));
+
+    // The release is handed off to another thread
+    thread::spawn(move || releaser.release()).join().unwrap();
+    assert!(!pool.is_allocated(&chunk));
+    assert_eq!(pool.stats().used, 0);
+
+    // Hidden elements are scrubbed by the handle
+    let mut pool = SlicePool::new(vec![String::from("a"), String::from("b")]);
+    pool.set_scrubbing(true);
+    let data = pool.alloc(2).unwrap().slice(1..);
+    let (_, releaser) = data.detach();
+    mem::drop(releaser);
+    assert!(pool.alloc(2).unwrap().iter().all(String::is_empty));
+  }
+
+  #[test]
+  fn pool_into_unsync() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let id = pool.id();
+    let data = pool.alloc(2).unwrap();
+    let Err(pool) = pool.into_unsync() else {
+      panic!("converted with a live allocation");
+    };
+    mem::drop(data);
+
+    let Ok(pool) = pool.into_unsync() else {
+      panic!("conversion failed");
+    };
+    let data = pool.alloc(2).unwrap();
+    assert_eq!((pool.id(), &*data), (id, &[10, 20][..]));
+    mem::drop(data);
+
+    // The pool is promoted back once it has been populated
+    let Ok(pool) = (unsafe { pool.into_sync() }) else {
+      panic!("conversion failed");
+    };
+    thread::spawn(move || assert_eq!(*pool.alloc(4).unwrap(), [10, 20, 30, 40]))
+      .join()
+      .unwrap();
+  }
+
+  #[test]
+  fn pool_chunks() {
+    let pool = SlicePool::new(vec![0; 12])
+      .with_header(1)
+      .with_small_region(4, 1);
+    let _small = pool.alloc(1).unwrap();
+    let small = pool.alloc(1).unwrap();
+    let _data = pool.alloc(3).unwrap();
+    mem::drop(small);
+
+    let chunk = |offset, len, state| ChunkInfo { offset, len, state };
+    assert_eq!(
+      pool.chunks().collect::<Vec<_>>(),
+      [
+        chunk(0, 1, ChunkState::Reserved),
+        chunk(1, 1, ChunkState::Allocated),
+        chunk(2, 3, ChunkState::Free),
+        chunk(5, 3, ChunkState::Allocated),
+        chunk(8, 4, ChunkState::Free),
+      ]
+    );
+    assert_eq!(pool.chunks().filter(ChunkInfo::is_free).count(), 2);
+  }
+
+  #[test]
+  fn box_raw_chunk() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let _val1 = pool.alloc(1).unwrap();
+    let mut val2 = pool.alloc(3).unwrap();
+
+    let raw = val2.as_raw_chunk();
+    assert_eq!((raw.offset, raw.len), (1, 3));
+    assert!(pool.is_allocated(&raw));
+
+    val2.release_tail(1);
+    assert!(!pool.is_allocated(&raw));
+    assert!(pool.is_allocated(&val2.as_raw_chunk()));
+
+    mem::drop(val2);
+    assert!(!pool.is_allocated(&RawChunk { len: 2, ..raw }));
+  }
+
+  #[test]
+  fn box_pool_ptr() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50]);
+    let val1 = pool.alloc(2).unwrap();
+    let mut val2 = pool.alloc(3).unwrap();
+
+    let ptr = val2.pool_ptr(1).unwrap();
+    assert_eq!(ptr.offset(), 3);
+    assert!(val2.pool_ptr(3).is_none());
+    assert!(val1.resolve(ptr).is_none());
+
+    *val2.resolve_mut(ptr).unwrap() = 45;
+    assert_eq!(val2.resolve(ptr), Some(&45));
+    assert_eq!(val1.resolve(val1.pool_ptr(0).unwrap()), Some(&10));
+  }
+
+  #[test]
+  fn box_byte_pool() {
+    let pool = SlicePool::new(vec![0u64; 4]);
+    let words = pool.alloc(2).unwrap();
+    assert_eq!(words.as_ptr() as usize % mem::align_of::<u64>(), 0);
+
+    let bytes = words.into_byte_pool();
+    assert_eq!(bytes.len(), 16);
+    assert!(bytes.alloc(17).is_none());
+
+    let mut val = bytes.alloc(3).unwrap();
+    val.copy_from_slice(&[1, 2, 3]);
+    assert!(pool.alloc(3).is_none());
+
+    // The chunk is only released once every byte allocation is gone
+    mem::drop(bytes);
+    assert!(pool.alloc(3).is_none());
+    mem::drop(val);
+    assert!(pool.alloc(3).is_some());
+  }
+
+  #[test]
+  fn box_partial_release() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80]);
+
+    let mut val1 = pool.alloc(6).unwrap();
+    let _val2 = pool.alloc(2).unwrap();
+    assert!(pool.alloc(1).is_none());
+
+    val1.release_head(2);
+    assert_eq!(*val1, [30, 40, 50, 60]);
+
+    val1.release_tail(1);
+    assert_eq!(*val1, [30, 40, 50]);
+
+    let val3 = pool.alloc(1).unwrap();
+    assert_eq!(*val3, [60]);
+
+    let val4 = pool.alloc(2).unwrap();
+    assert_eq!(*val4, [10, 20]);
+    assert!(pool.alloc(1).is_none());
+
+    // Released elements must merge back into a single chunk
+    mem::drop((val1, val3, val4));
+    assert_eq!(*pool.alloc(6).unwrap(), [10, 20, 30, 40, 50, 60]);
+  }
 }
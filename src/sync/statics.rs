@@ -0,0 +1,47 @@
+use super::ThreadSafe;
+use std::ops::Deref;
+use std::sync::OnceLock;
+use SlicePool;
+
+/// A thread-safe pool over a static arena, initialized on first use.
+///
+/// This is usually declared using the `static_pool!` macro.
+pub struct StaticPool<T: Send + Sync + 'static> {
+  arena: fn() -> &'static mut [T],
+  pool: OnceLock<SlicePool<T, ThreadSafe>>,
+}
+
+impl<T: Send + Sync + 'static> StaticPool<T> {
+  /// Constructs a new static pool from a function returning its arena.
+  ///
+  /// The function is invoked once, when the pool is first accessed.
+  pub const fn new(arena: fn() -> &'static mut [T]) -> Self {
+    StaticPool {
+      arena,
+      pool: OnceLock::new(),
+    }
+  }
+}
+
+impl<T: Send + Sync + 'static> Deref for StaticPool<T> {
+  type Target = SlicePool<T, ThreadSafe>;
+
+  fn deref(&self) -> &Self::Target {
+    self.pool.get_or_init(|| SlicePool::new((self.arena)()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  static_pool!(ARENA: [u32; 8]);
+  pool_layout!(8; HEADER = 2, BODY = 6);
+
+  #[test]
+  fn static_pool_layout() {
+    let header = ARENA.alloc(HEADER).unwrap();
+    let body = ARENA.alloc(BODY).unwrap();
+    assert_eq!(*header, [0, 0]);
+    assert_eq!(body.len(), 6);
+    assert!(ARENA.alloc(1).is_none());
+  }
+}
@@ -0,0 +1,329 @@
+use super::{SliceBox, SlicePool};
+use flavor::Lock;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+/// A clock used for ordering accesses to tiered allocations.
+static CLOCK: AtomicUsize = AtomicUsize::new(0);
+
+/// The storage tier currently holding an allocation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tier {
+  /// The primary (fast) pool.
+  Hot,
+  /// The secondary (spill) pool.
+  Cold,
+}
+
+/// An allocation and the tier it was allocated from.
+struct Placement<T: Send + 'static> {
+  tier: Tier,
+  data: SliceBox<T>,
+}
+
+/// The hot allocations of a tiered pool, keyed by the address of their slot.
+type Residents<T> = Mutex<HashMap<usize, Weak<Slot<T>>>>;
+
+/// The shared state of a tiered allocation.
+struct Slot<T: Send + 'static> {
+  data: Mutex<Placement<T>>,
+  accessed: AtomicUsize,
+  /// The residents of the pool, which the slot leaves once dropped.
+  residents: Weak<Residents<T>>,
+}
+
+impl<T: Send> Slot<T> {
+  fn touch(&self) {
+    self
+      .accessed
+      .store(CLOCK.fetch_add(1, Ordering::Relaxed), Ordering::Relaxed);
+  }
+
+  /// Returns the key of the slot among the residents.
+  fn key(&self) -> usize {
+    self as *const Self as usize
+  }
+}
+
+impl<T: Send + 'static> Drop for Slot<T> {
+  fn drop(&mut self) {
+    if let Some(residents) = self.residents.upgrade() {
+      Lock::lock(&*residents).remove(&self.key());
+    }
+  }
+}
+
+/// A thread-safe pool which spills allocations from a hot to a cold pool.
+///
+/// When the hot pool is exhausted, the least recently accessed hot
+/// allocations are demoted to the cold pool to make room. Allocations can
+/// also be moved between the tiers explicitly. Data is moved by swapping
+/// elements, so `T` does not need to be `Clone`.
+pub struct TieredPool<T: Send + 'static> {
+  hot: SlicePool<T>,
+  cold: SlicePool<T>,
+  residents: Arc<Residents<T>>,
+}
+
+impl<T: Send + 'static> TieredPool<T> {
+  /// Constructs a new tiered pool from a hot and a cold pool.
+  pub fn new(hot: SlicePool<T>, cold: SlicePool<T>) -> Self {
+    TieredPool {
+      hot,
+      cold,
+      residents: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+
+  /// Allocates a new slice, preferring the hot pool.
+  ///
+  /// If the hot pool is exhausted, hot allocations are demoted until the
+  /// request fits. If that fails, the slice is allocated in the cold pool.
+  pub fn alloc(&self, size: usize) -> Option<TieredBox<T>> {
+    let (tier, data) = match self.alloc_hot(size) {
+      Some(data) => (Tier::Hot, data),
+      None => (Tier::Cold, self.cold.alloc(size)?),
+    };
+
+    let slot = Arc::new(Slot {
+      data: Mutex::new(Placement { tier, data }),
+      accessed: AtomicUsize::new(0),
+      residents: Arc::downgrade(&self.residents),
+    });
+    slot.touch();
+
+    if tier == Tier::Hot {
+      self.register(&slot);
+    }
+
+    Some(TieredBox(slot))
+  }
+
+  /// Moves an allocation to the cold pool.
+  ///
+  /// Returns whether the allocation resides in the cold pool afterwards.
+  pub fn demote(&self, tiered: &TieredBox<T>) -> bool {
+    let mut placement = Lock::lock(&tiered.0.data);
+    let hot = placement.tier == Tier::Hot;
+    let demoted = self.move_to(&mut placement, Tier::Cold);
+    if hot && demoted {
+      self.unregister(&tiered.0);
+    }
+    demoted
+  }
+
+  /// Moves an allocation to the hot pool, demoting others if required.
+  ///
+  /// Returns whether the allocation resides in the hot pool afterwards.
+  pub fn promote(&self, tiered: &TieredBox<T>) -> bool {
+    let mut placement = Lock::lock(&tiered.0.data);
+    if placement.tier == Tier::Hot {
+      return true;
+    }
+
+    let size = placement.data.len();
+    match self.alloc_hot(size) {
+      Some(mut data) => {
+        data.swap_with_slice(&mut placement.data);
+        *placement = Placement {
+          tier: Tier::Hot,
+          data,
+        };
+        self.register(&tiered.0);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Returns the hot pool.
+  pub fn hot(&self) -> &SlicePool<T> {
+    &self.hot
+  }
+
+  /// Returns the cold pool.
+  pub fn cold(&self) -> &SlicePool<T> {
+    &self.cold
+  }
+
+  /// Allocates from the hot pool, demoting residents until the size fits.
+  fn alloc_hot(&self, size: usize) -> Option<SliceBox<T>> {
+    if size > self.hot.len() {
+      return None;
+    }
+
+    loop {
+      if let Some(data) = self.hot.alloc(size) {
+        return Some(data);
+      }
+
+      if !self.demote_coldest() {
+        return None;
+      }
+    }
+  }
+
+  /// Demotes the least recently accessed hot allocation.
+  fn demote_coldest(&self) -> bool {
+    // The residents are unlocked while the candidates are held, since the
+    // last reference to a slot may be dropped here, removing it.
+    let mut candidates = Lock::lock(&*self.residents)
+      .values()
+      .filter_map(Weak::upgrade)
+      .collect::<Vec<_>>();
+    candidates.sort_by_key(|slot| slot.accessed.load(Ordering::Relaxed));
+
+    candidates.iter().any(|slot| {
+      // Allocations which are currently accessed are never demoted
+      Lock::try_lock(&slot.data).is_some_and(|mut placement| {
+        let demoted = placement.tier == Tier::Hot && self.move_to(&mut placement, Tier::Cold);
+        if demoted {
+          self.unregister(slot);
+        }
+        demoted
+      })
+    })
+  }
+
+  /// Moves the data to another tier by allocating and swapping contents.
+  fn move_to(&self, placement: &mut Placement<T>, tier: Tier) -> bool {
+    if placement.tier == tier {
+      return true;
+    }
+
+    let pool = match tier {
+      Tier::Hot => &self.hot,
+      Tier::Cold => &self.cold,
+    };
+
+    match pool.alloc(placement.data.len()) {
+      Some(mut data) => {
+        data.swap_with_slice(&mut placement.data);
+        *placement = Placement { tier, data };
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Records a hot allocation, once, as a candidate for demotion.
+  fn register(&self, slot: &Arc<Slot<T>>) {
+    Lock::lock(&*self.residents).insert(slot.key(), Arc::downgrade(slot));
+  }
+
+  /// Removes an allocation which is no longer hot from the candidates.
+  fn unregister(&self, slot: &Slot<T>) {
+    Lock::lock(&*self.residents).remove(&slot.key());
+  }
+}
+
+/// An allocation in a `TieredPool`.
+///
+/// The data is accessed through a guard, since it may be moved to another
+/// tier whenever it is not borrowed.
+pub struct TieredBox<T: Send + 'static>(Arc<Slot<T>>);
+
+impl<T: Send> TieredBox<T> {
+  /// Locks the allocation for access, blocking until it is available.
+  pub fn lock(&self) -> TieredGuard<'_, T> {
+    let guard = Lock::lock(&self.0.data);
+    self.0.touch();
+    TieredGuard(guard)
+  }
+
+  /// Returns the tier currently holding the allocation.
+  pub fn tier(&self) -> Tier {
+    Lock::lock(&self.0.data).tier
+  }
+}
+
+impl<T: Send + fmt::Debug> fmt::Debug for TieredBox<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.lock().deref())
+  }
+}
+
+/// A guard providing access to the data of a `TieredBox`.
+pub struct TieredGuard<'a, T: Send + 'static>(MutexGuard<'a, Placement<T>>);
+
+impl<'a, T: Send> TieredGuard<'a, T> {
+  /// Returns the tier holding the allocation.
+  pub fn tier(&self) -> Tier {
+    self.0.tier
+  }
+}
+
+impl<'a, T: Send> Deref for TieredGuard<'a, T> {
+  type Target = [T];
+
+  fn deref(&self) -> &Self::Target {
+    &self.0.data
+  }
+}
+
+impl<'a, T: Send> DerefMut for TieredGuard<'a, T> {
+  fn deref_mut(&mut self) -> &mut [T] {
+    &mut self.0.data
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tiered_demotion() {
+    let hot = SlicePool::new(vec![0; 4]);
+    let cold = SlicePool::new(vec![0; 8]);
+    let pool = TieredPool::new(hot, cold);
+
+    let val1 = pool.alloc(2).unwrap();
+    val1.lock().copy_from_slice(&[1, 2]);
+
+    let val2 = pool.alloc(2).unwrap();
+    val2.lock().copy_from_slice(&[3, 4]);
+
+    // Touching the first allocation makes the second one the coldest
+    assert_eq!(*val1.lock(), [1, 2]);
+
+    let val3 = pool.alloc(2).unwrap();
+    assert_eq!(val1.tier(), Tier::Hot);
+    assert_eq!(val2.tier(), Tier::Cold);
+    assert_eq!(val3.tier(), Tier::Hot);
+    assert_eq!(*val2.lock(), [3, 4]);
+
+    assert!(pool.promote(&val2));
+    assert_eq!(val2.tier(), Tier::Hot);
+    assert_eq!(val1.tier(), Tier::Cold);
+    assert_eq!(*val1.lock(), [1, 2]);
+    assert_eq!(*val2.lock(), [3, 4]);
+
+    assert!(pool.demote(&val3));
+    assert_eq!(val3.tier(), Tier::Cold);
+  }
+
+  #[test]
+  fn tiered_residents() {
+    let pool = TieredPool::new(SlicePool::new(vec![0; 4]), SlicePool::new(vec![0; 4]));
+    let val1 = pool.alloc(2).unwrap();
+    let val2 = pool.alloc(2).unwrap();
+
+    // Only hot allocations are residents, each of them once
+    for _ in 0..8 {
+      assert!(pool.demote(&val1));
+      assert!(pool.promote(&val1));
+      assert!(pool.promote(&val1));
+    }
+    assert!(pool.demote(&val2));
+    assert_eq!(pool.residents.lock().unwrap().len(), 1);
+
+    // Dropped allocations leave the residents
+    drop(val1);
+    assert!(pool.residents.lock().unwrap().is_empty());
+    assert!(pool.promote(&val2));
+    assert_eq!(pool.residents.lock().unwrap().len(), 1);
+  }
+}
@@ -0,0 +1,166 @@
+use index::FreeLists;
+
+/// The logarithm of the number of second-level lists per first-level range.
+const SL_LOG2: u32 = 4;
+
+/// The number of second-level lists per first-level range.
+const SL_COUNT: usize = 1 << SL_LOG2;
+
+/// The number of first-level ranges, the first of which is linear.
+const FL_COUNT: usize = (usize::BITS - SL_LOG2 + 1) as usize;
+
+/// A two-level segregated fit index of free chunks.
+///
+/// Free chunks are kept in lists by size. The first level splits sizes into
+/// power-of-two ranges, and the second level splits each range linearly, so
+/// a list holds chunks whose sizes differ by less than 1/16th. Bitmaps
+/// record which lists are non-empty, so finding a list of large enough
+/// chunks, inserting and removing are all constant-time, except when only
+/// the list of the requested size may hold a chunk that fits.
+pub struct TlsfIndex {
+  /// The first-level ranges which have any non-empty list.
+  ranges: u64,
+  /// The non-empty second-level lists of each first-level range.
+  lists: [u16; FL_COUNT],
+  /// The free chunks, in each list.
+  chunks: FreeLists,
+}
+
+impl TlsfIndex {
+  pub fn new() -> Self {
+    TlsfIndex {
+      ranges: 0,
+      lists: [0; FL_COUNT],
+      chunks: FreeLists::new(FL_COUNT * SL_COUNT),
+    }
+  }
+
+  /// Adds a free chunk.
+  pub fn insert(&mut self, offset: usize, size: usize) {
+    let (fl, sl) = mapping(size);
+    self.chunks.insert(fl * SL_COUNT + sl, offset, size);
+    self.lists[fl] |= 1 << sl;
+    self.ranges |= 1 << fl;
+  }
+
+  /// Removes a free chunk.
+  pub fn remove(&mut self, offset: usize) {
+    let (list, empty) = self.chunks.remove(offset);
+    if empty {
+      let (fl, sl) = (list / SL_COUNT, list % SL_COUNT);
+      self.lists[fl] &= !(1 << sl);
+      if self.lists[fl] == 0 {
+        self.ranges &= !(1 << fl);
+      }
+    }
+  }
+
+  /// Returns the offset of a free chunk of at least `size` elements.
+  ///
+  /// The size is rounded up to the next list, so any chunk in a non-empty
+  /// list at or above it fits, and the last one added is used. Only if
+  /// there is none, the list of the size itself is scanned for a chunk
+  /// which fits, e.g. one of exactly the size.
+  pub fn find(&self, size: usize) -> Option<usize> {
+    let rounded = round_up(size).and_then(|size| {
+      let (fl, sl) = mapping(size);
+      self.next_list(fl, sl)
+    });
+    if let Some(list) = rounded {
+      return self.chunks.get(list).last().map(|&(offset, _)| offset);
+    }
+
+    let (fl, sl) = mapping(size);
+    self
+      .chunks
+      .get(fl * SL_COUNT + sl)
+      .iter()
+      .find(|&&(_, chunk)| chunk >= size)
+      .map(|&(offset, _)| offset)
+  }
+
+  /// Returns the first non-empty list at or above a list.
+  fn next_list(&self, fl: usize, sl: usize) -> Option<usize> {
+    let lists = self.lists[fl] & (!0 << sl);
+    if lists != 0 {
+      return Some(fl * SL_COUNT + lists.trailing_zeros() as usize);
+    }
+
+    let ranges = self.ranges & (!0u64).checked_shl(fl as u32 + 1).unwrap_or(0);
+    if ranges == 0 {
+      return None;
+    }
+
+    let fl = ranges.trailing_zeros() as usize;
+    Some(fl * SL_COUNT + self.lists[fl].trailing_zeros() as usize)
+  }
+}
+
+/// Returns the first- and second-level lists holding chunks of a size.
+fn mapping(size: usize) -> (usize, usize) {
+  if size < SL_COUNT {
+    return (0, size);
+  }
+
+  let msb = usize::BITS - 1 - size.leading_zeros();
+  let fl = (msb - SL_LOG2 + 1) as usize;
+  let sl = (size >> (msb - SL_LOG2)) - SL_COUNT;
+  (fl, sl)
+}
+
+/// Rounds a size up to the smallest size of the following list.
+fn round_up(size: usize) -> Option<usize> {
+  if size < SL_COUNT {
+    return Some(size);
+  }
+
+  let msb = usize::BITS - 1 - size.leading_zeros();
+  let step = (1 << (msb - SL_LOG2)) - 1;
+  size.checked_add(step).map(|size| size & !step)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tlsf_mapping() {
+    assert_eq!(mapping(7), (0, 7));
+    assert_eq!(mapping(16), (1, 0));
+    assert_eq!(mapping(31), (1, 15));
+    assert_eq!(mapping(100), (3, 9));
+    assert_eq!(mapping(usize::MAX), (FL_COUNT - 1, SL_COUNT - 1));
+
+    assert_eq!(round_up(33), Some(34));
+    assert_eq!(round_up(34), Some(34));
+    assert_eq!(round_up(usize::MAX), None);
+  }
+
+  #[test]
+  fn tlsf_find() {
+    let mut index = TlsfIndex::new();
+    index.insert(0, 4);
+    index.insert(10, 100);
+    index.insert(200, 35);
+    assert_eq!(index.find(4), Some(0));
+    assert_eq!(index.find(5), Some(200));
+    assert_eq!(index.find(36), Some(10));
+    assert_eq!(index.find(101), None);
+
+    // The list of the size itself is only scanned as a fallback
+    index.remove(10);
+    assert_eq!(index.find(34), Some(200));
+    assert_eq!(index.find(35), Some(200));
+    assert_eq!(index.find(36), None);
+    index.insert(300, 40);
+    assert_eq!(index.find(35), Some(300));
+    index.remove(300);
+
+    index.remove(200);
+    index.insert(200, 8);
+    assert_eq!(index.find(5), Some(200));
+    index.remove(0);
+    index.remove(200);
+    assert_eq!((index.ranges, index.find(1)), (0, None));
+  }
+}
@@ -0,0 +1,148 @@
+use flavor::Flavor;
+use std::alloc::Layout;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+use std::{fmt, mem, slice};
+use {SliceBox, SlicePool, ThreadSafe};
+
+impl<F: Flavor> SlicePool<u8, F> {
+  /// Allocates bytes satisfying the size and alignment of a layout.
+  ///
+  /// This is the building block for allocator adapters and `malloc`
+  /// shims. Any padding required for alignment is returned to the pool.
+  /// Zero-sized layouts are allocated a single byte, so every allocation
+  /// has a distinct address.
+  ///
+  /// ```
+  /// use slice_pool::sync::SlicePool;
+  /// use std::alloc::Layout;
+  ///
+  /// let pool = SlicePool::new(vec![0u8; 64]);
+  /// let layout = Layout::from_size_align(12, 8).unwrap();
+  /// let data = pool.alloc_for_layout(layout).unwrap();
+  /// assert_eq!((data.len(), data.as_ptr() as usize % 8), (12, 0));
+  /// ```
+  pub fn alloc_for_layout(&self, layout: Layout) -> Option<SliceBox<u8, F>> {
+    let len = layout.size().max(1);
+    let mut data = self.alloc(len.checked_add(layout.align() - 1)?)?;
+
+    // Trim the allocation to the aligned bytes
+    let padding = data.as_ptr().align_offset(layout.align());
+    let surplus = data.len() - padding - len;
+    if surplus > 0 {
+      data.release_tail(surplus);
+    }
+    if padding > 0 {
+      data.release_head(padding);
+    }
+    Some(data)
+  }
+
+  /// Allocates a suitably aligned array of `n` default values of `U`.
+  ///
+  /// The size and alignment are computed from the layout of `U`, and any
+  /// padding required for alignment is returned to the pool.
+  pub fn alloc_typed<U: Default>(&self, n: usize) -> Option<TypedSlice<U, F>> {
+    let layout = Layout::array::<U>(n).ok()?;
+    if layout.size() == 0 {
+      return Some(TypedSlice {
+        _data: self.alloc(1)?,
+        base: NonNull::dangling().as_ptr(),
+        len: n,
+      });
+    }
+
+    let mut data = self.alloc_for_layout(layout)?;
+    let base = data.as_mut_ptr() as *mut U;
+    for index in 0..n {
+      unsafe { ptr::write(base.add(index), U::default()) };
+    }
+
+    Some(TypedSlice {
+      _data: data,
+      base,
+      len: n,
+    })
+  }
+}
+
+/// A typed array allocated from a byte `SlicePool`.
+///
+/// The values are dropped when the array is dropped, after which the bytes
+/// are returned to the pool.
+pub struct TypedSlice<U, F: Flavor = ThreadSafe> {
+  _data: SliceBox<u8, F>,
+  base: *mut U,
+  len: usize,
+}
+
+unsafe impl<U: Send> Send for TypedSlice<U, ThreadSafe> {}
+unsafe impl<U: Sync> Sync for TypedSlice<U, ThreadSafe> {}
+
+impl<U, F: Flavor> Deref for TypedSlice<U, F> {
+  type Target = [U];
+
+  fn deref(&self) -> &[U] {
+    unsafe { slice::from_raw_parts(self.base, self.len) }
+  }
+}
+
+impl<U, F: Flavor> DerefMut for TypedSlice<U, F> {
+  fn deref_mut(&mut self) -> &mut [U] {
+    unsafe { slice::from_raw_parts_mut(self.base, self.len) }
+  }
+}
+
+impl<U, F: Flavor> Drop for TypedSlice<U, F> {
+  fn drop(&mut self) {
+    if mem::needs_drop::<U>() {
+      unsafe { ptr::drop_in_place(self.deref_mut()) };
+    }
+  }
+}
+
+impl<U: fmt::Debug, F: Flavor> fmt::Debug for TypedSlice<U, F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.deref())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use {sync, unsync};
+
+  #[test]
+  fn pool_typed_alloc() {
+    typed_alloc(sync::SlicePool::new(vec![0u8; 64]));
+    typed_alloc(unsync::SlicePool::new(vec![0u8; 64]));
+  }
+
+  fn typed_alloc<F: Flavor>(pool: SlicePool<u8, F>) {
+    let _offset = pool.alloc(1).unwrap();
+    let padding = pool.as_ptr().wrapping_add(1).align_offset(8);
+
+    let mut values = pool.alloc_typed::<u64>(3).unwrap();
+    assert_eq!(values.as_ptr() as usize % mem::align_of::<u64>(), 0);
+    assert_eq!(*values, [0, 0, 0]);
+    values[1] = u64::MAX;
+    assert_eq!(*values, [0, u64::MAX, 0]);
+
+    // Padding and surplus bytes are returned to the pool
+    let rest = pool.alloc(64 - 1 - padding - 24).unwrap();
+    let head = pool.alloc(padding).unwrap();
+    drop((values, rest, head));
+
+    let strings = pool.alloc_typed::<String>(2).unwrap();
+    assert!(strings.iter().all(String::is_empty));
+    assert!(pool.alloc_typed::<u64>(usize::MAX).is_none());
+    drop(strings);
+
+    let empty = pool.alloc_for_layout(Layout::from_size_align(0, 16).unwrap());
+    let empty = empty.unwrap();
+    assert_eq!((empty.len(), empty.as_ptr() as usize % 16), (1, 0));
+    assert!(pool
+      .alloc_for_layout(Layout::from_size_align(8, 64).unwrap())
+      .is_none());
+  }
+}
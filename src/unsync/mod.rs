@@ -1,93 +1,53 @@
 //! Unsynchronized memory pools.
 
-pub use self::owned::{SliceBox, SlicePool, Sliceable};
-use std::cell::RefCell;
-use Chunk;
+pub use self::owned::{Local, SliceBox, SlicePool, Sliceable};
 
 mod owned;
 
-enum Order {
-  Preceding,
-  Following,
-}
-
-/// A non thread-safe chunk chain.
-struct ChunkChain(RefCell<Vec<Chunk>>);
-
-impl ChunkChain {
-  pub fn new(size: usize) -> Self {
-    ChunkChain(RefCell::new(vec![Chunk::new(size)]))
-  }
-
-  pub fn allocate(&self, size: usize) -> Option<Chunk> {
-    let mut chunks = self.0.borrow_mut();
-
-    // Find a chunk with the least amount of memory required
-    let (index, _) = chunks
-      .iter()
-      .enumerate()
-      .filter(|(_, chunk)| chunk.free && chunk.size >= size)
-      .min_by_key(|(_, chunk)| chunk.size)?;
-
-    // Determine whether there is any memory surplus
-    let delta = chunks[index].size - size;
-
-    if delta > 0 {
-      // Deduct the left over memory from the allocation
-      chunks[index].size -= delta;
-
-      if Self::has_free_adjacent(&chunks, index, Order::Preceding) {
-        // Increase the size of the preceding chunk
-        chunks[index - 1].size += delta;
-
-        // Shift the offset of the allocated chunk
-        chunks[index].offset += delta;
-      } else if Self::has_free_adjacent(&chunks, index, Order::Following) {
-        // Update the size and offset of the next chunk
-        chunks[index + 1].offset -= delta;
-        chunks[index + 1].size += delta;
-      } else {
-        // Insert a new chunk representing the surplus memory
-        let offset = chunks[index].offset + size;
-        chunks.insert(index + 1, Chunk::with_offset(delta, offset));
-        chunks[index].free = false;
-      }
-    } else {
-      // The allocation covers a single chunk
-      chunks[index].free = false;
-    }
-
-    Some(chunks[index])
-  }
-
-  pub fn release(&self, offset: usize) {
-    let mut chunks = self.0.borrow_mut();
-
-    let index = chunks
-      .binary_search_by_key(&offset, |chunk| chunk.offset)
-      .expect("releasing chunk");
-    let size = chunks[index].size;
-
-    if Self::has_free_adjacent(&chunks, index, Order::Preceding) {
-      // Increase the preceding chunk's size
-      chunks[index - 1].size += size;
-    } else if Self::has_free_adjacent(&chunks, index, Order::Following) {
-      // Increase the extent of the next chunk
-      chunks[index + 1].offset -= size;
-      chunks[index + 1].size += size;
-    } else {
-      // No free adjacent chunks, simply mark this one as free
-      chunks[index].free = true;
-      return;
-    }
-
-    chunks.remove(index);
-  }
-
-  fn has_free_adjacent(chunks: &[Chunk], index: usize, order: Order) -> bool {
-    match order {
-      Order::Preceding => index > 0 && chunks[index - 1].free,
-      Order::Following => index + 1 < chunks.len() && chunks[index + 1].free,
-    }
-  }
-}
+/// A handle releasing a detached allocation of a non thread-safe `SlicePool`.
+pub type Releaser<T> = ::Releaser<T, Local>;
+
+/// A non thread-safe, shareable handle to an allocation in a `SlicePool`.
+pub type SharedBox<T> = ::SharedBox<T, Local>;
+
+/// An immutable borrow of a non thread-safe `SharedBox`.
+pub type SharedRef<'a, T> = ::SharedRef<'a, T, Local>;
+
+/// A mutable borrow of a non thread-safe `SharedBox`.
+pub type SharedRefMut<'a, T> = ::SharedRefMut<'a, T, Local>;
+
+/// A non thread-safe, copy-on-write handle to an allocation in a `SlicePool`.
+pub type CowSliceBox<'a, T> = ::CowSliceBox<'a, T, Local>;
+
+/// A growable vector stored in a non thread-safe `SlicePool`.
+pub type PoolVec<'a, T> = ::PoolVec<'a, T, Local>;
+
+/// A growable string stored in a non thread-safe byte `SlicePool`.
+pub type PoolString<'a> = ::PoolString<'a, Local>;
+
+/// A typed array allocated from a non thread-safe byte `SlicePool`.
+pub type TypedSlice<U> = ::TypedSlice<U, Local>;
+
+/// A length-prefixed frame allocated from a non thread-safe byte `SlicePool`.
+pub type FrameBox = ::FrameBox<Local>;
+
+/// An open allocation of a non thread-safe `SlicePool`, committed once appended.
+pub type OpenAlloc<'a, T> = ::OpenAlloc<'a, T, Local>;
+
+/// Elements which are either borrowed, on the heap, or in a non thread-safe `SlicePool`.
+pub type BorrowedOrPooled<'a, T> = ::BorrowedOrPooled<'a, T, Local>;
+
+/// Two non thread-safe allocations, of which one is presented while the other is prepared.
+pub type DoubleBuffer<T> = ::DoubleBuffer<T, Local>;
+
+/// A ring of non thread-safe allocations, passing data from a producer to a consumer.
+pub type SwapChain<T> = ::SwapChain<T, Local>;
+
+/// Elements of a non thread-safe `SlicePool` reserved for an allocation to grow into.
+pub type TailReservation<T> = ::TailReservation<T, Local>;
+
+/// A builder of a non thread-safe `SlicePool`.
+pub type SlicePoolBuilder<T> = ::SlicePoolBuilder<T, Local>;
+
+/// A reader of a stream into frames allocated from a non thread-safe byte `SlicePool`.
+pub type PooledReader<'a, R> = ::PooledReader<'a, R, Local>;
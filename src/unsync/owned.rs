@@ -1,7 +1,7 @@
-use super::ChunkChain;
-use std::ops::{Deref, DerefMut};
+use flavor::{Erase, Flavor, Sealed};
+use std::cell::RefCell;
 use std::rc::Rc;
-use std::{fmt, mem, slice};
+use {AllocPolicy, PoolError};
 
 /// Interface for any slice compatible with a non thread-safe `SlicePool`.
 pub trait Sliceable<T>: AsMut<[T]> + AsRef<[T]> {}
@@ -10,97 +10,77 @@ pub trait Sliceable<T>: AsMut<[T]> + AsRef<[T]> {}
 impl<T, V> Sliceable<T> for V where V: AsRef<[T]> + AsMut<[T]> {}
 
 /// A non thread-safe interface for allocating chunks in an owned slice.
-pub struct SlicePool<T> {
-  chain: Rc<ChunkChain>,
-  slice: Rc<Sliceable<T>>,
-}
+pub type SlicePool<T> = ::SlicePool<T, Local>;
 
-impl<T: 'static> SlicePool<T> {
-  /// Constructs a new owned slice pool from a sliceable object.
-  pub fn new<S: Sliceable<T> + 'static>(slice: S) -> Self {
-    let size = slice.as_ref().len();
+/// An allocation in a non thread-safe `SlicePool`.
+pub type SliceBox<T> = ::SliceBox<T, Local>;
 
-    SlicePool {
-      chain: Rc::new(ChunkChain::new(size)),
-      slice: Rc::new(slice),
-    }
-  }
+/// The flavor of non thread-safe pools, using `Rc` and `RefCell`.
+pub enum Local {}
 
-  /// Allocates a new slice from the pool.
-  pub fn alloc(&self, size: usize) -> Option<SliceBox<T>> {
-    let chunk = self.chain.allocate(size)?;
-
-    // The following code uses unsafe, and is the only occurring instance of it.
-    // Since the 'SliceBox' is a self-referential type, Rust does not allow us
-    // to express this with its current lifetime semantics. To avoid this
-    // restriction, the slice is transmuted to a static and mutable slice. It
-    // can be treated as static, since it's next to the 'Arc', which is keeping
-    // the data alive. It can also be treated as mutable since the 'SliceBox'
-    // becomes the only way to access the slice.
-    let data: &'static mut [T] = unsafe {
-      let offset = chunk.offset as isize;
-      let base = (*self.slice).as_ref().as_ptr().offset(offset);
-      slice::from_raw_parts_mut(base as *mut _, size)
-    };
+impl Sealed for Local {}
 
-    Some(SliceBox {
-      chain: self.chain.clone(),
-      slice: self.slice.clone(),
-      data,
-    })
+impl Flavor for Local {
+  type Ptr<U: ?Sized + 'static> = Rc<U>;
+  type Lock<U: 'static> = RefCell<U>;
+  type Slice<T: 'static> = dyn Sliceable<T>;
+  type Evict = dyn FnMut(usize) -> bool;
+  type Release = dyn FnMut();
+  type ErrorHook = dyn FnMut(PoolError);
+  type Policy = dyn AllocPolicy;
+
+  fn share<U: 'static>(value: U) -> Rc<U> {
+    Rc::new(value)
   }
 
-  /// Returns the address of the underlying slice.
-  pub fn as_ptr(&self) -> *const T {
-    (*self.slice).as_ref().as_ptr()
+  fn ptr_eq<U: ?Sized + 'static>(this: &Rc<U>, other: &Rc<U>) -> bool {
+    Rc::ptr_eq(this, other)
   }
 
-  /// Returns the size of the underlying slice.
-  pub fn len(&self) -> usize {
-    (*self.slice).as_ref().len()
+  fn try_unwrap<U: 'static>(ptr: Rc<U>) -> Result<U, Rc<U>> {
+    Rc::try_unwrap(ptr)
   }
-}
 
-/// An allocation in an owned `SlicePool`.
-pub struct SliceBox<T: 'static> {
-  #[allow(unused)]
-  slice: Rc<Sliceable<T>>,
-  chain: Rc<ChunkChain>,
-  data: &'static mut [T],
+  fn get_mut<U: ?Sized + 'static>(ptr: &mut Rc<U>) -> Option<&mut U> {
+    Rc::get_mut(ptr)
+  }
 }
 
-impl<T> Deref for SliceBox<T> {
-  type Target = [T];
+impl<T: 'static, S: Sliceable<T> + 'static> Erase<Local, dyn Sliceable<T>> for S {
+  fn erase(self) -> Box<dyn Sliceable<T>> {
+    Box::new(self)
+  }
+}
 
-  fn deref(&self) -> &Self::Target {
-    &self.data
+impl<E: FnMut(usize) -> bool + 'static> Erase<Local, dyn FnMut(usize) -> bool> for E {
+  fn erase(self) -> Box<dyn FnMut(usize) -> bool> {
+    Box::new(self)
   }
 }
 
-impl<T> DerefMut for SliceBox<T> {
-  fn deref_mut<'b>(&'b mut self) -> &'b mut [T] {
-    self.data
+impl<E: FnMut() + 'static> Erase<Local, dyn FnMut()> for E {
+  fn erase(self) -> Box<dyn FnMut()> {
+    Box::new(self)
   }
 }
 
-impl<T> Drop for SliceBox<T> {
-  /// Returns the ownership of the slice to the pool.
-  fn drop(&mut self) {
-    let base = (*self.slice).as_ref().as_ptr();
-    let diff = (self.data.as_ptr() as isize).wrapping_sub(base as isize);
-    self.chain.release(diff as usize / mem::size_of::<T>())
+impl<E: FnMut(PoolError) + 'static> Erase<Local, dyn FnMut(PoolError)> for E {
+  fn erase(self) -> Box<dyn FnMut(PoolError)> {
+    Box::new(self)
   }
 }
 
-impl<T: fmt::Debug> fmt::Debug for SliceBox<T> {
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "{:?}", self.deref())
+impl<P: AllocPolicy + 'static> Erase<Local, dyn AllocPolicy> for P {
+  fn erase(self) -> Box<dyn AllocPolicy> {
+    Box::new(self)
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use std::mem;
+  use {Order, RawChunk, RegionStats, Strategy, TaggedPoolPtr};
 
   #[test]
   fn pool_owned_lifetime() {
@@ -126,6 +106,84 @@ mod tests {
     assert_eq!(*alloc, [30]);
   }
 
+  #[test]
+  fn pool_contiguous() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let head = pool.alloc(2).unwrap();
+    let mid = pool.alloc(2).unwrap();
+    mem::drop(head);
+
+    let before = pool
+      .alloc_contiguous_with(&mid, 2, Order::Preceding)
+      .unwrap();
+    assert_eq!(before.as_ptr(), pool.as_ptr());
+    assert!(pool
+      .alloc_contiguous_with(&before, 1, Order::Preceding)
+      .is_none());
+
+    assert!(pool
+      .alloc_contiguous_with(&mid, 5, Order::Following)
+      .is_none());
+    let after = pool
+      .alloc_contiguous_with(&mid, 3, Order::Following)
+      .unwrap();
+    assert_eq!(after.as_ptr(), mid.as_ptr().wrapping_add(2));
+
+    // Allocations from another pool are never adjacent
+    let other = SlicePool::new(vec![0; 8]);
+    assert!(other
+      .alloc_contiguous_with(&after, 1, Order::Following)
+      .is_none());
+
+    mem::drop((after, mid, before));
+    assert!(pool.alloc(8).is_some());
+  }
+
+  #[test]
+  fn pool_small_region() {
+    let pool = SlicePool::new(vec![0; 16]).with_small_region(4, 2);
+    let small = pool.alloc(2).unwrap();
+    let large = pool.alloc(3).unwrap();
+    assert_eq!(small.as_ptr(), pool.as_ptr());
+    assert_eq!(large.as_ptr(), pool.as_ptr().wrapping_add(4));
+
+    // Small allocations use the chain once the region is full
+    let rest = pool.alloc(2).unwrap();
+    let spill = pool.alloc(1).unwrap();
+    assert_eq!(spill.as_ptr(), pool.as_ptr().wrapping_add(7));
+
+    let stats = RegionStats {
+      small_allocations: 2,
+      small_used: 4,
+      chain_allocations: 2,
+      chain_used: 4,
+    };
+    assert_eq!(pool.region_stats(), stats);
+
+    mem::drop((spill, large, small, rest));
+    assert!(pool.alloc(12).is_some());
+    assert!(pool.alloc(13).is_none());
+  }
+
+  #[test]
+  fn pool_shuffle() {
+    let layout = |seed| {
+      let pool = SlicePool::new(vec![0; 16]);
+      pool.set_shuffle_seed(seed);
+      let boxes = (0..8).map(|_| pool.alloc(2).unwrap()).collect::<Vec<_>>();
+      let base = pool.as_ptr();
+      boxes
+        .iter()
+        .map(|data| data.as_ptr() as usize - base as usize)
+        .collect::<Vec<_>>()
+    };
+
+    let regular = layout(None);
+    assert!(regular.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(layout(Some(7)), layout(Some(7)));
+    assert!((0..4).any(|seed| layout(Some(seed)) != regular));
+  }
+
   #[test]
   fn pool_fragmentation() {
     let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80, 90, 100]);
@@ -148,4 +206,223 @@ mod tests {
     let val5 = pool.alloc(4).unwrap();
     assert_eq!(*val5, [30, 40, 50, 60]);
   }
+
+  #[test]
+  fn pool_fast_path() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80]);
+
+    let val1 = pool.alloc(2).unwrap();
+    let val2 = pool.alloc(3).unwrap();
+    let _val3 = pool.alloc(1).unwrap();
+    mem::drop(val2);
+
+    let val4 = pool.alloc(3).unwrap();
+    assert_eq!(*val4, [30, 40, 50]);
+    assert_eq!(pool.fast_path_hits(), 1);
+
+    mem::drop(val1);
+    let val5 = pool.alloc(2).unwrap();
+    assert_eq!(*val5, [10, 20]);
+    assert_eq!(pool.fast_path_hits(), 2);
+
+    // The released chunk merges with its free neighbour
+    mem::drop(val4);
+    mem::drop(val5);
+    assert_eq!(*pool.alloc(3).unwrap(), [10, 20, 30]);
+    assert_eq!(pool.fast_path_hits(), 2);
+  }
+
+  #[test]
+  fn pool_strategy() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80]);
+    let _val1 = pool.alloc(2).unwrap();
+    let val2 = pool.alloc(3).unwrap();
+    let _val3 = pool.alloc(1).unwrap();
+    mem::drop(val2);
+
+    pool.set_strategy(Strategy::FirstFit);
+    assert_eq!(*pool.alloc(1).unwrap(), [30]);
+
+    pool.set_strategy(Strategy::BestFit);
+    assert_eq!(*pool.alloc(1).unwrap(), [70]);
+
+    pool.set_strategy(Strategy::Adaptive);
+    assert_eq!(pool.strategy(), Strategy::Adaptive);
+    assert_eq!(pool.active_strategy(), Strategy::FirstFit);
+  }
+
+  #[test]
+  fn pool_soft_limit() {
+    let pool = Rc::new(SlicePool::new(vec![0; 8]));
+    let cache = Rc::new(RefCell::new(Vec::new()));
+
+    let evicted = cache.clone();
+    pool.set_soft_limit(4, move |_| evicted.borrow_mut().pop().is_some());
+
+    for _ in 0..4 {
+      let value = pool.alloc(2).unwrap();
+      cache.borrow_mut().insert(0, value);
+    }
+
+    // The limit was exceeded, so the oldest allocations have been evicted
+    assert_eq!(cache.borrow_mut().len(), 2);
+    assert_eq!(pool.alloc(2).map(|value| value.len()), Some(2));
+
+    // Allocations fail once the callback has nothing left to evict
+    let evicted = cache.clone();
+    pool.set_soft_limit(0, move |_| evicted.borrow_mut().pop().is_some());
+    assert!(pool.alloc(9).is_none());
+    assert!(cache.borrow_mut().is_empty());
+  }
+
+  #[test]
+  fn pool_retain_allocations() {
+    let pool = SlicePool::new(vec![0; 8]);
+    let cache = Rc::new(RefCell::new(vec![pool.alloc(3).unwrap()]));
+    let state = Rc::new(RefCell::new(vec![pool.alloc(2).unwrap()]));
+
+    let held = cache.clone();
+    let id = pool.register_owner("cache", move || held.borrow_mut().clear());
+    let held = state.clone();
+    pool.register_owner("state", move || held.borrow_mut().clear());
+
+    assert_eq!(pool.retain_allocations(|tag| tag != "cache"), 3);
+    assert!(cache.borrow().is_empty());
+    assert_eq!(state.borrow().len(), 1);
+
+    assert!(pool.unregister_owner(id));
+    assert!(!pool.unregister_owner(id));
+    assert_eq!(pool.retain_allocations(|_| false), 2);
+  }
+
+  #[test]
+  fn pool_alloc_with() {
+    let pool = SlicePool::new(vec![0; 6]);
+
+    let val = pool.alloc_with(4, |init| {
+      for index in (0..init.len()).rev() {
+        init.write(index, index * 10);
+      }
+    });
+    assert_eq!(*val.unwrap(), [0, 10, 20, 30]);
+    assert!(pool.alloc_with(7, |_| unreachable!()).is_none());
+  }
+
+  #[test]
+  fn pool_scrubbing() {
+    let values = vec![String::from("a"), String::from("b"), String::from("c")];
+    let mut pool = SlicePool::new(values);
+    pool.set_scrubbing(true);
+
+    let mut val1 = pool.alloc(3).unwrap();
+    val1.release_tail(1);
+    assert_eq!(*val1, ["a", "b"]);
+    mem::drop(val1);
+
+    let val2 = pool.alloc(3).unwrap();
+    assert!(val2.iter().all(String::is_empty));
+  }
+
+  #[test]
+  fn pool_tag_bits() {
+    let mut pool = SlicePool::new(vec![0u16; 64]);
+    let _offset = pool.alloc(1).unwrap();
+    pool.set_tag_bits(3);
+
+    let mut data = pool.alloc(5).unwrap();
+    assert_eq!(data.len(), 5);
+    assert_eq!(data.as_ptr() as usize % 8, 0);
+
+    let ptr = data.as_mut_ptr();
+    let tagged = TaggedPoolPtr::<u16, 3>::new(ptr, 5).unwrap();
+    assert_eq!((tagged.ptr(), tagged.tag()), (ptr, 5));
+    assert_eq!(tagged.with_tag(2).unwrap().ptr(), ptr);
+    assert!(tagged.with_tag(8).is_none());
+    assert!(TaggedPoolPtr::<u16, 3>::new(ptr.wrapping_add(1), 0).is_none());
+
+    // The padding is returned to the pool
+    assert_eq!(data.as_raw_chunk().len, 5);
+    assert!(pool.is_allocated(&data.as_raw_chunk()));
+  }
+
+  #[test]
+  fn box_raw_chunk() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40]);
+    let _val1 = pool.alloc(1).unwrap();
+    let mut val2 = pool.alloc(3).unwrap();
+
+    let raw = val2.as_raw_chunk();
+    assert_eq!((raw.offset, raw.len), (1, 3));
+    assert!(pool.is_allocated(&raw));
+
+    val2.release_tail(1);
+    assert!(!pool.is_allocated(&raw));
+    assert!(pool.is_allocated(&val2.as_raw_chunk()));
+
+    mem::drop(val2);
+    assert!(!pool.is_allocated(&RawChunk { len: 2, ..raw }));
+  }
+
+  #[test]
+  fn box_pool_ptr() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50]);
+    let val1 = pool.alloc(2).unwrap();
+    let mut val2 = pool.alloc(3).unwrap();
+
+    let ptr = val2.pool_ptr(1).unwrap();
+    assert_eq!(ptr.offset(), 3);
+    assert!(val2.pool_ptr(3).is_none());
+    assert!(val1.resolve(ptr).is_none());
+
+    *val2.resolve_mut(ptr).unwrap() = 45;
+    assert_eq!(val2.resolve(ptr), Some(&45));
+    assert_eq!(val1.resolve(val1.pool_ptr(0).unwrap()), Some(&10));
+  }
+
+  #[test]
+  fn box_byte_pool() {
+    let pool = SlicePool::new(vec![0u64; 4]);
+    let words = pool.alloc(2).unwrap();
+    assert_eq!(words.as_ptr() as usize % mem::align_of::<u64>(), 0);
+
+    let bytes = words.into_byte_pool();
+    assert_eq!(bytes.len(), 16);
+    assert!(bytes.alloc(17).is_none());
+
+    let mut val = bytes.alloc(3).unwrap();
+    val.copy_from_slice(&[1, 2, 3]);
+    assert!(pool.alloc(3).is_none());
+
+    // The chunk is only released once every byte allocation is gone
+    mem::drop(bytes);
+    assert!(pool.alloc(3).is_none());
+    mem::drop(val);
+    assert!(pool.alloc(3).is_some());
+  }
+
+  #[test]
+  fn box_partial_release() {
+    let pool = SlicePool::new(vec![10, 20, 30, 40, 50, 60, 70, 80]);
+
+    let mut val1 = pool.alloc(6).unwrap();
+    let _val2 = pool.alloc(2).unwrap();
+    assert!(pool.alloc(1).is_none());
+
+    val1.release_head(2);
+    assert_eq!(*val1, [30, 40, 50, 60]);
+
+    val1.release_tail(1);
+    assert_eq!(*val1, [30, 40, 50]);
+
+    let val3 = pool.alloc(1).unwrap();
+    assert_eq!(*val3, [60]);
+
+    let val4 = pool.alloc(2).unwrap();
+    assert_eq!(*val4, [10, 20]);
+    assert!(pool.alloc(1).is_none());
+
+    // Released elements must merge back into a single chunk
+    mem::drop((val1, val3, val4));
+    assert_eq!(*pool.alloc(6).unwrap(), [10, 20, 30, 40, 50, 60]);
+  }
 }